@@ -0,0 +1,312 @@
+use crate::ast::expression::{Expression, LiteralValue};
+use crate::ast::statement::Statement;
+use crate::lexer::token::Token;
+
+/// Renders an expression tree as a Graphviz digraph.
+pub fn to_dot(expression: &Expression) -> String {
+    let mut writer = DotWriter::new();
+    writer.expression(expression);
+    writer.finish()
+}
+
+/// Renders a whole program as one digraph, with a tree per top-level
+/// statement.
+pub fn program_to_dot(statements: &[Statement]) -> String {
+    let mut writer = DotWriter::new();
+    for statement in statements {
+        writer.statement(statement);
+    }
+    writer.finish()
+}
+
+/// Numbers nodes in the order they're visited, parents before children, so
+/// the same tree always gets the same IDs.
+struct DotWriter {
+    body: String,
+    next_id: usize,
+}
+
+impl DotWriter {
+    fn new() -> Self {
+        Self {
+            body: String::new(),
+            next_id: 0,
+        }
+    }
+
+    fn finish(self) -> String {
+        format!("digraph ast {{\n  node [shape=box];\n{}}}\n", self.body)
+    }
+
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.body
+            .push_str(&format!("  n{} [label=\"{}\"];\n", id, escape(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.body.push_str(&format!("  n{} -> n{};\n", from, to));
+    }
+
+    /// Writes a tree with a stack of nodes still to visit rather than by
+    /// recursion, so a very deep one renders without overflowing the call
+    /// stack. Returns the root's ID.
+    fn tree(&mut self, root: Part) -> usize {
+        let root_id = self.next_id;
+        let mut stack = vec![Step::Visit(root, None)];
+        while let Some(step) = stack.pop() {
+            let (part, parent) = match step {
+                Step::Edge(from, to) => {
+                    self.edge(from, to);
+                    continue;
+                }
+                Step::Visit(part, parent) => (part, parent),
+            };
+            let (label, children) = parts(part);
+            let id = self.node(&label);
+            // A match arm's edge comes straight after it, a child's only
+            // once the child's own subtree has been written.
+            match (parent, part) {
+                (Some(parent), Part::Arm(..) | Part::Default(_)) => self.edge(parent, id),
+                (Some(parent), _) => stack.push(Step::Edge(parent, id)),
+                (None, _) => {}
+            }
+            stack.extend(
+                children
+                    .into_iter()
+                    .rev()
+                    .map(|child| Step::Visit(child, Some(id))),
+            );
+        }
+
+        root_id
+    }
+
+    fn expression(&mut self, expression: &Expression) -> usize {
+        self.tree(Part::Expression(expression))
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        self.tree(Part::Statement(statement))
+    }
+}
+
+/// Anything that gets a node of its own: an expression, a statement, or one
+/// arm of a `match`.
+#[derive(Clone, Copy)]
+enum Part<'a> {
+    Expression(&'a Expression),
+    Statement(&'a Statement),
+    Arm(&'a Expression, &'a Statement),
+    Default(&'a Statement),
+}
+
+enum Step<'a> {
+    Visit(Part<'a>, Option<usize>),
+    Edge(usize, usize),
+}
+
+/// A part's label and the parts below it, in order.
+fn parts(part: Part) -> (String, Vec<Part>) {
+    use Part::{Expression as E, Statement as S};
+
+    match part {
+        Part::Expression(expression) => match expression {
+            Expression::ArrayLiteral { elements, .. } => {
+                ("array".to_string(), elements.iter().map(E).collect())
+            }
+            Expression::Assign { name, value, .. } => {
+                (format!("= {}", name.lexeme), vec![E(value)])
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            }
+            | Expression::Logical {
+                left,
+                operator,
+                right,
+            } => (operator.lexeme.clone(), vec![E(left), E(right)]),
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                let mut children = vec![E(callee)];
+                children.extend(arguments.iter().map(E));
+                ("call".to_string(), children)
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => (
+                "?:".to_string(),
+                vec![E(condition), E(then_branch), E(else_branch)],
+            ),
+            Expression::Get { object, name } => (format!("get {}", name.lexeme), vec![E(object)]),
+            Expression::Grouping { expression, .. } => ("group".to_string(), vec![E(expression)]),
+            Expression::Index { object, index, .. } => {
+                ("index".to_string(), vec![E(object), E(index)])
+            }
+            Expression::Lambda { params, body, .. } => (
+                format!("lambda ({})", names(params)),
+                body.iter().map(S).collect(),
+            ),
+            Expression::Literal { value, .. } => match value {
+                LiteralValue::StringValue(s) => (format!("\"{}\"", s), vec![]),
+                value => (value.to_string(), vec![]),
+            },
+            Expression::Set {
+                object,
+                name,
+                value,
+            } => (format!("set {}", name.lexeme), vec![E(object), E(value)]),
+            Expression::Super { method, .. } => (format!("super {}", method.lexeme), vec![]),
+            Expression::This { .. } => ("this".to_string(), vec![]),
+            Expression::Unary { operator, right } => (operator.lexeme.clone(), vec![E(right)]),
+            Expression::Variable { name, .. } => (name.lexeme.clone(), vec![]),
+        },
+        Part::Statement(statement) => match statement {
+            Statement::Block(statements) => {
+                ("block".to_string(), statements.iter().map(S).collect())
+            }
+            Statement::Break { .. } => ("break".to_string(), vec![]),
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let mut children: Vec<Part> = superclass.iter().map(E).collect();
+                children.extend(methods.iter().map(S));
+                (format!("class {}", name.lexeme), children)
+            }
+            Statement::Const { name, initializer } => {
+                (format!("const {}", name.lexeme), vec![E(initializer)])
+            }
+            Statement::Continue { .. } => ("continue".to_string(), vec![]),
+            Statement::DoWhile { body, condition } => {
+                ("do-while".to_string(), vec![S(body), E(condition)])
+            }
+            Statement::Expression(expression) => ("expr".to_string(), vec![E(expression)]),
+            Statement::Function { name, params, body } => (
+                format!("fun {} ({})", name.lexeme, names(params)),
+                body.iter().map(S).collect(),
+            ),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let mut children = vec![E(condition), S(then_branch)];
+                children.extend(else_branch.iter().map(|branch| S(branch)));
+                ("if".to_string(), children)
+            }
+            Statement::Match {
+                subject,
+                arms,
+                default,
+            } => {
+                let mut children = vec![E(subject)];
+                children.extend(arms.iter().map(|(pattern, body)| Part::Arm(pattern, body)));
+                children.extend(default.iter().map(|default| Part::Default(default)));
+                ("match".to_string(), children)
+            }
+            Statement::Return { value, .. } => {
+                ("return".to_string(), value.iter().map(E).collect())
+            }
+            Statement::Say(expression) => ("say".to_string(), vec![E(expression)]),
+            Statement::Var { name, initializer } => (
+                format!("var {}", name.lexeme),
+                initializer.iter().map(E).collect(),
+            ),
+            Statement::While { condition, body } => {
+                ("while".to_string(), vec![E(condition), S(body)])
+            }
+        },
+        Part::Arm(pattern, body) => ("=>".to_string(), vec![E(pattern), S(body)]),
+        Part::Default(body) => ("else".to_string(), vec![S(body)]),
+    }
+}
+
+fn names(params: &[Token]) -> String {
+    let names: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+    names.join(" ")
+}
+
+/// Escapes a label for a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parser::Parser;
+    use crate::lexer::lexer::Lexer;
+
+    fn dot(source: &str) -> String {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        to_dot(&Parser::new(tokens).parse_expression().unwrap())
+    }
+
+    #[test]
+    fn test_expression_to_dot() {
+        let output = dot("1 + 2 * -x");
+
+        assert!(output.starts_with("digraph ast {\n"));
+        assert!(output.ends_with("}\n"));
+        assert!(output.contains("n0 [label=\"+\"];"));
+        assert!(output.contains("n1 [label=\"1\"];"));
+        assert!(output.contains("n2 [label=\"*\"];"));
+        assert!(output.contains("n4 [label=\"-\"];"));
+        assert!(output.contains("n5 [label=\"x\"];"));
+        assert_eq!(output.matches(" -> ").count(), 5);
+        assert!(output.contains("n0 -> n2;"));
+    }
+
+    #[test]
+    fn test_dot_is_stable() {
+        assert_eq!(dot("f(a, [1, 2])"), dot("f(a, [1, 2])"));
+    }
+
+    #[test]
+    fn test_dot_escapes_strings() {
+        let output = dot(r#""say \"hi\"\n" + s"#);
+
+        assert!(output.contains(r#"n1 [label="\"say \"hi\"\n\""];"#));
+    }
+
+    #[test]
+    fn test_dot_long_chain() {
+        let output = dot(&format!("1{}", " + 1".repeat(100_000)));
+
+        assert!(output.contains("n0 [label=\"+\"];"));
+        assert!(output.ends_with("  n0 -> n1;\n  n200000 [label=\"1\"];\n  n0 -> n200000;\n}\n"));
+        assert_eq!(output.matches(" -> ").count(), 200_000);
+    }
+
+    #[test]
+    fn test_program_to_dot() {
+        let tokens = Lexer::new("var x = 1; if (x) say x;")
+            .scan_tokens()
+            .unwrap();
+        let output = program_to_dot(&Parser::new(tokens).parse_program().unwrap());
+
+        assert!(output.contains("n0 [label=\"var x\"];"));
+        assert!(output.contains("n2 [label=\"if\"];"));
+        assert!(output.contains("n4 [label=\"say\"];"));
+        assert_eq!(output.matches(" -> ").count(), 4);
+    }
+}
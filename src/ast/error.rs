@@ -0,0 +1,57 @@
+use crate::lexer::token::{Token, TokenType};
+
+/// A parse error, reported at the token where parsing went wrong.
+#[derive(Debug, Clone)]
+pub struct ParserError {
+    pub message: String,
+    pub token: Token,
+}
+
+impl ParserError {
+    pub fn new(token: Token, message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            token,
+        }
+    }
+}
+
+impl std::fmt::Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.token.token_type == TokenType::Eof {
+            write!(
+                f,
+                "[line {}] Error at end: {}.",
+                self.token.line_number, self.message
+            )
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}.",
+                self.token.line_number, self.token.lexeme, self.message
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let token = Token::new(TokenType::RightParen, ")".to_string(), None, 4, 7, (30, 31));
+        let error = ParserError::new(token, "Expected expression");
+        assert_eq!(
+            error.to_string(),
+            "[line 4] Error at ')': Expected expression."
+        );
+
+        let token = Token::new(TokenType::Eof, "".to_string(), None, 9, 1, (80, 80));
+        let error = ParserError::new(token, "Expected ';' after value");
+        assert_eq!(
+            error.to_string(),
+            "[line 9] Error at end: Expected ';' after value."
+        );
+    }
+}
@@ -1,97 +1,819 @@
-use crate::lexer::token::{LiteralValue as TokenLiteralValue, Token, TokenType};
+use crate::ast::statement::Statement;
+use crate::lexer::token::{json_string, LiteralValue as TokenLiteralValue, Token, TokenType};
 
+/// Numbers compare as `f64` does, so a NaN is never equal to anything, even
+/// itself. Number literals can't be NaN, so this only matters for trees built
+/// by hand.
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
-    Number(f32),
+    /// A number written without a fraction or exponent, like `5`.
+    Integer(i64),
+    Number(f64),
     StringValue(String),
     True,
     False,
     Nil,
 }
 
-fn unwrap_as_f32(literal: Option<TokenLiteralValue>) -> f32 {
+fn unwrap_as_number(literal: Option<TokenLiteralValue>) -> Result<LiteralValue, String> {
     match literal {
-        Some(TokenLiteralValue::IntValue(x)) => x as f32,
-        Some(TokenLiteralValue::FValue(x)) => x as f32,
-        _ => panic!("Couldn't unwrap as f32"),
+        Some(TokenLiteralValue::IntValue(x)) => Ok(LiteralValue::Integer(x)),
+        Some(TokenLiteralValue::FValue(x)) => Ok(LiteralValue::Number(x)),
+        other => Err(format!("Couldn't unwrap {:?} as a number", other)),
     }
 }
 
-fn unwrap_as_string(literal: Option<TokenLiteralValue>) -> String {
+fn unwrap_as_string(literal: Option<TokenLiteralValue>) -> Result<String, String> {
     match literal {
-        Some(TokenLiteralValue::StringValue(s)) => s.clone(),
-        Some(TokenLiteralValue::IdentifierValue(s)) => s.clone(),
-        _ => panic!("Could not unwrap as string"),
+        Some(TokenLiteralValue::StringValue(s)) => Ok(s),
+        Some(TokenLiteralValue::IdentifierValue(s)) => Ok(s),
+        other => Err(format!("Couldn't unwrap {:?} as a string", other)),
+    }
+}
+
+impl std::fmt::Display for LiteralValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LiteralValue::Integer(x) => write!(f, "{}", x),
+            // Keep a whole float looking like one, so `5.0` isn't shown as `5`.
+            LiteralValue::Number(x) if x.is_finite() && x.fract() == 0.0 => write!(f, "{:.1}", x),
+            LiteralValue::Number(x) => write!(f, "{}", x),
+            LiteralValue::StringValue(x) => write!(f, "{}", x),
+            LiteralValue::True => write!(f, "True"),
+            LiteralValue::False => write!(f, "False"),
+            LiteralValue::Nil => write!(f, "Nil"),
+        }
     }
 }
 
 impl LiteralValue {
-    pub fn to_string(&self) -> String {
+    /// The name of the value's type, as error messages give it.
+    pub fn type_name(&self) -> &'static str {
         match self {
-            LiteralValue::Number(x) => x.to_string(),
-            LiteralValue::StringValue(x) => x.clone(),
-            LiteralValue::True => "True".to_string(),
-            LiteralValue::False => "False".to_string(),
-            LiteralValue::Nil => "Nil".to_string(),
+            LiteralValue::Integer(_) | LiteralValue::Number(_) => "number",
+            LiteralValue::StringValue(_) => "string",
+            LiteralValue::True | LiteralValue::False => "bool",
+            LiteralValue::Nil => "nil",
+        }
+    }
+
+    /// Only `Nil` and `False` are falsey. Everything else is truthy,
+    /// including `0` and the empty string.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, LiteralValue::Nil | LiteralValue::False)
+    }
+
+    /// Values of different types are never equal, except that integers and
+    /// floats compare by value, so `1 == 1.0`. `Nil` is only equal to itself.
+    pub fn is_equal(&self, other: &LiteralValue) -> bool {
+        match (self, other) {
+            (LiteralValue::Integer(a), LiteralValue::Integer(b)) => a == b,
+            (LiteralValue::Integer(a), LiteralValue::Number(b))
+            | (LiteralValue::Number(b), LiteralValue::Integer(a)) => *a as f64 == *b,
+            (LiteralValue::Number(a), LiteralValue::Number(b)) => a == b,
+            (LiteralValue::StringValue(a), LiteralValue::StringValue(b)) => a == b,
+            (LiteralValue::True, LiteralValue::True)
+            | (LiteralValue::False, LiteralValue::False)
+            | (LiteralValue::Nil, LiteralValue::Nil) => true,
+            _ => false,
         }
     }
 
-    pub fn from_token(token: Token) -> Self {
+    /// JSON has no infinities, so a non-finite number, which only folding
+    /// can produce, becomes `null`.
+    fn to_json(&self) -> String {
+        match self {
+            LiteralValue::Integer(x) => x.to_string(),
+            LiteralValue::Number(x) if x.is_finite() => x.to_string(),
+            LiteralValue::Number(_) | LiteralValue::Nil => "null".to_string(),
+            LiteralValue::StringValue(s) => json_string(s),
+            LiteralValue::True => "true".to_string(),
+            LiteralValue::False => "false".to_string(),
+        }
+    }
+
+    /// Fails if the token isn't a literal, or its literal value doesn't
+    /// match its type, which only a malformed token stream can cause.
+    pub fn from_token(token: Token) -> Result<Self, String> {
         match token.token_type {
-            TokenType::Number => Self::Number(unwrap_as_f32(token.literal)),
-            TokenType::StringLiteral => Self::StringValue(unwrap_as_string(token.literal)),
-            TokenType::False => Self::False,
-            TokenType::True => Self::True,
-            TokenType::Nil => Self::Nil,
-            _ => panic!("Couldn't create LiteralValue from {:?}", token),
+            TokenType::Number => unwrap_as_number(token.literal),
+            TokenType::StringLiteral => Ok(Self::StringValue(unwrap_as_string(token.literal)?)),
+            TokenType::False => Ok(Self::False),
+            TokenType::True => Ok(Self::True),
+            TokenType::Nil => Ok(Self::Nil),
+            _ => Err(format!(
+                "Couldn't create a literal from {:?} '{}'",
+                token.token_type, token.lexeme
+            )),
         }
     }
 }
 
+/// Every node keeps a token to locate it in the source; see `line`.
+#[derive(Debug, PartialEq)]
 pub enum Expression {
+    /// `bracket` is the opening `[`.
+    ArrayLiteral {
+        bracket: Token,
+        elements: Vec<Expression>,
+    },
+    /// `distance`, like that of `Variable`, is how many scopes out from the
+    /// innermost one the variable lives. The resolver fills it in; `None`
+    /// means a global.
+    Assign {
+        name: Token,
+        value: Box<Expression>,
+        distance: Option<usize>,
+    },
     Binary {
         left: Box<Expression>,
         operator: Token,
         right: Box<Expression>,
     },
+    /// `paren` is the closing parenthesis, kept to report errors at the call.
+    Call {
+        callee: Box<Expression>,
+        paren: Token,
+        arguments: Vec<Expression>,
+    },
+    Conditional {
+        condition: Box<Expression>,
+        question: Token,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
+    },
+    Get {
+        object: Box<Expression>,
+        name: Token,
+    },
+    /// `paren` is the opening parenthesis.
     Grouping {
+        paren: Token,
         expression: Box<Expression>,
     },
+    /// `bracket` is the opening `[`.
+    Index {
+        object: Box<Expression>,
+        bracket: Token,
+        index: Box<Expression>,
+    },
+    /// `start` is the `fun`, or the `(` of a lambda written without it. An
+    /// `->` body is stored as a single return statement.
+    Lambda {
+        start: Token,
+        params: Vec<Token>,
+        body: Vec<Statement>,
+    },
     Literal {
+        token: Token,
         value: LiteralValue,
     },
+    /// `and` / `or`, kept apart from Binary because they short-circuit.
+    Logical {
+        left: Box<Expression>,
+        operator: Token,
+        right: Box<Expression>,
+    },
+    Set {
+        object: Box<Expression>,
+        name: Token,
+        value: Box<Expression>,
+    },
+    /// `distance` is that of the scope holding `super`.
+    Super {
+        keyword: Token,
+        method: Token,
+        distance: Option<usize>,
+    },
+    This {
+        keyword: Token,
+        distance: Option<usize>,
+    },
     Unary {
         operator: Token,
         right: Box<Expression>,
     },
+    Variable {
+        name: Token,
+        distance: Option<usize>,
+    },
 }
 
 impl Expression {
-    pub fn to_string(&self) -> String {
+    /// The line the node is on; see `token`.
+    pub fn line(&self) -> usize {
+        self.token().line_number
+    }
+
+    /// The token locating the node: its operator or keyword where it has
+    /// one, so errors point at the part of the code that failed.
+    pub fn token(&self) -> &Token {
         match self {
-            Expression::Binary {
-                left,
-                operator,
-                right,
-            } => format!(
-                "({} {} {})",
-                operator.lexeme,
-                left.to_string(),
-                right.to_string()
-            ),
-            Expression::Grouping { expression } => {
-                format!("(group {})", (*expression).to_string())
-            }
-            Expression::Literal { value } => format!("{}", value.to_string()),
+            Expression::ArrayLiteral { bracket, .. } => bracket,
+            Expression::Assign { name, .. } => name,
+            Expression::Binary { operator, .. } => operator,
+            Expression::Call { paren, .. } => paren,
+            Expression::Conditional { question, .. } => question,
+            Expression::Get { name, .. } => name,
+            Expression::Grouping { paren, .. } => paren,
+            Expression::Index { bracket, .. } => bracket,
+            Expression::Lambda { start, .. } => start,
+            Expression::Literal { token, .. } => token,
+            Expression::Logical { operator, .. } => operator,
+            Expression::Set { name, .. } => name,
+            Expression::Super { keyword, .. } => keyword,
+            Expression::This { keyword, .. } => keyword,
+            Expression::Unary { operator, .. } => operator,
+            Expression::Variable { name, .. } => name,
+        }
+    }
+
+    /// Moves the expression out, leaving a `Nil` literal in its place, for
+    /// passes that take the tree apart as they go.
+    pub(crate) fn take(&mut self) -> Expression {
+        let nil = Token::new(TokenType::Nil, String::new(), None, 0, 0, (0, 0));
+        std::mem::replace(
+            self,
+            Expression::Literal {
+                token: nil,
+                value: LiteralValue::Nil,
+            },
+        )
+    }
+
+    /// How many levels the tree has, a lone literal or variable being one.
+    /// Worked out with a stack of its own, so even a tree too deep to
+    /// recurse over can be measured.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((expression, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            stack.extend(expression.children().map(|child| (child, depth + 1)));
+        }
+
+        deepest
+    }
+
+    /// How many expression nodes the tree has. A lambda's body is made of
+    /// statements, so a lambda counts as one node.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self];
+        while let Some(expression) = stack.pop() {
+            count += 1;
+            stack.extend(expression.children());
+        }
+
+        count
+    }
+
+    /// Splits a chain of operators grouping to the left, like `1 + 2 + 3`,
+    /// into its leftmost operand and each operator after it with its right
+    /// operand, in source order. Anything but a `Binary` or `Logical` node is
+    /// a chain of one operand.
+    ///
+    /// The parser builds such chains in a loop, so they can be any length,
+    /// and nest as deep as they're long. Passes over the tree walk them with
+    /// this rather than recursing down their left operands.
+    pub fn operator_chain(&self) -> (&Expression, Vec<(&Token, &Expression)>) {
+        let (nodes, leftmost) = self.chain_nodes();
+        let links = nodes
+            .into_iter()
+            .rev()
+            .filter_map(|node| match node {
+                Expression::Binary {
+                    operator, right, ..
+                }
+                | Expression::Logical {
+                    operator, right, ..
+                } => Some((operator, &**right)),
+                _ => None,
+            })
+            .collect();
+
+        (leftmost, links)
+    }
+
+    /// The binary and logical nodes of an operator chain, from the outermost
+    /// in, and the leftmost operand they all have below them.
+    fn chain_nodes(&self) -> (Vec<&Expression>, &Expression) {
+        let mut nodes = Vec::new();
+        let mut leftmost = self;
+        while let Expression::Binary { left, .. } | Expression::Logical { left, .. } = leftmost {
+            nodes.push(leftmost);
+            leftmost = left;
+        }
+
+        (nodes, leftmost)
+    }
+
+    fn children(&self) -> impl Iterator<Item = &Expression> {
+        let children: Vec<&Expression> = match self {
+            Expression::ArrayLiteral { elements, .. } => elements.iter().collect(),
+            Expression::Assign { value, .. } => vec![value],
+            Expression::Binary { left, right, .. } | Expression::Logical { left, right, .. } => {
+                vec![left, right]
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => std::iter::once(&**callee).chain(arguments).collect(),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => vec![condition, then_branch, else_branch],
+            Expression::Get { object, .. } => vec![object],
+            Expression::Grouping { expression, .. } => vec![expression],
+            Expression::Index { object, index, .. } => vec![object, index],
+            Expression::Set { object, value, .. } => vec![object, value],
+            Expression::Unary { right, .. } => vec![right],
+            Expression::Lambda { .. }
+            | Expression::Literal { .. }
+            | Expression::Super { .. }
+            | Expression::This { .. }
+            | Expression::Variable { .. } => vec![],
+        };
+
+        children.into_iter()
+    }
+
+    /// Renders the tree as nested JSON objects, each with a `type` and the
+    /// `line` it's on.
+    pub fn to_json(&self) -> String {
+        if let Expression::Binary { .. } | Expression::Logical { .. } = self {
+            return self.operator_chain_json();
+        }
+
+        let mut fields = vec![
+            ("type", json_string(self.json_type())),
+            ("line", self.line().to_string()),
+        ];
+        match self {
+            Expression::ArrayLiteral { elements, .. } => {
+                fields.push(("elements", json_array(elements, Expression::to_json)));
+            }
+            Expression::Assign { name, value, .. } => {
+                fields.push(("name", json_string(&name.lexeme)));
+                fields.push(("value", value.to_json()));
+            }
+            Expression::Binary { .. } | Expression::Logical { .. } => unreachable!(),
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                fields.push(("callee", callee.to_json()));
+                fields.push(("arguments", json_array(arguments, Expression::to_json)));
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                fields.push(("condition", condition.to_json()));
+                fields.push(("then", then_branch.to_json()));
+                fields.push(("else", else_branch.to_json()));
+            }
+            Expression::Get { object, name } => {
+                fields.push(("object", object.to_json()));
+                fields.push(("name", json_string(&name.lexeme)));
+            }
+            Expression::Grouping { expression, .. } => {
+                fields.push(("expression", expression.to_json()));
+            }
+            Expression::Index { object, index, .. } => {
+                fields.push(("object", object.to_json()));
+                fields.push(("index", index.to_json()));
+            }
+            Expression::Lambda { params, body, .. } => {
+                fields.push((
+                    "params",
+                    json_array(params, |param| json_string(&param.lexeme)),
+                ));
+                fields.push(("body", json_array(body, Statement::to_json)));
+            }
+            Expression::Literal { value, .. } => fields.push(("value", value.to_json())),
+            Expression::Set {
+                object,
+                name,
+                value,
+            } => {
+                fields.push(("object", object.to_json()));
+                fields.push(("name", json_string(&name.lexeme)));
+                fields.push(("value", value.to_json()));
+            }
+            Expression::Super { method, .. } => {
+                fields.push(("method", json_string(&method.lexeme)));
+            }
+            Expression::This { .. } => (),
             Expression::Unary { operator, right } => {
-                let operator_str = operator.lexeme.clone();
-                let right_str = (*right).to_string();
-                format!("({} {})", operator_str, right_str)
+                fields.push(("operator", json_string(&operator.lexeme)));
+                fields.push(("right", right.to_json()));
+            }
+            Expression::Variable { name, .. } => fields.push(("name", json_string(&name.lexeme))),
+        }
+
+        json_object(&fields)
+    }
+
+    /// A chain of operators as nested objects, each holding the one before it
+    /// as its `left`. The objects are opened from the outermost in, then
+    /// closed with their `right` from the innermost out, so a long chain is
+    /// written without recursing down it.
+    fn operator_chain_json(&self) -> String {
+        let (nodes, leftmost) = self.chain_nodes();
+        let mut json = String::new();
+        for node in &nodes {
+            json.push_str(&format!(
+                r#"{{"type":{},"line":{},"operator":{},"left":"#,
+                json_string(node.json_type()),
+                node.line(),
+                json_string(&node.token().lexeme)
+            ));
+        }
+        json.push_str(&leftmost.to_json());
+        for node in nodes.iter().rev() {
+            if let Expression::Binary { right, .. } | Expression::Logical { right, .. } = node {
+                json.push_str(&format!(r#","right":{}}}"#, right.to_json()));
             }
         }
+
+        json
     }
 
-    pub fn print(&self) {
-        println!("{}", self.to_string());
+    fn json_type(&self) -> &'static str {
+        match self {
+            Expression::ArrayLiteral { .. } => "array",
+            Expression::Assign { .. } => "assign",
+            Expression::Binary { .. } => "binary",
+            Expression::Call { .. } => "call",
+            Expression::Conditional { .. } => "conditional",
+            Expression::Get { .. } => "get",
+            Expression::Grouping { .. } => "grouping",
+            Expression::Index { .. } => "index",
+            Expression::Lambda { .. } => "lambda",
+            Expression::Literal { .. } => "literal",
+            Expression::Logical { .. } => "logical",
+            Expression::Set { .. } => "set",
+            Expression::Super { .. } => "super",
+            Expression::This { .. } => "this",
+            Expression::Unary { .. } => "unary",
+            Expression::Variable { .. } => "variable",
+        }
+    }
+}
+
+/// A chain of operators is copied a link at a time from its leftmost operand
+/// up, so however long it is, it's never recursed down.
+impl Clone for Expression {
+    fn clone(&self) -> Self {
+        match self {
+            Expression::ArrayLiteral { bracket, elements } => Expression::ArrayLiteral {
+                bracket: bracket.clone(),
+                elements: elements.clone(),
+            },
+            Expression::Assign {
+                name,
+                value,
+                distance,
+            } => Expression::Assign {
+                name: name.clone(),
+                value: value.clone(),
+                distance: *distance,
+            },
+            Expression::Binary { .. } | Expression::Logical { .. } => {
+                let (nodes, leftmost) = self.chain_nodes();
+                let mut copy = leftmost.clone();
+                for node in nodes.into_iter().rev() {
+                    copy = match node {
+                        Expression::Binary {
+                            operator, right, ..
+                        } => Expression::Binary {
+                            left: Box::new(copy),
+                            operator: operator.clone(),
+                            right: right.clone(),
+                        },
+                        Expression::Logical {
+                            operator, right, ..
+                        } => Expression::Logical {
+                            left: Box::new(copy),
+                            operator: operator.clone(),
+                            right: right.clone(),
+                        },
+                        _ => unreachable!(),
+                    };
+                }
+                copy
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => Expression::Call {
+                callee: callee.clone(),
+                paren: paren.clone(),
+                arguments: arguments.clone(),
+            },
+            Expression::Conditional {
+                condition,
+                question,
+                then_branch,
+                else_branch,
+            } => Expression::Conditional {
+                condition: condition.clone(),
+                question: question.clone(),
+                then_branch: then_branch.clone(),
+                else_branch: else_branch.clone(),
+            },
+            Expression::Get { object, name } => Expression::Get {
+                object: object.clone(),
+                name: name.clone(),
+            },
+            Expression::Grouping { paren, expression } => Expression::Grouping {
+                paren: paren.clone(),
+                expression: expression.clone(),
+            },
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => Expression::Index {
+                object: object.clone(),
+                bracket: bracket.clone(),
+                index: index.clone(),
+            },
+            Expression::Lambda {
+                start,
+                params,
+                body,
+            } => Expression::Lambda {
+                start: start.clone(),
+                params: params.clone(),
+                body: body.clone(),
+            },
+            Expression::Literal { token, value } => Expression::Literal {
+                token: token.clone(),
+                value: value.clone(),
+            },
+            Expression::Set {
+                object,
+                name,
+                value,
+            } => Expression::Set {
+                object: object.clone(),
+                name: name.clone(),
+                value: value.clone(),
+            },
+            Expression::Super {
+                keyword,
+                method,
+                distance,
+            } => Expression::Super {
+                keyword: keyword.clone(),
+                method: method.clone(),
+                distance: *distance,
+            },
+            Expression::This { keyword, distance } => Expression::This {
+                keyword: keyword.clone(),
+                distance: *distance,
+            },
+            Expression::Unary { operator, right } => Expression::Unary {
+                operator: operator.clone(),
+                right: right.clone(),
+            },
+            Expression::Variable { name, distance } => Expression::Variable {
+                name: name.clone(),
+                distance: *distance,
+            },
+        }
+    }
+}
+
+/// Taken apart with a stack of its own, like `depth`, so even a tree too
+/// deep to recurse over can be dropped.
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        while let Some(mut expression) = stack.pop() {
+            expression.take_children(&mut stack);
+        }
+    }
+}
+
+impl Expression {
+    /// Moves the node's operands onto `stack`, leaving it with none to drop.
+    fn take_children(&mut self, stack: &mut Vec<Expression>) {
+        match self {
+            Expression::ArrayLiteral { elements, .. } => stack.append(elements),
+            Expression::Assign { value: child, .. }
+            | Expression::Get { object: child, .. }
+            | Expression::Grouping {
+                expression: child, ..
+            }
+            | Expression::Unary { right: child, .. } => stack.push(child.take()),
+            Expression::Binary { left, right, .. }
+            | Expression::Logical { left, right, .. }
+            | Expression::Index {
+                object: left,
+                index: right,
+                ..
+            }
+            | Expression::Set {
+                object: left,
+                value: right,
+                ..
+            } => {
+                stack.push(left.take());
+                stack.push(right.take());
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                stack.push(callee.take());
+                stack.append(arguments);
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                stack.push(condition.take());
+                stack.push(then_branch.take());
+                stack.push(else_branch.take());
+            }
+            Expression::Lambda { .. }
+            | Expression::Literal { .. }
+            | Expression::Super { .. }
+            | Expression::This { .. }
+            | Expression::Variable { .. } => (),
+        }
+    }
+}
+
+/// Joins `(key, json)` pairs into an object, keeping their order.
+pub(crate) fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+pub(crate) fn json_array<T>(items: &[T], to_json: impl Fn(&T) -> String) -> String {
+    let items: Vec<String> = items.iter().map(to_json).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// A part of an expression's printed form still to be written.
+enum Piece<'a> {
+    Node(&'a Expression),
+    Text(&'a str),
+}
+
+/// Written with a stack of pieces rather than by recursion, so a very deep
+/// tree prints without overflowing the call stack.
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Piece::{Node, Text};
+
+        let mut stack = vec![Node(self)];
+        while let Some(piece) = stack.pop() {
+            let expression = match piece {
+                Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+                Node(expression) => expression,
+            };
+            let pieces = match expression {
+                Expression::ArrayLiteral { elements, .. } => {
+                    let mut pieces = vec![Text("(array")];
+                    for element in elements {
+                        pieces.extend([Text(" "), Node(element)]);
+                    }
+                    pieces.push(Text(")"));
+                    pieces
+                }
+                Expression::Assign { name, value, .. } => {
+                    vec![
+                        Text("(= "),
+                        Text(&name.lexeme),
+                        Text(" "),
+                        Node(value),
+                        Text(")"),
+                    ]
+                }
+                Expression::Binary {
+                    left,
+                    operator,
+                    right,
+                }
+                | Expression::Logical {
+                    left,
+                    operator,
+                    right,
+                } => vec![
+                    Text("("),
+                    Text(&operator.lexeme),
+                    Text(" "),
+                    Node(left),
+                    Text(" "),
+                    Node(right),
+                    Text(")"),
+                ],
+                Expression::Call {
+                    callee, arguments, ..
+                } => {
+                    let mut pieces = vec![Text("(call "), Node(callee)];
+                    for argument in arguments {
+                        pieces.extend([Text(" "), Node(argument)]);
+                    }
+                    pieces.push(Text(")"));
+                    pieces
+                }
+                Expression::Conditional {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    ..
+                } => vec![
+                    Text("(?: "),
+                    Node(condition),
+                    Text(" "),
+                    Node(then_branch),
+                    Text(" "),
+                    Node(else_branch),
+                    Text(")"),
+                ],
+                Expression::Get { object, name } => {
+                    vec![
+                        Text("(get "),
+                        Node(object),
+                        Text(" "),
+                        Text(&name.lexeme),
+                        Text(")"),
+                    ]
+                }
+                Expression::Grouping { expression, .. } => {
+                    vec![Text("(group "), Node(expression), Text(")")]
+                }
+                Expression::Index { object, index, .. } => {
+                    vec![
+                        Text("(index "),
+                        Node(object),
+                        Text(" "),
+                        Node(index),
+                        Text(")"),
+                    ]
+                }
+                Expression::Lambda { params, body, .. } => {
+                    let params: Vec<&str> =
+                        params.iter().map(|param| param.lexeme.as_str()).collect();
+                    write!(f, "(lambda ({})", params.join(" "))?;
+                    for statement in body {
+                        write!(f, " {}", statement)?;
+                    }
+                    vec![Text(")")]
+                }
+                Expression::Literal { value, .. } => {
+                    write!(f, "{}", value)?;
+                    continue;
+                }
+                Expression::Set {
+                    object,
+                    name,
+                    value,
+                } => vec![
+                    Text("(set "),
+                    Node(object),
+                    Text(" "),
+                    Text(&name.lexeme),
+                    Text(" "),
+                    Node(value),
+                    Text(")"),
+                ],
+                Expression::Super { method, .. } => {
+                    vec![Text("(super "), Text(&method.lexeme), Text(")")]
+                }
+                Expression::This { .. } => vec![Text("this")],
+                Expression::Unary { operator, right } => {
+                    vec![
+                        Text("("),
+                        Text(&operator.lexeme),
+                        Text(" "),
+                        Node(right),
+                        Text(")"),
+                    ]
+                }
+                Expression::Variable { name, .. } => vec![Text(&name.lexeme)],
+            };
+            stack.extend(pieces.into_iter().rev());
+        }
+
+        Ok(())
     }
 }
 
@@ -109,12 +831,20 @@ mod tests {
             lexeme: "-".to_string(),
             literal: None,
             line_number: 0,
+            column: 0,
+            span: (0, 0),
+        };
+        let token = |token_type, lexeme: &str| {
+            Token::new(token_type, lexeme.to_string(), None, 0, 0, (0, 0))
         };
         let one_two_three = Literal {
-            value: Number(123.0),
+            token: token(TokenType::Number, "123"),
+            value: Integer(123),
         };
         let group = Grouping {
+            paren: token(TokenType::LeftParen, "("),
             expression: Box::new(Literal {
+                token: token(TokenType::Number, "45.67"),
                 value: Number(45.67),
             }),
         };
@@ -123,6 +853,8 @@ mod tests {
             lexeme: "*".to_string(),
             literal: None,
             line_number: 0,
+            column: 0,
+            span: (0, 0),
         };
         let ast = Binary {
             left: Box::new(Unary {
@@ -133,6 +865,186 @@ mod tests {
             right: Box::new(group),
         };
 
-        assert_eq!(ast.to_string(), "(* (- 123) (group 45.67))".to_string())
+        assert_eq!(format!("{}", ast), "(* (- 123) (group 45.67))");
+        assert!(format!("{:?}", ast).starts_with("Binary { left: Unary {"));
+    }
+
+    #[test]
+    fn test_from_token() {
+        let token = |token_type, lexeme: &str, literal| {
+            Token::new(token_type, lexeme.to_string(), literal, 1, 1, (0, 0))
+        };
+
+        let number = token(TokenType::Number, "2", Some(TokenLiteralValue::IntValue(2)));
+        assert_eq!(LiteralValue::from_token(number), Ok(Integer(2)));
+        let number = token(
+            TokenType::Number,
+            "2.0",
+            Some(TokenLiteralValue::FValue(2.0)),
+        );
+        assert_eq!(LiteralValue::from_token(number), Ok(Number(2.0)));
+        assert_eq!(
+            LiteralValue::from_token(token(TokenType::Nil, "Nil", None)),
+            Ok(Nil)
+        );
+
+        let identifier = token(
+            TokenType::Identifier,
+            "x",
+            Some(TokenLiteralValue::IdentifierValue("x".to_string())),
+        );
+        assert_eq!(
+            LiteralValue::from_token(identifier),
+            Err("Couldn't create a literal from Identifier 'x'".to_string())
+        );
+
+        let number = token(TokenType::Number, "2", None);
+        assert_eq!(
+            LiteralValue::from_token(number),
+            Err("Couldn't unwrap None as a number".to_string())
+        );
+
+        let string = token(
+            TokenType::StringLiteral,
+            "\"a\"",
+            Some(TokenLiteralValue::IntValue(1)),
+        );
+        assert!(LiteralValue::from_token(string).is_err());
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        let cases = [
+            (Nil, false),
+            (False, false),
+            (True, true),
+            (Integer(0), true),
+            (Integer(-1), true),
+            (Number(0.0), true),
+            (Number(f64::NAN), true),
+            (StringValue("".to_string()), true),
+            (StringValue("False".to_string()), true),
+        ];
+        for (value, truthy) in cases {
+            assert_eq!(value.is_truthy(), truthy, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_is_equal() {
+        let string = |s: &str| StringValue(s.to_string());
+        let equal = [
+            (Nil, Nil),
+            (True, True),
+            (False, False),
+            (Integer(1), Integer(1)),
+            (Integer(1), Number(1.0)),
+            (Number(1.0), Integer(1)),
+            (Number(0.5), Number(0.5)),
+            (string("a"), string("a")),
+            (string(""), string("")),
+        ];
+        let unequal = [
+            (Nil, False),
+            (Nil, Integer(0)),
+            (Nil, string("")),
+            (Nil, string("Nil")),
+            (True, False),
+            (True, Integer(1)),
+            (False, Integer(0)),
+            (Integer(1), Integer(2)),
+            (Integer(1), Number(1.5)),
+            (Integer(1), string("1")),
+            (Number(f64::NAN), Number(f64::NAN)),
+            (string("a"), string("A")),
+        ];
+        for (left, right) in equal {
+            assert!(left.is_equal(&right), "{:?} == {:?}", left, right);
+            assert!(right.is_equal(&left), "{:?} == {:?}", right, left);
+        }
+        for (left, right) in unequal {
+            assert!(!left.is_equal(&right), "{:?} != {:?}", left, right);
+            assert!(!right.is_equal(&left), "{:?} != {:?}", right, left);
+        }
+    }
+
+    /// `((1 + 1) + 1) + ...`, `additions` deep on the left.
+    fn left_chain(additions: usize) -> Expression {
+        let one = || Literal {
+            token: Token::new(TokenType::Number, "1".to_string(), None, 1, 0, (0, 0)),
+            value: Integer(1),
+        };
+        let plus = Token::new(TokenType::Plus, "+".to_string(), None, 1, 0, (0, 0));
+        (0..additions).fold(one(), |left, _| Binary {
+            left: Box::new(left),
+            operator: plus.clone(),
+            right: Box::new(one()),
+        })
+    }
+
+    #[test]
+    fn test_depth_and_node_count() {
+        let chain = left_chain(3);
+        assert_eq!(chain.depth(), 4);
+        assert_eq!(chain.node_count(), 7);
+        assert_eq!(chain.to_string(), "(+ (+ (+ 1 1) 1) 1)");
+
+        let call = parse("f(-x, [1, 2], (y))");
+        assert_eq!(call.depth(), 3);
+        assert_eq!(call.node_count(), 9);
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        let chain = left_chain(10_000);
+        assert_eq!(chain.depth(), 10_001);
+        assert_eq!(chain.node_count(), 20_001);
+
+        let printed = chain.to_string();
+        assert_eq!(printed.len(), 10_000 * "(+  1)".len() + 1);
+        assert!(printed.starts_with(&"(+ ".repeat(10_000)));
+        assert!(printed.ends_with(&" 1)".repeat(10_000)));
+
+        let chain = left_chain(100_000);
+        let copy = chain.clone();
+        drop(chain);
+        assert_eq!(copy.node_count(), 200_001);
+        let json = copy.to_json();
+        assert!(
+            json.ends_with(&r#","right":{"type":"literal","line":1,"value":1}}"#.repeat(100_000))
+        );
+    }
+
+    fn parse(source: &str) -> Expression {
+        let tokens = crate::lexer::lexer::Lexer::new(source)
+            .scan_tokens()
+            .unwrap();
+        crate::ast::parser::Parser::new(tokens)
+            .parse_expression()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            parse("1 + (2 * 3)").to_json(),
+            concat!(
+                r#"{"type":"binary","line":1,"operator":"+","#,
+                r#""left":{"type":"literal","line":1,"value":1},"#,
+                r#""right":{"type":"grouping","line":1,"expression":"#,
+                r#"{"type":"binary","line":1,"operator":"*","#,
+                r#""left":{"type":"literal","line":1,"value":2},"#,
+                r#""right":{"type":"literal","line":1,"value":3}}}}"#
+            )
+        );
+        assert_eq!(
+            parse(r#"f("a \"quote\"", 2.5, True)"#).to_json(),
+            concat!(
+                r#"{"type":"call","line":1,"callee":{"type":"variable","line":1,"name":"f"},"#,
+                r#""arguments":[{"type":"literal","line":1,"value":"a \"quote\""},"#,
+                r#"{"type":"literal","line":1,"value":2.5},"#,
+                r#"{"type":"literal","line":1,"value":true}]}"#
+            )
+        );
     }
 }
@@ -0,0 +1,402 @@
+use crate::ast::expression::Expression;
+use crate::ast::statement::Statement;
+use crate::lexer::token::{Token, TokenType};
+
+const INDENT: &str = "    ";
+
+/// Turns a parsed program back into source, one statement per line with
+/// blocks indented by four spaces.
+///
+/// The output parses back to an equal tree, so formatting it again changes
+/// nothing. Comments aren't in the tree and are lost, which is why
+/// `oli::format` refuses source that has any, and sugar the parser removes
+/// stays removed: a `for` loop comes back as the `while` it became.
+pub fn format_program(statements: &[Statement]) -> String {
+    let mut output = String::new();
+    for statement in statements {
+        output.push_str(&format_statement(statement, 0));
+        output.push('\n');
+    }
+    output
+}
+
+/// Formats a statement indented `depth` levels, without indenting its first
+/// line.
+fn format_statement(statement: &Statement, depth: usize) -> String {
+    match statement {
+        Statement::Block(statements) => block(statements, depth),
+        Statement::Break { .. } => "break;".to_string(),
+        Statement::Class {
+            name,
+            superclass,
+            methods,
+        } => {
+            let mut output = format!("class {}", name.lexeme);
+            if let Some(superclass) = superclass {
+                output.push_str(&format!(" < {}", format_expression(superclass, depth)));
+            }
+            if methods.is_empty() {
+                return output + " {}";
+            }
+            output.push_str(" {\n");
+            for method in methods {
+                if let Statement::Function { name, params, body } = method {
+                    output.push_str(&indent(depth + 1));
+                    output.push_str(&function(name, params, body, depth + 1));
+                    output.push('\n');
+                }
+            }
+            output + &indent(depth) + "}"
+        }
+        Statement::Const { name, initializer } => format!(
+            "const {} = {};",
+            name.lexeme,
+            format_expression(initializer, depth)
+        ),
+        Statement::Continue { .. } => "continue;".to_string(),
+        Statement::DoWhile { body, condition } => format!(
+            "do{}{}while ({});",
+            branch(body, depth),
+            after_branch(body, depth),
+            format_expression(condition, depth)
+        ),
+        Statement::Expression(expression) => format!("{};", format_expression(expression, depth)),
+        Statement::Function { name, params, body } => {
+            format!("fun {}", function(name, params, body, depth))
+        }
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let mut output = format!(
+                "if ({}){}",
+                format_expression(condition, depth),
+                branch(then_branch, depth)
+            );
+            match else_branch.as_deref() {
+                // `elif (c)` parses to the same tree as `else if (c)`.
+                Some(else_if @ Statement::If { .. }) => {
+                    output.push_str(&after_branch(then_branch, depth));
+                    output.push_str("el");
+                    output.push_str(&format_statement(else_if, depth));
+                }
+                Some(else_branch) => {
+                    output.push_str(&after_branch(then_branch, depth));
+                    output.push_str("else");
+                    output.push_str(&branch(else_branch, depth));
+                }
+                None => (),
+            }
+            output
+        }
+        Statement::Match {
+            subject,
+            arms,
+            default,
+        } => {
+            let mut output = format!("match ({}) {{\n", format_expression(subject, depth));
+            for (pattern, body) in arms {
+                output.push_str(&format!(
+                    "{}{} => {}\n",
+                    indent(depth + 1),
+                    format_expression(pattern, depth + 1),
+                    format_statement(body, depth + 1)
+                ));
+            }
+            if let Some(default) = default {
+                output.push_str(&format!(
+                    "{}else => {}\n",
+                    indent(depth + 1),
+                    format_statement(default, depth + 1)
+                ));
+            }
+            output + &indent(depth) + "}"
+        }
+        Statement::Return { value, .. } => match value {
+            Some(value) => format!("return {};", format_expression(value, depth)),
+            None => "return;".to_string(),
+        },
+        Statement::Say(expression) => format!("say {};", format_expression(expression, depth)),
+        Statement::Var { name, initializer } => match initializer {
+            Some(initializer) => format!(
+                "var {} = {};",
+                name.lexeme,
+                format_expression(initializer, depth)
+            ),
+            None => format!("var {};", name.lexeme),
+        },
+        Statement::While { condition, body } => format!(
+            "while ({}){}",
+            format_expression(condition, depth),
+            branch(body, depth)
+        ),
+    }
+}
+
+/// Grouping nodes keep the source's parentheses, so printing every operator
+/// between its operands reproduces the tree without adding any.
+fn format_expression(expression: &Expression, depth: usize) -> String {
+    let format = |expression: &Expression| format_expression(expression, depth);
+    match expression {
+        Expression::ArrayLiteral { elements, .. } => format!("[{}]", list(elements, depth)),
+        Expression::Assign { name, value, .. } => format!("{} = {}", name.lexeme, format(value)),
+        // Chains can be any length, so they're printed in a loop.
+        Expression::Binary { .. } | Expression::Logical { .. } => {
+            let (leftmost, links) = expression.operator_chain();
+            let mut output = format(leftmost);
+            for (operator, right) in links {
+                if operator.token_type == TokenType::Comma {
+                    output.push_str(", ");
+                } else {
+                    output.push(' ');
+                    output.push_str(&operator.lexeme);
+                    output.push(' ');
+                }
+                output.push_str(&format(right));
+            }
+            output
+        }
+        // A pipe is stored as the call it makes, marked by its `|>` token.
+        Expression::Call {
+            callee,
+            paren,
+            arguments,
+        } if paren.token_type == TokenType::PipeGreater => {
+            format!("{} |> {}", list(arguments, depth), format(callee))
+        }
+        Expression::Call {
+            callee, arguments, ..
+        } => format!("{}({})", format(callee), list(arguments, depth)),
+        Expression::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => format!(
+            "{} ? {} : {}",
+            format(condition),
+            format(then_branch),
+            format(else_branch)
+        ),
+        Expression::Get { object, name } => format!("{}.{}", format(object), name.lexeme),
+        Expression::Grouping { expression, .. } => format!("({})", format(expression)),
+        Expression::Index { object, index, .. } => {
+            format!("{}[{}]", format(object), format(index))
+        }
+        Expression::Lambda {
+            start,
+            params,
+            body,
+        } => {
+            let params = format!("({})", names(params));
+            let head = if start.token_type == TokenType::Function {
+                format!("fun {}", params)
+            } else {
+                params
+            };
+            // An `->` body is a return whose keyword is the arrow.
+            match body.as_slice() {
+                [Statement::Return {
+                    keyword,
+                    value: Some(value),
+                }] if keyword.token_type == TokenType::Arrow => {
+                    format!("{} -> {}", head, format(value))
+                }
+                body => format!("{} {}", head, block(body, depth)),
+            }
+        }
+        Expression::Literal { token, .. } => token.lexeme.clone(),
+        Expression::Set {
+            object,
+            name,
+            value,
+        } => format!("{}.{} = {}", format(object), name.lexeme, format(value)),
+        Expression::Super { method, .. } => format!("super.{}", method.lexeme),
+        Expression::This { .. } => "this".to_string(),
+        Expression::Unary { operator, right } => {
+            let right = format(right);
+            // Keep `- -x` from reading as a decrement.
+            if right.starts_with(&operator.lexeme) {
+                format!("{} {}", operator.lexeme, right)
+            } else {
+                format!("{}{}", operator.lexeme, right)
+            }
+        }
+        Expression::Variable { name, .. } => name.lexeme.clone(),
+    }
+}
+
+fn indent(depth: usize) -> String {
+    INDENT.repeat(depth)
+}
+
+fn block(statements: &[Statement], depth: usize) -> String {
+    if statements.is_empty() {
+        return "{}".to_string();
+    }
+
+    let mut output = "{\n".to_string();
+    for statement in statements {
+        output.push_str(&indent(depth + 1));
+        output.push_str(&format_statement(statement, depth + 1));
+        output.push('\n');
+    }
+    output + &indent(depth) + "}"
+}
+
+/// The body of an `if`, loop or `else`: a block stays on the keyword's line,
+/// and anything else goes on the next line, indented.
+fn branch(statement: &Statement, depth: usize) -> String {
+    match statement {
+        Statement::Block(statements) => format!(" {}", block(statements, depth)),
+        statement => format!(
+            "\n{}{}",
+            indent(depth + 1),
+            format_statement(statement, depth + 1)
+        ),
+    }
+}
+
+/// What separates a branch from the keyword after it: `} else` shares a
+/// line, but an unbraced branch ends its own.
+fn after_branch(statement: &Statement, depth: usize) -> String {
+    match statement {
+        Statement::Block(_) => " ".to_string(),
+        _ => format!("\n{}", indent(depth)),
+    }
+}
+
+fn function(name: &Token, params: &[Token], body: &[Statement], depth: usize) -> String {
+    format!("{}({}) {}", name.lexeme, names(params), block(body, depth))
+}
+
+fn names(params: &[Token]) -> String {
+    let names: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+    names.join(", ")
+}
+
+fn list(expressions: &[Expression], depth: usize) -> String {
+    let items: Vec<String> = expressions
+        .iter()
+        .map(|expression| format_expression(expression, depth))
+        .collect();
+    items.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parser::Parser;
+    use crate::lexer::lexer::Lexer;
+
+    fn parse(source: &str) -> Vec<Statement> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse_program().unwrap()
+    }
+
+    /// Formats the source, checking that the output parses to the same tree
+    /// and formats to itself.
+    fn format(source: &str) -> String {
+        let statements = parse(source);
+        let formatted = format_program(&statements);
+
+        assert_eq!(parse(&formatted), statements, "{}", formatted);
+        assert_eq!(format_program(&parse(&formatted)), formatted);
+        formatted
+    }
+
+    #[test]
+    fn test_format_spacing() {
+        assert_eq!(
+            format("var   x=1+2*(3-y);say x  ;x=-  -x;"),
+            "var x = 1 + 2 * (3 - y);\nsay x;\nx = - -x;\n"
+        );
+        assert_eq!(
+            format("a.b=[1,2,f(3,4)][0]|>g;"),
+            "a.b = [1, 2, f(3, 4)][0] |> g;\n"
+        );
+    }
+
+    #[test]
+    fn test_format_nested_blocks() {
+        let source = "fun f(a,b){if(a){while(b){b=b-1;}}elif(b)say 1;else{return;}}";
+        assert_eq!(
+            format(source),
+            "fun f(a, b) {
+    if (a) {
+        while (b) {
+            b = b - 1;
+        }
+    } elif (b)
+        say 1;
+    else {
+        return;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_format_declarations() {
+        let source = "class A<B{init(x){this.x=x;}get(){return fun(){return super.get();};}}
+            do { continue; } while (n < 10);
+            match (x) { 1 => say \"one\"; else => {} }
+            const inc = (n) -> n + 1;";
+        assert_eq!(
+            format(source),
+            "class A < B {
+    init(x) {
+        this.x = x;
+    }
+    get() {
+        return fun () {
+            return super.get();
+        };
+    }
+}
+do {
+    continue;
+} while (n < 10);
+match (x) {
+    1 => say \"one\";
+    else => {}
+}
+const inc = (n) -> n + 1;
+"
+        );
+    }
+
+    #[test]
+    fn test_format_long_expression() {
+        let source = "say a ? b : c ? d : e, (f ?? g) or h and i | j ^ k & l == m <= n << o + p * q ** -r[s](t).u;";
+        assert_eq!(
+            format(source),
+            "say a ? b : c ? d : e, (f ?? g) or h and i | j ^ k & l == m <= n << o + p * q ** -r[s](t).u;\n"
+        );
+    }
+
+    #[test]
+    fn test_format_long_chain() {
+        // Compared as text: the tree is too deep for `==` to recurse over.
+        let source = format!("say 1{}, x and y;", " - 1".repeat(10_000));
+        let formatted = format_program(&parse(&source));
+        assert_eq!(formatted, format!("{}\n", source));
+    }
+
+    #[test]
+    fn test_format_for_loop() {
+        assert_eq!(
+            format("for (var i = 0; i < 3; i = i + 1) say i;"),
+            "{
+    var i = 0;
+    while (i < 3) {
+        say i;
+        i = i + 1;
+    }
+}
+"
+        );
+    }
+}
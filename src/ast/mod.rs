@@ -1,2 +1,9 @@
+pub mod dot;
+pub mod error;
 pub mod expression;
+pub mod format;
+pub mod operators;
+pub mod optimize;
 pub mod parser;
+pub mod rpn;
+pub mod statement;
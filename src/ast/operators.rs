@@ -0,0 +1,344 @@
+use crate::ast::expression::LiteralValue;
+use crate::lexer::token::TokenType;
+
+/// Applies a binary operator to two values. Constant folding and the
+/// interpreter both come here, so a folded expression always has the value
+/// it would have had at runtime.
+///
+/// Integer arithmetic stays integral while it can, and moves to floats when a
+/// result would overflow or isn't whole, so `7 / 2` is `3.5`. Dividing by
+/// zero, or taking a remainder by it, is an error rather than an infinity or
+/// NaN, for integers and floats alike. NaN can still come from infinities,
+/// as in `10.0 ** 400 - 10.0 ** 400`; every comparison with it is false, and
+/// it isn't even equal to itself. `,` and `??`
+/// decide whether to evaluate their right operand, so they aren't handled
+/// here.
+///
+/// `+` joins strings, and turns a number on either side of a string into
+/// text first, so `"count: " + 3` is `"count: 3"`.
+pub fn binary(
+    operator: TokenType,
+    left: &LiteralValue,
+    right: &LiteralValue,
+) -> Result<LiteralValue, String> {
+    use LiteralValue::*;
+
+    match (operator, left, right) {
+        (TokenType::EqualEqual, _, _) => Ok(boolean(left.is_equal(right))),
+        (TokenType::BangEqual, _, _) => Ok(boolean(!left.is_equal(right))),
+        (TokenType::Plus, StringValue(a), StringValue(b)) => Ok(StringValue(format!("{}{}", a, b))),
+        (TokenType::Plus, StringValue(a), number @ (Integer(_) | Number(_))) => {
+            Ok(StringValue(format!("{}{}", a, number_text(number))))
+        }
+        (TokenType::Plus, number @ (Integer(_) | Number(_)), StringValue(b)) => {
+            Ok(StringValue(format!("{}{}", number_text(number), b)))
+        }
+        (_, Integer(a), Integer(b)) => integers(operator, *a, *b),
+        _ => match (as_f64(left), as_f64(right)) {
+            (Some(a), Some(b)) => numbers(operator, a, b),
+            _ => Err(operand_error(operator, left.type_name(), right.type_name())),
+        },
+    }
+}
+
+/// The error for operands, named by type, that `operator` can't combine.
+/// Values that aren't literals, such as functions, get the same errors.
+pub fn operand_error(operator: TokenType, left: &str, right: &str) -> String {
+    match operator {
+        TokenType::Plus => format!("Can't add {} and {}.", left, right),
+        operator if is_bitwise(operator) => "Operands must be integers.".to_string(),
+        _ => "Operands must be numbers.".to_string(),
+    }
+}
+
+/// Applies a prefix operator to a value.
+pub fn unary(operator: TokenType, operand: &LiteralValue) -> Result<LiteralValue, String> {
+    match (operator, operand) {
+        (TokenType::Bang, operand) => Ok(boolean(!operand.is_truthy())),
+        (TokenType::Minus, LiteralValue::Integer(x)) => Ok(x
+            .checked_neg()
+            .map_or(LiteralValue::Number(-(*x as f64)), LiteralValue::Integer)),
+        (TokenType::Minus, LiteralValue::Number(x)) => Ok(LiteralValue::Number(-x)),
+        (TokenType::Tilde, LiteralValue::Integer(x)) => Ok(LiteralValue::Integer(!x)),
+        (TokenType::Minus | TokenType::Tilde, _) => Err(unary_operand_error(operator)),
+        _ => Err(format!("'{}' isn't a prefix operator.", operator)),
+    }
+}
+
+/// The error for an operand `operator` can't be applied to.
+pub fn unary_operand_error(operator: TokenType) -> String {
+    match operator {
+        TokenType::Tilde => "Operand must be an integer.".to_string(),
+        _ => "Operand must be a number.".to_string(),
+    }
+}
+
+fn integers(operator: TokenType, a: i64, b: i64) -> Result<LiteralValue, String> {
+    let integer = |result: Option<i64>, float: fn(f64, f64) -> f64| {
+        Ok(result.map_or(
+            LiteralValue::Number(float(a as f64, b as f64)),
+            LiteralValue::Integer,
+        ))
+    };
+    let shift = |result: Option<i64>| {
+        result
+            .map(LiteralValue::Integer)
+            .ok_or_else(|| "Shift amount must be between 0 and 63.".to_string())
+    };
+
+    match operator {
+        TokenType::Plus => integer(a.checked_add(b), |a, b| a + b),
+        TokenType::Minus => integer(a.checked_sub(b), |a, b| a - b),
+        TokenType::Star => integer(a.checked_mul(b), |a, b| a * b),
+        TokenType::Slash if b != 0 && a.checked_rem(b) == Some(0) => {
+            integer(a.checked_div(b), |a, b| a / b)
+        }
+        // `i64::MIN % -1` overflows, though the remainder is plainly zero.
+        TokenType::Percent if b != 0 => Ok(LiteralValue::Integer(a.checked_rem(b).unwrap_or(0))),
+        TokenType::StarStar if b >= 0 => integer(
+            u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            f64::powf,
+        ),
+        TokenType::Ampersand => Ok(LiteralValue::Integer(a & b)),
+        TokenType::Pipe => Ok(LiteralValue::Integer(a | b)),
+        TokenType::Caret => Ok(LiteralValue::Integer(a ^ b)),
+        TokenType::LessLess => shift(u32::try_from(b).ok().and_then(|b| a.checked_shl(b))),
+        TokenType::GreaterGreater => shift(u32::try_from(b).ok().and_then(|b| a.checked_shr(b))),
+        TokenType::Greater => Ok(boolean(a > b)),
+        TokenType::GreaterEqual => Ok(boolean(a >= b)),
+        TokenType::Less => Ok(boolean(a < b)),
+        TokenType::LessEqual => Ok(boolean(a <= b)),
+        _ => numbers(operator, a as f64, b as f64),
+    }
+}
+
+fn numbers(operator: TokenType, a: f64, b: f64) -> Result<LiteralValue, String> {
+    let number = |x: f64| Ok(LiteralValue::Number(x));
+    match operator {
+        TokenType::Plus => number(a + b),
+        TokenType::Minus => number(a - b),
+        TokenType::Star => number(a * b),
+        TokenType::Slash | TokenType::Percent if b == 0.0 => Err("Division by zero.".to_string()),
+        TokenType::Slash => number(a / b),
+        TokenType::Percent => number(a % b),
+        TokenType::StarStar => number(a.powf(b)),
+        TokenType::Greater => Ok(boolean(a > b)),
+        TokenType::GreaterEqual => Ok(boolean(a >= b)),
+        TokenType::Less => Ok(boolean(a < b)),
+        TokenType::LessEqual => Ok(boolean(a <= b)),
+        operator if is_bitwise(operator) => Err("Operands must be integers.".to_string()),
+        operator => Err(format!("'{}' isn't a binary operator.", operator)),
+    }
+}
+
+/// A number as a running program shows it. A whole float loses its `.0`,
+/// so `"x" + 2.0` is `"x2"`, and `-0` is shown as `0`, which it equals.
+pub fn number_text(number: &LiteralValue) -> String {
+    match number {
+        LiteralValue::Number(x) if *x == 0.0 => "0".to_string(),
+        LiteralValue::Number(x) => x.to_string(),
+        number => number.to_string(),
+    }
+}
+
+fn is_bitwise(operator: TokenType) -> bool {
+    matches!(
+        operator,
+        TokenType::Ampersand
+            | TokenType::Pipe
+            | TokenType::Caret
+            | TokenType::LessLess
+            | TokenType::GreaterGreater
+    )
+}
+
+fn as_f64(value: &LiteralValue) -> Option<f64> {
+    match value {
+        LiteralValue::Integer(x) => Some(*x as f64),
+        LiteralValue::Number(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// `True` or `False`.
+pub fn boolean(value: bool) -> LiteralValue {
+    if value {
+        LiteralValue::True
+    } else {
+        LiteralValue::False
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LiteralValue::*;
+
+    #[test]
+    fn test_integers_stay_integral() {
+        assert_eq!(
+            binary(TokenType::Plus, &Integer(2), &Integer(3)),
+            Ok(Integer(5))
+        );
+        assert_eq!(
+            binary(TokenType::Slash, &Integer(6), &Integer(3)),
+            Ok(Integer(2))
+        );
+        assert_eq!(
+            binary(TokenType::Slash, &Integer(7), &Integer(2)),
+            Ok(Number(3.5))
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &Integer(1), &Number(0.5)),
+            Ok(Number(1.5))
+        );
+        assert_eq!(
+            binary(TokenType::Star, &Integer(i64::MAX), &Integer(2)),
+            Ok(Number(i64::MAX as f64 * 2.0))
+        );
+        assert_eq!(
+            binary(TokenType::Percent, &Integer(i64::MIN), &Integer(-1)),
+            Ok(Integer(0))
+        );
+        assert_eq!(
+            unary(TokenType::Minus, &Integer(i64::MIN)),
+            Ok(Number(-(i64::MIN as f64)))
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let zero = "Division by zero.".to_string();
+        for operator in [TokenType::Slash, TokenType::Percent] {
+            assert_eq!(
+                binary(operator, &Integer(1), &Integer(0)),
+                Err(zero.clone())
+            );
+            assert_eq!(
+                binary(operator, &Integer(0), &Integer(0)),
+                Err(zero.clone())
+            );
+            assert_eq!(
+                binary(operator, &Number(1.5), &Integer(0)),
+                Err(zero.clone())
+            );
+            assert_eq!(
+                binary(operator, &Integer(1), &Number(0.0)),
+                Err(zero.clone())
+            );
+            assert_eq!(
+                binary(operator, &Number(0.0), &Number(-0.0)),
+                Err(zero.clone())
+            );
+        }
+        assert_eq!(
+            binary(TokenType::Percent, &Integer(-7), &Integer(3)),
+            Ok(Integer(-1))
+        );
+        assert_eq!(
+            binary(TokenType::Percent, &Number(5.5), &Integer(2)),
+            Ok(Number(1.5))
+        );
+    }
+
+    #[test]
+    fn test_nan_comparisons() {
+        let nan = Number(f64::NAN);
+        for operator in [
+            TokenType::Less,
+            TokenType::LessEqual,
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::EqualEqual,
+        ] {
+            assert_eq!(binary(operator, &nan, &Integer(1)), Ok(False));
+            assert_eq!(binary(operator, &Number(1.0), &nan), Ok(False));
+            assert_eq!(binary(operator, &nan, &nan), Ok(False));
+        }
+        assert_eq!(binary(TokenType::BangEqual, &nan, &nan), Ok(True));
+    }
+
+    #[test]
+    fn test_negative_zero() {
+        assert_eq!(unary(TokenType::Minus, &Integer(0)), Ok(Integer(0)));
+        assert_eq!(
+            binary(TokenType::EqualEqual, &Number(-0.0), &Integer(0)),
+            Ok(True)
+        );
+        assert_eq!(number_text(&Number(-0.0)), "0");
+        assert_eq!(
+            binary(
+                TokenType::Plus,
+                &StringValue("x".to_string()),
+                &Number(-0.0)
+            ),
+            Ok(StringValue("x0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        let string = |s: &str| StringValue(s.to_string());
+        assert_eq!(
+            binary(TokenType::Plus, &string("foo"), &string("bar")),
+            Ok(string("foobar"))
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &string("count: "), &Integer(3)),
+            Ok(string("count: 3"))
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &Integer(3), &string(" left")),
+            Ok(string("3 left"))
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &string("x = "), &Number(2.5)),
+            Ok(string("x = 2.5"))
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &Number(2.0), &string("")),
+            Ok(string("2"))
+        );
+    }
+
+    #[test]
+    fn test_addition_errors() {
+        assert_eq!(
+            binary(TokenType::Plus, &Integer(1), &Nil),
+            Err("Can't add number and nil.".to_string())
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &True, &Number(1.5)),
+            Err("Can't add bool and number.".to_string())
+        );
+        assert_eq!(
+            binary(TokenType::Plus, &StringValue("a".to_string()), &False),
+            Err("Can't add string and bool.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_operand_errors() {
+        let string = StringValue("a".to_string());
+        assert_eq!(
+            binary(TokenType::Less, &Integer(1), &string),
+            Err("Operands must be numbers.".to_string())
+        );
+        assert_eq!(
+            binary(TokenType::Ampersand, &Number(1.5), &Integer(1)),
+            Err("Operands must be integers.".to_string())
+        );
+        assert_eq!(
+            binary(TokenType::LessLess, &Integer(1), &Integer(64)),
+            Err("Shift amount must be between 0 and 63.".to_string())
+        );
+        assert_eq!(
+            binary(TokenType::Slash, &Integer(1), &Integer(0)),
+            Err("Division by zero.".to_string())
+        );
+        assert_eq!(
+            unary(TokenType::Minus, &True),
+            Err("Operand must be a number.".to_string())
+        );
+    }
+}
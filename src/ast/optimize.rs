@@ -0,0 +1,341 @@
+use crate::ast::expression::{Expression, LiteralValue};
+use crate::ast::operators;
+use crate::ast::statement::Statement;
+use crate::lexer::token::{string_lexeme, LiteralValue as TokenLiteralValue, Token, TokenType};
+
+/// Collapses operators whose operands are all literals into a single literal.
+///
+/// Operators are applied as the interpreter applies them, and anything that
+/// would fail at runtime, like division by zero or operands of the wrong
+/// type, keeps its node so the interpreter reports it.
+pub fn fold_constants(mut expression: Expression) -> Expression {
+    fold(&mut expression);
+    expression
+}
+
+/// Folds the expression where it is.
+fn fold(expression: &mut Expression) {
+    match expression {
+        Expression::ArrayLiteral { elements, .. } => elements.iter_mut().for_each(fold),
+        Expression::Assign { value, .. } => fold(value),
+        Expression::Binary { .. } | Expression::Logical { .. } => fold_chain(expression),
+        Expression::Call {
+            callee, arguments, ..
+        } => {
+            fold(callee);
+            arguments.iter_mut().for_each(fold);
+        }
+        Expression::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            fold(condition);
+            fold(then_branch);
+            fold(else_branch);
+        }
+        Expression::Get { object, .. } => fold(object),
+        Expression::Grouping {
+            expression: inner, ..
+        } => {
+            fold(inner);
+            if let Expression::Literal { .. } = **inner {
+                *expression = inner.take();
+            }
+        }
+        Expression::Index { object, index, .. } => {
+            fold(object);
+            fold(index);
+        }
+        Expression::Lambda { body, .. } => *body = fold_statements(std::mem::take(body)),
+        Expression::Set { object, value, .. } => {
+            fold(object);
+            fold(value);
+        }
+        Expression::Unary { operator, right } => {
+            fold(right);
+            if let Expression::Literal { token, value } = &**right {
+                if let Ok(value) = operators::unary(operator.token_type, value) {
+                    *expression = literal(value, operator, token);
+                }
+            }
+        }
+        Expression::Literal { .. }
+        | Expression::Super { .. }
+        | Expression::This { .. }
+        | Expression::Variable { .. } => (),
+    }
+}
+
+/// A chain of operators grouping to the left, like `1 + 2 + 3`, nests as deep
+/// as it's long. So rather than recursing down it, it's taken apart into its
+/// leftmost operand and the operators after it, then folded back together
+/// from the left in a loop.
+fn fold_chain(expression: &mut Expression) {
+    let mut links = Vec::new();
+    let mut leftmost = expression.take();
+    loop {
+        let (logical, left, operator, right) = match &mut leftmost {
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => (false, left, operator, right),
+            Expression::Logical {
+                left,
+                operator,
+                right,
+            } => (true, left, operator, right),
+            _ => break,
+        };
+        links.push((logical, operator.clone(), right.take()));
+        leftmost = left.take();
+    }
+    fold(&mut leftmost);
+
+    let mut folded = leftmost;
+    for (logical, operator, mut right) in links.into_iter().rev() {
+        fold(&mut right);
+        folded = if logical {
+            Expression::Logical {
+                left: Box::new(folded),
+                operator,
+                right: Box::new(right),
+            }
+        } else {
+            binary(folded, operator, right)
+        };
+    }
+    *expression = folded;
+}
+
+/// A binary node, or the literal it comes to if both operands are literals.
+fn binary(left: Expression, operator: Token, right: Expression) -> Expression {
+    if let (
+        Expression::Literal {
+            token: left_token,
+            value: left_value,
+        },
+        Expression::Literal {
+            token: right_token,
+            value: right_value,
+        },
+    ) = (&left, &right)
+    {
+        if let Ok(value) = operators::binary(operator.token_type, left_value, right_value) {
+            return literal(value, left_token, right_token);
+        }
+    }
+
+    Expression::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+/// Folds every expression in the statement, including inside nested blocks
+/// and function bodies.
+pub fn fold_statement(statement: Statement) -> Statement {
+    let fold_boxed = |statement: Box<Statement>| Box::new(fold_statement(*statement));
+    match statement {
+        Statement::Block(statements) => Statement::Block(fold_statements(statements)),
+        Statement::Class {
+            name,
+            superclass,
+            methods,
+        } => Statement::Class {
+            name,
+            superclass,
+            methods: fold_statements(methods),
+        },
+        Statement::Const { name, initializer } => Statement::Const {
+            name,
+            initializer: fold_constants(initializer),
+        },
+        Statement::DoWhile { body, condition } => Statement::DoWhile {
+            body: fold_boxed(body),
+            condition: fold_constants(condition),
+        },
+        Statement::Expression(expression) => Statement::Expression(fold_constants(expression)),
+        Statement::Function { name, params, body } => Statement::Function {
+            name,
+            params,
+            body: fold_statements(body),
+        },
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Statement::If {
+            condition: fold_constants(condition),
+            then_branch: fold_boxed(then_branch),
+            else_branch: else_branch.map(fold_boxed),
+        },
+        Statement::Match {
+            subject,
+            arms,
+            default,
+        } => Statement::Match {
+            subject: fold_constants(subject),
+            arms: arms
+                .into_iter()
+                .map(|(pattern, body)| (fold_constants(pattern), fold_statement(body)))
+                .collect(),
+            default: default.map(fold_boxed),
+        },
+        Statement::Return { keyword, value } => Statement::Return {
+            keyword,
+            value: value.map(fold_constants),
+        },
+        Statement::Say(expression) => Statement::Say(fold_constants(expression)),
+        Statement::Var { name, initializer } => Statement::Var {
+            name,
+            initializer: initializer.map(fold_constants),
+        },
+        Statement::While { condition, body } => Statement::While {
+            condition: fold_constants(condition),
+            body: fold_boxed(body),
+        },
+        statement @ (Statement::Break { .. } | Statement::Continue { .. }) => statement,
+    }
+}
+
+pub fn fold_statements(statements: Vec<Statement>) -> Vec<Statement> {
+    statements.into_iter().map(fold_statement).collect()
+}
+
+/// A literal node standing in for everything from `first` to `last`.
+fn literal(value: LiteralValue, first: &Token, last: &Token) -> Expression {
+    let (token_type, literal) = match &value {
+        LiteralValue::Integer(x) => (TokenType::Number, Some(TokenLiteralValue::IntValue(*x))),
+        LiteralValue::Number(x) => (TokenType::Number, Some(TokenLiteralValue::FValue(*x))),
+        LiteralValue::StringValue(s) => (
+            TokenType::StringLiteral,
+            Some(TokenLiteralValue::StringValue(s.clone())),
+        ),
+        LiteralValue::True => (TokenType::True, None),
+        LiteralValue::False => (TokenType::False, None),
+        LiteralValue::Nil => (TokenType::Nil, None),
+    };
+    let lexeme = match &value {
+        LiteralValue::StringValue(s) => string_lexeme(s),
+        value => value.to_string(),
+    };
+    let token = Token::new(
+        token_type,
+        lexeme,
+        literal,
+        first.line_number,
+        first.column,
+        (first.span.0, last.span.1),
+    );
+
+    Expression::Literal { token, value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parser::Parser;
+    use crate::lexer::lexer::Lexer;
+
+    fn fold(source: &str) -> String {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+        fold_constants(expression).to_string()
+    }
+
+    #[test]
+    fn test_fold_arithmetic() {
+        assert_eq!(fold("1 + 2 * 3"), "7");
+        assert_eq!(fold("-(4)"), "-4");
+        assert_eq!(fold("(1 + 2.5) * 2"), "7.0");
+        assert_eq!(fold("7 / 2"), "3.5");
+        assert_eq!(fold("2 ** 10 % 1000"), "24");
+    }
+
+    #[test]
+    fn test_fold_logic_and_strings() {
+        assert_eq!(fold("!True"), "False");
+        assert_eq!(fold("!Nil"), "True");
+        assert_eq!(fold("!0"), "False");
+        assert_eq!(fold("1 < 2 == True"), "True");
+        assert_eq!(fold("\"a\" + \"b\""), "ab");
+    }
+
+    #[test]
+    fn test_fold_keeps_runtime_errors() {
+        assert_eq!(fold("1 / 0"), "(/ 1 0)");
+        assert_eq!(fold("(2 + 3) / (1 - 1)"), "(/ 5 0)");
+        assert_eq!(fold("1.5 % 0.0"), "(% 1.5 0.0)");
+        assert_eq!(fold("\"a\" - 1"), "(- a 1)");
+    }
+
+    #[test]
+    fn test_fold_leaves_variables() {
+        assert_eq!(fold("x + 1 * 2"), "(+ x 2)");
+        assert_eq!(fold("f(1 + 1, [2 * 2])"), "(call f 2 (array 4))");
+        assert_eq!(fold("(x)"), "(group x)");
+    }
+
+    #[test]
+    fn test_fold_long_chain() {
+        // Each operator nests one level deeper, far too deep to recurse down.
+        let source = format!("1{}", " + 1".repeat(9_999));
+        assert_eq!(fold(&source), "10000");
+
+        let source = format!("x{}", " + 1 * 2".repeat(10_000));
+        let folded = fold(&source);
+        assert!(folded.starts_with(&"(+ ".repeat(10_000)));
+        assert!(folded.ends_with(&" 2)".repeat(10_000)));
+
+        let source = format!("x{} or 2 - 1", " or 2 - 1".repeat(10_000));
+        assert!(fold(&source).ends_with(" 1) 1)"));
+    }
+
+    #[test]
+    fn test_fold_statements() {
+        let tokens = Lexer::new("fun f() { return 60 * 60; } say 1 + 1;")
+            .scan_tokens()
+            .unwrap();
+        let statements = fold_statements(Parser::new(tokens).parse_program().unwrap());
+        let printed: Vec<String> = statements.iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(printed, ["(fun f () (return 3600))", "(say 2)"]);
+    }
+
+    #[test]
+    fn test_folded_string_lexes_back() {
+        let tokens = Lexer::new(r#""say \"" + 'hi\\' + "\n\u{7}""#)
+            .scan_tokens()
+            .unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+
+        match &fold_constants(expression) {
+            Expression::Literal { token, .. } => {
+                assert_eq!(token.lexeme, r#""say \"hi\\\n\u{7}""#);
+                let relexed = Lexer::new(&token.lexeme).scan_tokens().unwrap();
+                assert_eq!(relexed[0].literal, token.literal);
+            }
+            other => panic!("Expected a literal, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_folded_literal_spans_operands() {
+        let tokens = Lexer::new("say 10 + 20").scan_tokens().unwrap();
+        let expression = Parser::new(tokens[1..].to_vec())
+            .parse_expression()
+            .unwrap();
+
+        match &fold_constants(expression) {
+            Expression::Literal { token, .. } => {
+                assert_eq!(token.lexeme, "30");
+                assert_eq!(token.span, (4, 11));
+            }
+            other => panic!("Expected a literal, got {}", other),
+        }
+    }
+}
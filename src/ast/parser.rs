@@ -1,262 +1,2685 @@
+use crate::ast::error::ParserError;
 use crate::ast::expression::{Expression, LiteralValue};
+use crate::ast::statement::Statement;
 use crate::lexer::token::{
     Token, TokenType,
     TokenType::{
-        Bang, BangEqual, Class, EqualEqual, For, Function, Greater, GreaterEqual, If, LeftParen,
-        Less, LessEqual, Minus, Plus, Return, RightParen, Say, SemiColon, Slash, Star, Variable,
-        While, True, False, Nil, Number, StringLiteral
+        Ampersand, And, Arrow, Bang, BangEqual, Break, Caret, Class, Colon, Comma, Const, Continue,
+        Do, Dot, Elif, Else, Equal, EqualEqual, False, FatArrow, For, Function, Greater,
+        GreaterEqual, GreaterGreater, Identifier, If, LeftBrace, LeftBracket, LeftParen, Less,
+        LessEqual, LessLess, Match, Minus, Nil, Number, Or, Percent, Pipe, PipeGreater, Plus,
+        Question, QuestionQuestion, Return, RightBrace, RightBracket, RightParen, Say, SemiColon,
+        Slash, Star, StarStar, StringLiteral, Super, This, Tilde, True, Variable, While,
     },
 };
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// How many loops enclose the statement being parsed, so `break` and
+    /// `continue` outside of one can be rejected.
+    loop_depth: usize,
+    /// Errors that don't stop the parse, reported alongside the rest.
+    errors: Vec<ParserError>,
+    /// How deeply the expression being parsed is nested, so pathological
+    /// input fails with an error instead of overflowing the stack, here or
+    /// in any later pass over the tree.
+    nesting: usize,
+    /// Stands in for the Eof token when reading past the end of a stream that
+    /// is empty or was built without one.
+    end_of_input: Token,
 }
 
+/// The most arguments a call, or parameters a function, may have.
+const MAX_ARGUMENTS: usize = 255;
+
+/// How deeply expressions may nest inside one another, counting every
+/// parenthesis, prefix operator, operand, call, index, property, pipe,
+/// assignment and conditional.
+///
+/// Chains of binary and logical operators like `1 + 2 + 3` are the exception:
+/// they can be any length, though each operator nests the ones before it a
+/// level deeper. Every pass over the tree walks such a chain in a loop; see
+/// `Expression::operator_chain`.
+const MAX_NESTING: usize = 256;
+
+/// How tightly an infix operator binds, from loosest to tightest.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    None,
+    Comma,
+    Assignment,
+    Conditional,
+    Pipe,
+    Coalesce,
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equality,
+    Comparison,
+    Shift,
+    Term,
+    Factor,
+    Power,
+    Unary,
+    Call,
+}
+
+impl Precedence {
+    /// The precedence an operator's right operand is parsed at. Operands of
+    /// right-associative operators may contain the same operator again.
+    fn operand(self) -> Self {
+        use Precedence::*;
+
+        match self {
+            Assignment | Conditional | Coalesce | Power => self,
+            None => Comma,
+            Comma => Assignment,
+            Pipe => Coalesce,
+            Or => And,
+            And => BitOr,
+            BitOr => BitXor,
+            BitXor => BitAnd,
+            BitAnd => Equality,
+            Equality => Comparison,
+            Comparison => Shift,
+            Shift => Term,
+            Term => Factor,
+            Factor => Power,
+            Unary | Call => Call,
+        }
+    }
+}
+
+type PrefixRule = fn(&mut Parser) -> Result<Expression, ParserError>;
+type InfixRule = fn(&mut Parser, Expression) -> Result<Expression, ParserError>;
+
+/// How a token is parsed at the start of an expression, how it is parsed
+/// after a left operand, and how tightly it binds there. Adding an operator
+/// is a matter of adding its row.
+fn rule(token_type: TokenType) -> (Option<PrefixRule>, Option<InfixRule>, Precedence) {
+    let missing_left: Option<PrefixRule> = Some(Parser::missing_left_operand);
+
+    match token_type {
+        Comma => (None, Some(Parser::binary), Precedence::Comma),
+        Equal => (None, Some(Parser::assignment), Precedence::Assignment),
+        Question => (None, Some(Parser::conditional), Precedence::Conditional),
+        PipeGreater => (missing_left, Some(Parser::pipe), Precedence::Pipe),
+        QuestionQuestion => (missing_left, Some(Parser::binary), Precedence::Coalesce),
+        Or => (missing_left, Some(Parser::logical), Precedence::Or),
+        And => (missing_left, Some(Parser::logical), Precedence::And),
+        Pipe => (missing_left, Some(Parser::binary), Precedence::BitOr),
+        Caret => (missing_left, Some(Parser::binary), Precedence::BitXor),
+        Ampersand => (missing_left, Some(Parser::binary), Precedence::BitAnd),
+        BangEqual | EqualEqual => (missing_left, Some(Parser::binary), Precedence::Equality),
+        Greater | GreaterEqual | Less | LessEqual => {
+            (missing_left, Some(Parser::binary), Precedence::Comparison)
+        }
+        LessLess | GreaterGreater => (missing_left, Some(Parser::binary), Precedence::Shift),
+        Minus => (Some(Parser::unary), Some(Parser::binary), Precedence::Term),
+        Plus => (missing_left, Some(Parser::binary), Precedence::Term),
+        Slash | Star | Percent => (missing_left, Some(Parser::binary), Precedence::Factor),
+        StarStar => (missing_left, Some(Parser::binary), Precedence::Power),
+        Bang | Tilde => (Some(Parser::unary), None, Precedence::None),
+        LeftParen => (Some(Parser::grouping), Some(Parser::call), Precedence::Call),
+        LeftBracket => (
+            Some(Parser::array_literal),
+            Some(Parser::index),
+            Precedence::Call,
+        ),
+        Dot => (None, Some(Parser::get), Precedence::Call),
+        Function => (Some(Parser::function_lambda), None, Precedence::None),
+        Identifier => (Some(Parser::variable), None, Precedence::None),
+        False | True | Nil | Number | StringLiteral => {
+            (Some(Parser::literal), None, Precedence::None)
+        }
+        This => (Some(Parser::this), None, Precedence::None),
+        Super => (Some(Parser::super_method), None, Precedence::None),
+        _ => (None, None, Precedence::None),
+    }
+}
+
+/// What a line typed at the REPL turned out to be.
+pub enum ReplLine {
+    Statements(Vec<Statement>),
+    Expression(Expression),
+}
+
+// A program is a list of statements, each ending in `;`:
+//
+// program     -> declaration* EOF
+// declaration -> varDecl | constDecl | funDecl | classDecl | statement
+// funDecl     -> "fun" function
+// classDecl   -> "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}"
+// function    -> IDENTIFIER "(" parameters? ")" block
+// parameters  -> IDENTIFIER ( "," IDENTIFIER )*
+// varDecl     -> "var" IDENTIFIER ( "=" expression )? ";"
+// constDecl   -> "const" IDENTIFIER "=" expression ";"
+// statement   -> "say" expression ";" | ifStmt | whileStmt | doWhileStmt | forStmt
+//              | matchStmt | block | "break" ";" | "continue" ";"
+//              | "return" expression? ";" | expression ";"
+// ifStmt      -> "if" "(" expression ")" statement
+//                ( "elif" "(" expression ")" statement )* ( "else" statement )?
+// whileStmt   -> "while" "(" expression ")" statement
+// doWhileStmt -> "do" statement "while" "(" expression ")" ";"
+// matchStmt   -> "match" "(" expression ")" "{" arm* "}"
+// arm         -> ( expression | "else" ) "=>" statement
+// forStmt     -> "for" "(" ( varDecl | expression ";" | ";" ) expression? ";"
+//                expression? ")" statement
+// block       -> "{" declaration* "}"
+//
+// Expression grammar, from lowest to highest precedence. The logical and
+// bitwise levels follow C, sitting between the conditional and equality, with
+// shifts binding tighter than comparison but looser than addition. As in C#,
+// `??` binds looser than `or`, and the comma operator is lowest of all. The
+// pipe `x |> f` calls `f(x)`:
+//
+// expression  -> comma
+// comma       -> assignment ( "," assignment )*
+// assignment  -> ( postfix "." )? IDENTIFIER "=" assignment | conditional
+// conditional -> pipe ( "?" expression ":" conditional )?
+// pipe        -> coalesce ( "|>" coalesce )*
+// coalesce    -> logic_or ( "??" coalesce )?
+// logic_or    -> logic_and ( "or" logic_and )*
+// logic_and   -> bit_or ( "and" bit_or )*
+// bit_or      -> bit_xor ( "|" bit_xor )*
+// bit_xor     -> bit_and ( "^" bit_and )*
+// bit_and     -> equality ( "&" equality )*
+// equality    -> comparison ( ( "!=" | "==" ) comparison )*
+// comparison  -> shift ( ( ">" | ">=" | "<" | "<=" ) shift )*
+// shift       -> term ( ( "<<" | ">>" ) term )*
+// term        -> factor ( ( "-" | "+" ) factor )*
+// factor      -> power ( ( "/" | "*" | "%" ) power )*
+// power       -> unary ( "**" power )?
+// unary       -> ( "!" | "-" | "~" ) unary | postfix
+// postfix     -> primary ( "[" expression "]" | "(" arguments? ")" | "." IDENTIFIER )*
+// arguments   -> assignment ( "," assignment )*
+//
+// Exponentiation is right-associative, and prefix operators bind tighter than
+// it, so `-2 ** 2` is `(-2) ** 2` (unlike Python, where unary minus binds
+// looser than `**`).
+// primary     -> NUMBER | STRING | "True" | "False" | "Nil" | "this" | IDENTIFIER
+//              | "super" "." IDENTIFIER
+//              | "(" expression ")"
+//              | "[" ( assignment ( "," assignment )* ","? )? "]" | lambda
+// lambda      -> "fun" "(" parameters? ")" ( "->" assignment | block )
+//              | "(" parameters? ")" "->" assignment
+//
+// Without `fun`, a parenthesised list is only a lambda if `->` follows it.
+// Wherever a comma separates things, each of them is parsed at assignment
+// precedence, so `f(a, b)` has two arguments rather than one comma expression.
+//
+// Expressions are parsed by precedence climbing rather than a function per
+// level: `rule` gives each token's prefix and infix parse and its precedence.
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        let end_of_input = Self::end_of_input(&tokens);
+        Self {
+            tokens,
+            current: 0,
+            loop_depth: 0,
+            errors: vec![],
+            nesting: 0,
+            end_of_input,
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Expression, String> {
-        self.expression()
-    }
+    pub fn parse_program(&mut self) -> Result<Vec<Statement>, Vec<ParserError>> {
+        let (statements, errors) = self.parse_lossy();
 
-    fn expression(&mut self) -> Result<Expression, String> {
-        self.equality()
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(statements)
     }
 
-    fn comparison(&mut self) -> Result<Expression, String> {
-        let mut expression = self.term()?;
+    /// Parses the whole token stream as a single expression, with no `;`.
+    pub fn parse_expression(&mut self) -> Result<Expression, Vec<ParserError>> {
+        let expression = self.expression().and_then(|expression| {
+            if self.is_at_end() {
+                Ok(expression)
+            } else {
+                Err(ParserError::new(
+                    self.peek().clone(),
+                    "Expected end of expression",
+                ))
+            }
+        });
 
-        while self.match_tokens(&[Greater, GreaterEqual, Less, LessEqual]) {
-            let operator = self.previous();
-            let right = self.term()?;
-            expression = Expression::Binary {
-                left: Box::from(expression),
-                operator: operator,
-                right: Box::from(right),
+        let mut errors = std::mem::take(&mut self.errors);
+        match expression {
+            Ok(expression) if errors.is_empty() => Ok(expression),
+            Ok(_) => Err(errors),
+            Err(error) => {
+                errors.push(error);
+                Err(errors)
             }
         }
-        Ok(expression)
     }
 
-    fn term(&mut self) -> Result<Expression, String> {
-        let mut expression = self.factor()?;
+    /// Parses a line typed at the REPL. It is parsed as statements, unless
+    /// the only thing wrong with it is that it's an expression lacking its
+    /// `;`, in which case it's a bare expression whose value gets printed.
+    pub fn parse_repl_line(&mut self) -> Result<ReplLine, Vec<ParserError>> {
+        let errors = match self.parse_program() {
+            Ok(statements) => return Ok(ReplLine::Statements(statements)),
+            Err(errors) => errors,
+        };
 
-        while self.match_tokens(&[Minus, Plus]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expression = Expression::Binary {
-                left: Box::from(expression),
-                operator: operator,
-                right: Box::from(right),
+        if errors.len() == 1 {
+            let mut parser = Parser::new(self.tokens.clone());
+            if let Ok(expression) = parser.parse_expression() {
+                return Ok(ReplLine::Expression(expression));
             }
         }
 
-        Ok(expression)
+        Err(errors)
     }
 
-    fn factor(&mut self) -> Result<Expression, String> {
-        let mut expression = self.unary()?;
-        while self.match_tokens(&[Slash, Star]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            expression = Expression::Binary {
-                left: Box::from(expression),
-                operator: operator,
-                right: Box::from(right),
+    /// Parses statements until Eof, keeping every statement that parsed
+    /// alongside the errors. After an error the parser skips to the next
+    /// statement boundary and carries on, so every error is reported.
+    pub fn parse_lossy(&mut self) -> (Vec<Statement>, Vec<ParserError>) {
+        let mut statements = vec![];
+
+        while !self.is_at_end() {
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(msg) => {
+                    self.errors.push(msg);
+                    self.synchronize();
+                    // A stray `}` has no block to close at the top level.
+                    if self.check(&RightBrace) {
+                        self.advance();
+                    }
+                }
             }
         }
 
-        Ok(expression)
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    fn declaration(&mut self) -> Result<Statement, ParserError> {
+        if self.match_token(&Variable) {
+            return self.var_declaration();
+        }
+        if self.match_token(&Const) {
+            return self.const_declaration();
+        }
+        if self.match_token(&Class) {
+            return self.class_declaration();
+        }
+        // `fun (` with no name is a lambda, which is parsed as an expression.
+        if self.check_sequence(&[Function, Identifier]) {
+            self.advance();
+            self.advance();
+            return self.function_declaration();
+        }
+
+        self.statement()
     }
 
-    fn unary(&mut self) -> Result<Expression, String> {
-        if self.match_tokens(&[Bang, BangEqual]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            Ok(Expression::Unary {
-                operator: operator,
-                right: Box::from(right),
+    fn class_declaration(&mut self) -> Result<Statement, ParserError> {
+        let name = self.consume(Identifier, "Expected class name after 'class'")?;
+
+        let superclass = if self.match_token(&Less) {
+            let superclass = self.consume(Identifier, "Expected superclass name after '<'")?;
+            Some(Expression::Variable {
+                name: superclass,
+                distance: None,
             })
         } else {
-            self.primary()
+            None
+        };
+
+        self.consume(LeftBrace, "Expected '{' before class body")?;
+
+        let methods = self.methods()?;
+        if !self.match_token(&RightBrace) {
+            return Err(ParserError::new(name, "Expected '}' after class body"));
         }
+
+        Ok(Statement::Class {
+            name,
+            superclass,
+            methods,
+        })
     }
 
-    fn primary(&mut self) -> Result<Expression, String> {
-        let token = self.peek();
-        let result;
+    fn methods(&mut self) -> Result<Vec<Statement>, ParserError> {
+        let mut methods = vec![];
 
-        match token.token_type {
-            LeftParen => {
-                self.advance();
-                let expression = self.expression()?;
-                self.consume(RightParen, "Expected ')'")?;
-                result = Expression::Grouping {
-                    expression: Box::from(expression),
-                }
-            }
-            False | True | Nil | Number | StringLiteral => {
-                self.advance();
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            self.consume(Identifier, "Expected method name in class body")?;
+            methods.push(self.function_declaration()?);
+        }
+
+        Ok(methods)
+    }
+
+    /// Parses a named function once its name has been consumed, for both
+    /// `fun` declarations and methods.
+    fn function_declaration(&mut self) -> Result<Statement, ParserError> {
+        let name = self.previous().clone();
+        self.consume(LeftParen, "Expected '(' after function name")?;
+        let params = self.parameters()?;
+        self.consume(LeftBrace, "Expected '{' before function body")?;
+        let body = self.function_body()?;
+
+        Ok(Statement::Function { name, params, body })
+    }
+
+    /// Parses a parameter list once its `(` has been consumed, up to and
+    /// including the `)`.
+    fn parameters(&mut self) -> Result<Vec<Token>, ParserError> {
+        let mut params = vec![];
 
-                result = Expression::Literal {
-                    value: LiteralValue::from_token(token),
+        if !self.check(&RightParen) {
+            loop {
+                let param = self.consume(Identifier, "Expected parameter name")?;
+                if params.len() == MAX_ARGUMENTS {
+                    self.errors.push(ParserError::new(
+                        param.clone(),
+                        &format!("Can't have more than {} parameters", MAX_ARGUMENTS),
+                    ));
+                }
+                params.push(param);
+                if !self.match_token(&Comma) {
+                    break;
                 }
             }
-            _ => return Err("Expected expression".to_string()),
         }
 
-        Ok(result)
+        self.consume(RightParen, "Expected ')' after parameters")?;
+
+        Ok(params)
     }
 
-    fn consume(&mut self, token_type: TokenType, msg: &str) -> Result<(), String> {
-        let token = self.peek();
-        if token.token_type == token_type {
-            self.advance();
-            Ok(())
+    /// Parses a function's block body once its `{` has been consumed. Loops
+    /// around the function don't extend into it.
+    fn function_body(&mut self) -> Result<Vec<Statement>, ParserError> {
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.block();
+        self.loop_depth = loop_depth;
+
+        body
+    }
+
+    fn var_declaration(&mut self) -> Result<Statement, ParserError> {
+        let declaration = self.var_binding()?;
+        self.end_statement("variable declaration")?;
+
+        Ok(declaration)
+    }
+
+    /// Parses a `var` declaration up to, but not including, its `;`.
+    fn var_binding(&mut self) -> Result<Statement, ParserError> {
+        let name = self.declared_name("var")?;
+        let initializer = if self.match_token(&Equal) {
+            Some(self.expression()?)
         } else {
-            Err(msg.to_string())
+            None
+        };
+
+        Ok(Statement::Var { name, initializer })
+    }
+
+    fn const_declaration(&mut self) -> Result<Statement, ParserError> {
+        let name = self.declared_name("const")?;
+        if !self.match_token(&Equal) {
+            let message = format!("Const '{}' requires an initializer", name.lexeme);
+            return Err(ParserError::new(name, &message));
         }
+        let initializer = self.expression()?;
+        self.end_statement("const declaration")?;
+
+        Ok(Statement::Const { name, initializer })
     }
 
-    fn match_token(&mut self, _type: &TokenType) -> bool {
-        if self.is_at_end() {
-            false
+    /// Consumes the identifier following a `var` or `const` keyword.
+    fn declared_name(&mut self, keyword: &str) -> Result<Token, ParserError> {
+        self.consume(
+            Identifier,
+            &format!("Expected variable name after '{}'", keyword),
+        )
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParserError> {
+        if self.match_token(&Say) {
+            let expression = self.expression()?;
+            self.end_statement("value")?;
+            return Ok(Statement::Say(expression));
+        }
+        if self.match_token(&If) {
+            return self.if_statement("if");
+        }
+        if self.match_token(&While) {
+            return self.while_statement();
+        }
+        if self.match_token(&Do) {
+            return self.do_while_statement();
+        }
+        if self.match_token(&Match) {
+            return self.match_statement();
+        }
+        if self.match_token(&For) {
+            return self.for_statement();
+        }
+        if self.match_tokens(&[Break, Continue]) {
+            return self.loop_jump();
+        }
+        if self.match_token(&Return) {
+            return self.return_statement();
+        }
+        if self.match_token(&LeftBrace) {
+            return Ok(Statement::Block(self.block()?));
+        }
+
+        let expression = self.expression()?;
+        self.end_statement("expression")?;
+        Ok(Statement::Expression(expression))
+    }
+
+    /// Parses an `if` or `elif` once the keyword has been consumed. An `elif`
+    /// chain becomes nested ifs in the else branch, and an `else` binds to
+    /// the nearest `if`.
+    fn if_statement(&mut self, keyword: &str) -> Result<Statement, ParserError> {
+        self.consume(LeftParen, &format!("Expected '(' after '{}'", keyword))?;
+        let condition = self.expression()?;
+        self.consume(
+            RightParen,
+            &format!("Expected ')' after {} condition", keyword),
+        )?;
+        let then_branch = self.statement()?;
+
+        let else_branch = if self.match_token(&Elif) {
+            Some(Box::from(self.if_statement("elif")?))
+        } else if self.match_token(&Else) {
+            Some(Box::from(self.statement()?))
         } else {
-            if self.peek().token_type == *_type {
-                self.advance();
-                true
+            None
+        };
+
+        Ok(Statement::If {
+            condition,
+            then_branch: Box::from(then_branch),
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Statement, ParserError> {
+        self.consume(LeftParen, "Expected '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(RightParen, "Expected ')' after while condition")?;
+
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+
+        Ok(Statement::While {
+            condition,
+            body: Box::from(body?),
+        })
+    }
+
+    /// Parses a `match` once the keyword has been consumed. A second `else`
+    /// arm is reported, but parsing carries on with the first one kept.
+    fn match_statement(&mut self) -> Result<Statement, ParserError> {
+        self.consume(LeftParen, "Expected '(' after 'match'")?;
+        let subject = self.expression()?;
+        self.consume(RightParen, "Expected ')' after match subject")?;
+        let brace = self.consume(LeftBrace, "Expected '{' before match arms")?;
+
+        let mut arms = vec![];
+        let mut default = None;
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            if self.match_token(&Else) {
+                let keyword = self.previous().clone();
+                self.consume(FatArrow, "Expected '=>' after 'else'")?;
+                let body = self.statement()?;
+                if default.is_some() {
+                    self.errors
+                        .push(ParserError::new(keyword, "Duplicate 'else' arm in match"));
+                } else {
+                    default = Some(Box::from(body));
+                }
             } else {
-                false
+                let pattern = self.expression()?;
+                self.consume(FatArrow, "Expected '=>' after match pattern")?;
+                arms.push((pattern, self.statement()?));
             }
         }
+        if !self.match_token(&RightBrace) {
+            return Err(ParserError::new(brace, "Expected '}' after match arms"));
+        }
+
+        Ok(Statement::Match {
+            subject,
+            arms,
+            default,
+        })
     }
 
-    fn match_tokens(&mut self, types: &[TokenType]) -> bool {
-        for typ in types {
-            if self.match_token(typ) {
-                return true;
+    fn do_while_statement(&mut self) -> Result<Statement, ParserError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        let body = body?;
+
+        self.consume(While, "Expected 'while' after do-while body")?;
+        self.consume(LeftParen, "Expected '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(RightParen, "Expected ')' after while condition")?;
+        self.end_statement("do-while condition")?;
+
+        Ok(Statement::DoWhile {
+            body: Box::from(body),
+            condition,
+        })
+    }
+
+    /// Desugars `for (init; condition; increment) body` into
+    /// `{ init; while (condition) { body; increment; } }`, leaving out the
+    /// parts whose clause is empty. A missing condition loops forever.
+    fn for_statement(&mut self) -> Result<Statement, ParserError> {
+        self.consume(LeftParen, "Expected '(' after 'for'")?;
+
+        let initializer = if self.match_token(&SemiColon) {
+            None
+        } else {
+            let initializer = if self.match_token(&Variable) {
+                self.var_binding()?
+            } else {
+                Statement::Expression(self.expression()?)
+            };
+            self.end_statement("for-loop initializer")?;
+            Some(initializer)
+        };
+
+        let condition = if self.check(&SemiColon) {
+            let semi_colon = self.peek();
+            Expression::Literal {
+                token: Token::new(
+                    True,
+                    "True".to_string(),
+                    None,
+                    semi_colon.line_number,
+                    semi_colon.column,
+                    (semi_colon.span.0, semi_colon.span.0),
+                ),
+                value: LiteralValue::True,
             }
+        } else {
+            self.expression()?
+        };
+        self.end_statement("for-loop condition")?;
+
+        let increment = if self.check(&RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(RightParen, "Expected ')' after for-loop increment")?;
+
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+
+        let mut body = body?;
+        if let Some(increment) = increment {
+            body = Statement::Block(vec![body, Statement::Expression(increment)]);
+        }
+        let mut result = Statement::While {
+            condition,
+            body: Box::from(body),
+        };
+        if let Some(initializer) = initializer {
+            result = Statement::Block(vec![initializer, result]);
         }
 
-        false
+        Ok(result)
     }
 
-    fn advance(&mut self) -> Token {
-        if !self.is_at_end() {
-            self.current += 1;
+    /// Parses `break;` or `continue;` once the keyword has been consumed.
+    fn loop_jump(&mut self) -> Result<Statement, ParserError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            let message = format!("'{}' outside of a loop", keyword.lexeme);
+            return Err(ParserError::new(keyword, &message));
         }
+        self.end_statement(&format!("'{}'", keyword.lexeme))?;
 
-        self.previous()
+        match keyword.token_type {
+            Break => Ok(Statement::Break { keyword }),
+            _ => Ok(Statement::Continue { keyword }),
+        }
     }
 
-    fn peek(&mut self) -> Token {
-        self.tokens[self.current].clone()
+    fn return_statement(&mut self) -> Result<Statement, ParserError> {
+        let keyword = self.previous().clone();
+        let value = if self.check(&SemiColon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.end_statement("return value")?;
+
+        Ok(Statement::Return { keyword, value })
     }
 
-    fn previous(&mut self) -> Token {
-        self.tokens[self.current - 1].clone()
+    /// Parses the statements of a block once its `{` has been consumed. An
+    /// unclosed block is reported at the line of the opening brace.
+    fn block(&mut self) -> Result<Vec<Statement>, ParserError> {
+        let brace = self.previous().clone();
+        let mut statements = vec![];
+
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(msg) => {
+                    self.errors.push(msg);
+                    self.synchronize();
+                }
+            }
+        }
+        if !self.match_token(&RightBrace) {
+            return Err(ParserError::new(brace, "Expected '}' after block"));
+        }
+
+        Ok(statements)
     }
 
-    fn is_at_end(&mut self) -> bool {
-        self.peek().token_type == TokenType::Eof
+    /// Consumes the `;` ending a statement. A missing one is reported on the
+    /// line where the statement stopped, not where the next one starts.
+    fn end_statement(&mut self, after: &str) -> Result<(), ParserError> {
+        if self.match_token(&SemiColon) {
+            return Ok(());
+        }
+
+        Err(ParserError::new(
+            self.previous().clone(),
+            &format!("Expected ';' after {}", after),
+        ))
     }
 
-    fn equality(&mut self) -> Result<Expression, String> {
-        let mut expression = self.comparison()?;
+    fn expression(&mut self) -> Result<Expression, ParserError> {
+        self.parse_precedence(Precedence::Comma)
+    }
 
-        while self.match_tokens(&[BangEqual, EqualEqual]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            expression = Expression::Binary {
-                left: Box::from(expression),
-                operator,
-                right: Box::from(right),
-            };
+    /// Parses an expression that stops at a comma, for the places where
+    /// commas separate items.
+    fn argument(&mut self) -> Result<Expression, ParserError> {
+        self.parse_precedence(Precedence::Assignment)
+    }
+
+    /// Parses an expression whose operators all bind at least as tightly as
+    /// `precedence`, one level deeper, failing once the nesting limit is hit.
+    fn parse_precedence(&mut self, precedence: Precedence) -> Result<Expression, ParserError> {
+        let nesting = self.nesting;
+        let result = self.nest().and_then(|()| self.operators(precedence));
+        self.nesting = nesting;
+        result
+    }
+
+    fn nest(&mut self) -> Result<(), ParserError> {
+        if self.nesting == MAX_NESTING {
+            return Err(ParserError::new(
+                self.peek().clone(),
+                &format!("Expression nesting too deep (limit {})", MAX_NESTING),
+            ));
         }
 
-        Ok(expression)
+        self.nesting += 1;
+        Ok(())
     }
 
-    fn synchronize(&mut self) {
+    /// The first token is parsed by its prefix rule, and the expression so
+    /// far then becomes the left operand of each following infix operator
+    /// that binds tightly enough. Each one but a binary or logical operator
+    /// nests it a level deeper; see `MAX_NESTING`.
+    fn operators(&mut self, precedence: Precedence) -> Result<Expression, ParserError> {
+        let prefix = match rule(self.peek().token_type).0 {
+            Some(prefix) => prefix,
+            None => {
+                let message = if self.is_at_end() {
+                    "Unexpected end of input"
+                } else {
+                    "Expected expression"
+                };
+                return Err(ParserError::new(self.peek().clone(), message));
+            }
+        };
         self.advance();
+        let mut expression = prefix(self)?;
 
-        while !self.is_at_end() {
-            if self.previous().token_type == SemiColon {
-                return;
+        loop {
+            match rule(self.peek().token_type) {
+                (_, Some(infix), infix_precedence) if infix_precedence >= precedence => {
+                    self.advance();
+                    expression = infix(self, expression)?;
+                    if !matches!(
+                        expression,
+                        Expression::Binary { .. } | Expression::Logical { .. }
+                    ) {
+                        self.nest()?;
+                    }
+                }
+                _ => break,
             }
+        }
 
-            match self.peek().token_type {
-                Class | Function | Variable | For | If | While | Say | Return => return,
-                _ => (),
+        Ok(expression)
+    }
+
+    fn binary(&mut self, left: Expression) -> Result<Expression, ParserError> {
+        let operator = self.previous().clone();
+        let right = self.parse_precedence(rule(operator.token_type).2.operand())?;
+
+        Ok(Expression::Binary {
+            left: Box::from(left),
+            operator,
+            right: Box::from(right),
+        })
+    }
+
+    fn logical(&mut self, left: Expression) -> Result<Expression, ParserError> {
+        let operator = self.previous().clone();
+        let right = self.parse_precedence(rule(operator.token_type).2.operand())?;
+
+        Ok(Expression::Logical {
+            left: Box::from(left),
+            operator,
+            right: Box::from(right),
+        })
+    }
+
+    /// `x |> f` is sugar for `f(x)`, with the `|>` standing in for the
+    /// call's parenthesis.
+    fn pipe(&mut self, argument: Expression) -> Result<Expression, ParserError> {
+        let paren = self.previous().clone();
+        let callee = self.parse_precedence(Precedence::Pipe.operand())?;
+
+        Ok(Expression::Call {
+            callee: Box::from(callee),
+            paren,
+            arguments: vec![argument],
+        })
+    }
+
+    /// The target is parsed as an ordinary expression first and only then
+    /// checked, since the parser can't know it is an assignment until it
+    /// reaches the `=`.
+    fn assignment(&mut self, mut target: Expression) -> Result<Expression, ParserError> {
+        let equals = self.previous().clone();
+        let value = self.parse_precedence(Precedence::Assignment)?;
+
+        match &mut target {
+            Expression::Variable { name, .. } => Ok(Expression::Assign {
+                name: name.clone(),
+                value: Box::from(value),
+                distance: None,
+            }),
+            Expression::Get { object, name } => Ok(Expression::Set {
+                object: Box::from(object.take()),
+                name: name.clone(),
+                value: Box::from(value),
+            }),
+            _ => Err(ParserError::new(equals, "Invalid assignment target")),
+        }
+    }
+
+    fn conditional(&mut self, condition: Expression) -> Result<Expression, ParserError> {
+        let question = self.previous().clone();
+        let then_branch = self.expression()?;
+        self.consume(Colon, "Expected ':' after then-branch of conditional")?;
+        // Parsing the else branch at the conditional's own precedence makes
+        // `a ? b : c ? d : e` group as `a ? b : (c ? d : e)`.
+        let else_branch = self.parse_precedence(Precedence::Conditional)?;
+
+        Ok(Expression::Conditional {
+            condition: Box::from(condition),
+            question,
+            then_branch: Box::from(then_branch),
+            else_branch: Box::from(else_branch),
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expression, ParserError> {
+        let operator = self.previous().clone();
+        let right = self.parse_precedence(Precedence::Unary)?;
+
+        Ok(Expression::Unary {
+            operator,
+            right: Box::from(right),
+        })
+    }
+
+    /// Parses a call's arguments once its `(` has been consumed.
+    fn call(&mut self, callee: Expression) -> Result<Expression, ParserError> {
+        let mut arguments = vec![];
+
+        if !self.check(&RightParen) {
+            loop {
+                if arguments.len() == MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    self.errors.push(ParserError::new(
+                        token,
+                        &format!("Can't have more than {} arguments", MAX_ARGUMENTS),
+                    ));
+                }
+                arguments.push(self.argument()?);
+                if !self.match_token(&Comma) {
+                    break;
+                }
             }
+        }
 
-            self.advance();
+        let paren = self.consume(RightParen, "Expected ')' after arguments")?;
+
+        Ok(Expression::Call {
+            callee: Box::from(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    fn index(&mut self, object: Expression) -> Result<Expression, ParserError> {
+        let bracket = self.previous().clone();
+        let index = self.expression()?;
+        self.consume(RightBracket, "Expected ']' after index")?;
+
+        Ok(Expression::Index {
+            object: Box::from(object),
+            bracket,
+            index: Box::from(index),
+        })
+    }
+
+    fn get(&mut self, object: Expression) -> Result<Expression, ParserError> {
+        let name = self.consume(Identifier, "Expected property name after '.'")?;
+
+        Ok(Expression::Get {
+            object: Box::from(object),
+            name,
+        })
+    }
+
+    /// A `(` starts a lambda if `->` follows its parameter list, and is a
+    /// grouping otherwise.
+    fn grouping(&mut self) -> Result<Expression, ParserError> {
+        let paren = self.previous().clone();
+        if self.starts_lambda() {
+            return self.lambda(paren);
         }
+        if self.check(&RightParen) {
+            return Err(ParserError::new(
+                self.previous().clone(),
+                "Empty grouping '()' is not a valid expression",
+            ));
+        }
+
+        let expression = self.expression()?;
+        self.consume(RightParen, "Expected ')' after expression")?;
+
+        Ok(Expression::Grouping {
+            paren,
+            expression: Box::from(expression),
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::lexer::lexer::Lexer;
-    use crate::lexer::token::LiteralValue::IntValue;
-    use crate::lexer::token::TokenType::{Number, Plus, SemiColon};
+    fn function_lambda(&mut self) -> Result<Expression, ParserError> {
+        let keyword = self.previous().clone();
+        self.consume(LeftParen, "Expected '(' after 'fun'")?;
+        self.lambda(keyword)
+    }
 
-    #[test]
-    fn test_addition() {
-        let one = Token {
-            token_type: Number,
-            lexeme: "1".to_string(),
-            literal: Some(IntValue(1)),
-            line_number: 0,
-        };
-        let two = Token {
-            token_type: Number,
-            lexeme: "2".to_string(),
-            literal: Some(IntValue(2)),
-            line_number: 0,
-        };
-        let plus = Token {
-            token_type: Plus,
-            lexeme: "+".to_string(),
-            literal: None,
-            line_number: 0,
-        };
-        let semi_colon = Token {
-            token_type: SemiColon,
-            lexeme: ";".to_string(),
-            literal: None,
-            line_number: 0,
-        };
-        let tokens = vec![one, plus, two, semi_colon];
-        let mut parser = Parser::new(tokens);
-        let parsed_expression = parser.parse().unwrap();
-        let string_expression = parsed_expression.to_string();
-        assert_eq!(string_expression, "(+ 1 2)");
+    fn variable(&mut self) -> Result<Expression, ParserError> {
+        Ok(Expression::Variable {
+            name: self.previous().clone(),
+            distance: None,
+        })
     }
 
-    #[test]
-    fn test_comparison() {
-        let source = "1 + 2 == 5 + 7";
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.scan_tokens().unwrap();
-        let mut parser = Parser::new(tokens);
-        let parsed_expression = parser.parse().unwrap();
-        let string_expression = parsed_expression.to_string();
-        assert_eq!(string_expression, "(== (+ 1 2) (+ 5 7))");
+    fn literal(&mut self) -> Result<Expression, ParserError> {
+        let token = self.previous().clone();
+        match LiteralValue::from_token(token.clone()) {
+            Ok(value) => Ok(Expression::Literal { token, value }),
+            Err(message) => Err(ParserError::new(token, &message)),
+        }
     }
 
-    #[test]
-    fn test_eq_with_paren() {
-        let source = "1 == (2 + 2);";
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.scan_tokens().unwrap();
-        let mut parser = Parser::new(tokens);
-        let parsed_expression = parser.parse().unwrap();
-        let string_expression = parsed_expression.to_string();
-        assert_eq!(string_expression, "(== 1 (group (+ 2 2)))");
+    fn this(&mut self) -> Result<Expression, ParserError> {
+        Ok(Expression::This {
+            keyword: self.previous().clone(),
+            distance: None,
+        })
+    }
+
+    fn super_method(&mut self) -> Result<Expression, ParserError> {
+        let keyword = self.previous().clone();
+        self.consume(Dot, "Expected '.' after 'super'")?;
+        let method = self.consume(Identifier, "Expected superclass method name")?;
+
+        Ok(Expression::Super {
+            keyword,
+            method,
+            distance: None,
+        })
+    }
+
+    /// Reports a binary operator found where an expression should start, then
+    /// parses its right operand at the operator's precedence and carries on
+    /// with that, so the rest of the expression isn't reported as well.
+    fn missing_left_operand(&mut self) -> Result<Expression, ParserError> {
+        let operator = self.previous().clone();
+        let message = format!(
+            "Binary operator '{}' missing left-hand operand",
+            operator.lexeme
+        );
+        let precedence = rule(operator.token_type).2.operand();
+        self.errors.push(ParserError::new(operator, &message));
+
+        self.parse_precedence(precedence)
+    }
+
+    fn array_literal(&mut self) -> Result<Expression, ParserError> {
+        let bracket = self.previous().clone();
+        let mut elements = vec![];
+
+        while !self.check(&RightBracket) && !self.is_at_end() {
+            if self.check(&Comma) {
+                return Err(ParserError::new(
+                    self.peek().clone(),
+                    "Unexpected ',' in array literal",
+                ));
+            }
+            elements.push(self.argument()?);
+            if !self.match_token(&Comma) {
+                break;
+            }
+        }
+
+        self.consume(RightBracket, "Expected ']' after array elements")?;
+
+        Ok(Expression::ArrayLiteral { bracket, elements })
+    }
+
+    /// Checks whether the tokens after the `(` just consumed form a
+    /// parameter list followed by `->`, leaving the position where it was.
+    fn starts_lambda(&mut self) -> bool {
+        let checkpoint = self.checkpoint();
+
+        if self.match_token(&Identifier) {
+            while self.check_sequence(&[Comma, Identifier]) {
+                self.advance();
+                self.advance();
+            }
+        }
+        let is_lambda = self.check_sequence(&[RightParen, Arrow]);
+
+        self.rewind(checkpoint);
+        is_lambda
+    }
+
+    /// Parses the rest of a lambda once its opening `(` has been consumed,
+    /// `start` being the `fun` or the `(`. Only lambdas written with `fun`
+    /// may have a block body.
+    fn lambda(&mut self, start: Token) -> Result<Expression, ParserError> {
+        let with_fun = start.token_type == Function;
+        let params = self.parameters()?;
+
+        if with_fun && self.match_token(&LeftBrace) {
+            let body = self.function_body()?;
+            return Ok(Expression::Lambda {
+                start,
+                params,
+                body,
+            });
+        }
+
+        let keyword = self.peek().clone();
+        let expected = if with_fun { "'->' or '{'" } else { "'->'" };
+        self.consume(Arrow, &format!("Expected {} after parameters", expected))?;
+        let value = self.argument()?;
+
+        Ok(Expression::Lambda {
+            start,
+            params,
+            body: vec![Statement::Return {
+                keyword,
+                value: Some(value),
+            }],
+        })
+    }
+
+    fn consume(&mut self, token_type: TokenType, msg: &str) -> Result<Token, ParserError> {
+        if self.check(&token_type) {
+            Ok(self.advance().clone())
+        } else {
+            Err(ParserError::new(self.peek().clone(), msg))
+        }
+    }
+
+    /// Whether the current token is of `token_type`, without consuming it.
+    pub fn check(&self, token_type: &TokenType) -> bool {
+        self.check_sequence(&[*token_type])
+    }
+
+    /// Whether the tokens from the current one on are of `token_types`, in
+    /// order, without consuming any of them.
+    pub fn check_sequence(&self, token_types: &[TokenType]) -> bool {
+        token_types.iter().enumerate().all(|(i, token_type)| {
+            let token = self.tokens.get(self.current + i);
+            token.unwrap_or(&self.end_of_input).token_type == *token_type
+        })
+    }
+
+    /// Marks the current position, so a speculative parse can go back to it
+    /// with `rewind`.
+    pub fn checkpoint(&self) -> usize {
+        self.current
+    }
+
+    /// Goes back to a position from `checkpoint`. Only the position is
+    /// restored: errors recorded since then are kept.
+    pub fn rewind(&mut self, checkpoint: usize) {
+        self.current = checkpoint;
+    }
+
+    fn match_token(&mut self, _type: &TokenType) -> bool {
+        if self.is_at_end() {
+            false
+        } else {
+            if self.check(_type) {
+                self.advance();
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    fn match_tokens(&mut self, types: &[TokenType]) -> bool {
+        for typ in types {
+            if self.match_token(typ) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+
+        self.previous()
+    }
+
+    fn peek(&self) -> &Token {
+        self.tokens.get(self.current).unwrap_or(&self.end_of_input)
+    }
+
+    fn previous(&self) -> &Token {
+        self.current
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .unwrap_or(&self.end_of_input)
+    }
+
+    fn end_of_input(tokens: &[Token]) -> Token {
+        let (line_number, end) = match tokens.last() {
+            Some(token) => (token.line_number, token.span.1),
+            None => (1, 0),
+        };
+
+        Token::new(
+            TokenType::Eof,
+            "".to_string(),
+            None,
+            line_number,
+            0,
+            (end, end),
+        )
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::Eof
+    }
+
+    /// Skips tokens after an error until the start of the next statement. It
+    /// never skips a `}`, which the enclosing block still needs to see.
+    fn synchronize(&mut self) {
+        if !self.check(&RightBrace) {
+            self.advance();
+        }
+
+        while !self.is_at_end() {
+            if self.previous().token_type == SemiColon {
+                return;
+            }
+
+            match self.peek().token_type {
+                Class | Function | Variable | Const | For | If | While | Do | Match | Say
+                | Return | Break | Continue | RightBrace => return,
+                _ => (),
+            }
+
+            self.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+    use crate::lexer::token::LiteralValue as TokenLiteralValue;
+    use crate::lexer::token::LiteralValue::IntValue;
+    use crate::lexer::token::TokenType::{Number, Plus, SemiColon};
+
+    #[test]
+    fn test_addition() {
+        let one = Token {
+            token_type: Number,
+            lexeme: "1".to_string(),
+            literal: Some(IntValue(1)),
+            line_number: 0,
+            column: 0,
+            span: (0, 0),
+        };
+        let two = Token {
+            token_type: Number,
+            lexeme: "2".to_string(),
+            literal: Some(IntValue(2)),
+            line_number: 0,
+            column: 0,
+            span: (0, 0),
+        };
+        let plus = Token {
+            token_type: Plus,
+            lexeme: "+".to_string(),
+            literal: None,
+            line_number: 0,
+            column: 0,
+            span: (0, 0),
+        };
+        let semi_colon = Token {
+            token_type: SemiColon,
+            lexeme: ";".to_string(),
+            literal: None,
+            line_number: 0,
+            column: 0,
+            span: (0, 0),
+        };
+        let expected = Expression::Binary {
+            left: Box::from(Expression::Literal {
+                token: one.clone(),
+                value: LiteralValue::Integer(1),
+            }),
+            operator: plus.clone(),
+            right: Box::from(Expression::Literal {
+                token: two.clone(),
+                value: LiteralValue::Integer(2),
+            }),
+        };
+        let tokens = vec![one, plus, two, semi_colon];
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.expression().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_integer_and_float_literals_coexist() {
+        let tokens = Lexer::new("1 + 2.5").scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+        match &expression {
+            Expression::Binary { left, right, .. } => {
+                assert!(matches!(
+                    **left,
+                    Expression::Literal {
+                        value: LiteralValue::Integer(1),
+                        ..
+                    }
+                ));
+                assert!(matches!(
+                    **right,
+                    Expression::Literal {
+                        value: LiteralValue::Number(x),
+                        ..
+                    } if x == 2.5
+                ));
+            }
+            other => panic!("Expected a binary expression, got {}", other),
+        }
+        assert_eq!(expression.to_string(), "(+ 1 2.5)");
+    }
+
+    /// A token as the lexer would produce it, at no particular position.
+    fn token(token_type: TokenType, lexeme: &str) -> Token {
+        let literal = match token_type {
+            Identifier => Some(TokenLiteralValue::IdentifierValue(lexeme.to_string())),
+            Number => Some(IntValue(lexeme.parse().unwrap())),
+            _ => None,
+        };
+        Token::new(token_type, lexeme.to_string(), literal, 0, 0, (0, 0))
+    }
+
+    #[test]
+    fn test_structural_equality() {
+        let tokens = Lexer::new("say x = -1,\n  x;").scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+
+        let x = Expression::Variable {
+            name: token(Identifier, "x"),
+            distance: None,
+        };
+        let expected = Statement::Say(Expression::Binary {
+            left: Box::from(Expression::Assign {
+                name: token(Identifier, "x"),
+                value: Box::from(Expression::Unary {
+                    operator: token(Minus, "-"),
+                    right: Box::from(Expression::Literal {
+                        token: token(Number, "1"),
+                        value: LiteralValue::Integer(1),
+                    }),
+                }),
+                distance: None,
+            }),
+            operator: token(Comma, ","),
+            right: Box::from(x.clone()),
+        });
+        assert_eq!(statements, vec![expected.clone()]);
+
+        let tokens = Lexer::new("say x = -1, y;").scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        assert_ne!(statements, vec![expected]);
+    }
+
+    #[test]
+    fn test_comparison() {
+        let source = "1 + 2 == 5 + 7";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expression = parser.expression().unwrap();
+        let string_expression = parsed_expression.to_string();
+        assert_eq!(string_expression, "(== (+ 1 2) (+ 5 7))");
+    }
+
+    #[test]
+    fn test_eq_with_paren() {
+        let source = "1 == (2 + 2);";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expression = parser.expression().unwrap();
+        let string_expression = parsed_expression.to_string();
+        assert_eq!(string_expression, "(== 1 (group (+ 2 2)))");
+    }
+
+    #[test]
+    fn test_integer_and_float_literals() {
+        let source = "5 + 2.5";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expression = parser.expression().unwrap();
+        let string_expression = parsed_expression.to_string();
+        assert_eq!(string_expression, "(+ 5 2.5)");
+    }
+
+    #[test]
+    fn test_modulo() {
+        let source = "10 % 3 == 1";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expression = parser.expression().unwrap();
+        let string_expression = parsed_expression.to_string();
+        assert_eq!(string_expression, "(== (% 10 3) 1)");
+    }
+
+    fn parse_source(source: &str) -> Result<Expression, ParserError> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.expression()
+    }
+
+    fn location(error: &ParserError) -> (&str, usize) {
+        (error.message.as_str(), error.token.line_number)
+    }
+
+    fn located(errors: &[ParserError]) -> Vec<(&str, usize)> {
+        errors.iter().map(location).collect()
+    }
+
+    fn parse_program(source: &str) -> Result<Vec<String>, Vec<ParserError>> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let statements = parser.parse_program()?;
+        Ok(statements
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_statements() {
+        let statements = parse_program(
+            "say 1 + 2;
+\"a\";
+say -3;",
+        )
+        .unwrap();
+        assert_eq!(statements, vec!["(say (+ 1 2))", "a", "(say (- 3))"]);
+    }
+
+    #[test]
+    fn test_empty_program() {
+        assert!(parse_program("").unwrap().is_empty());
+        assert!(parse_program("// just a comment").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_semicolon() {
+        match parse_program(
+            "say 1;
+say 2
+say 3;",
+        ) {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected ';' after value", 2)]),
+            _ => panic!("Should have rejected statement without ';'."),
+        }
+
+        match parse_program("1 + 2") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected ';' after expression", 1)]),
+            _ => panic!("Should have rejected statement without ';'."),
+        }
+    }
+
+    #[test]
+    fn test_var_declaration() {
+        let statements = parse_program("var x = 12;\nvar y;").unwrap();
+        assert_eq!(statements, vec!["(var x 12)", "(var y nil)"]);
+    }
+
+    #[test]
+    fn test_var_declaration_errors() {
+        match parse_program("var\n= 1;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected variable name after 'var'", 2)]
+            ),
+            _ => panic!("Should have rejected var without a name."),
+        }
+
+        match parse_program("var x = 1\nsay x;") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected ';' after variable declaration", 1)
+            ),
+            _ => panic!("Should have rejected var without ';'."),
+        }
+    }
+
+    #[test]
+    fn test_const_declaration() {
+        let statements = parse_program("const LIMIT = 10 * 2;").unwrap();
+        assert_eq!(statements, vec!["(const LIMIT (* 10 2))"]);
+
+        match parse_program("const x;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Const 'x' requires an initializer", 1)]
+            ),
+            _ => panic!("Should have rejected const without an initializer."),
+        }
+    }
+
+    #[test]
+    fn test_assignment() {
+        let parsed_expression = parse_source("x = 1 + 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(= x (+ 1 2))");
+
+        let parsed_expression = parse_source("a = b = 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(= a (= b 3))");
+
+        let parsed_expression = parse_source("a = b ? c : d").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(= a (?: b c d))");
+
+        let statements = parse_program("var x;\nx = x + 1;").unwrap();
+        assert_eq!(statements, vec!["(var x nil)", "(= x (+ x 1))"]);
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        for source in ["(a) = 1", "1 = 2", "a + b\n= 3"] {
+            match parse_source(source) {
+                Err(error) => assert_eq!(error.message, "Invalid assignment target"),
+                _ => panic!("Should have rejected {}.", source),
+            }
+        }
+
+        match parse_source("a + b\n= 3") {
+            Err(error) => assert_eq!(location(&error), ("Invalid assignment target", 2)),
+            _ => panic!("Should have rejected assignment to a binary expression."),
+        }
+    }
+
+    #[test]
+    fn test_block() {
+        let statements = parse_program("{}").unwrap();
+        assert_eq!(statements, vec!["(block)"]);
+
+        let statements = parse_program("{ var a = 1; { say a; {} } a = 2; }").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(block (var a 1) (block (say a) (block)) (= a 2))"]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_block() {
+        match parse_program("say 0;\n{\n  say 1;\n") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected '}' after block", 2)]),
+            _ => panic!("Should have rejected unterminated block."),
+        }
+    }
+
+    #[test]
+    fn test_if() {
+        let statements = parse_program("if (a) say 1;").unwrap();
+        assert_eq!(statements, vec!["(if a (say 1))"]);
+
+        let statements = parse_program("if (a > 1) { say 1; } else say 2;").unwrap();
+        assert_eq!(statements, vec!["(if (> a 1) (block (say 1)) (say 2))"]);
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_nearest_if() {
+        let statements = parse_program("if (a) if (b) say 1; else say 2;").unwrap();
+        assert_eq!(statements, vec!["(if a (if b (say 1) (say 2)))"]);
+    }
+
+    #[test]
+    fn test_elif() {
+        let statements =
+            parse_program("if (a) say 1; elif (b) say 2; elif (c) say 3; else say 4;").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(if a (say 1) (if b (say 2) (if c (say 3) (say 4))))"]
+        );
+    }
+
+    #[test]
+    fn test_if_errors() {
+        match parse_program("if a) say 1;") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected '(' after 'if'", 1)]),
+            _ => panic!("Should have rejected if without '('."),
+        }
+
+        match parse_program("if (a\n say 1;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected ')' after if condition", 2)]
+            ),
+            _ => panic!("Should have rejected if without ')'."),
+        }
+
+        match parse_program("if (a) say 1; elif b say 2;") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected '(' after 'elif'", 1)]),
+            _ => panic!("Should have rejected elif without '('."),
+        }
+    }
+
+    #[test]
+    fn test_while() {
+        let statements = parse_program("while (True) say 1;").unwrap();
+        assert_eq!(statements, vec!["(while True (say 1))"]);
+
+        let statements = parse_program("while (i < 3) { say i; i = i + 1; }").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(while (< i 3) (block (say i) (= i (+ i 1))))"]
+        );
+    }
+
+    #[test]
+    fn test_while_errors() {
+        match parse_program("while True) say 1;") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected '(' after 'while'", 1)]),
+            _ => panic!("Should have rejected while without '('."),
+        }
+
+        match parse_program("while (True\nsay 1;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected ')' after while condition", 2)]
+            ),
+            _ => panic!("Should have rejected while without ')'."),
+        }
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let statements =
+            parse_program("while (True) { if (a) break; elif (b) { continue; } }").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(while True (block (if a (break) (if b (block (continue))))))"]
+        );
+    }
+
+    #[test]
+    fn test_break_and_continue_outside_loop() {
+        match parse_program("break;\nwhile (True) say 1;\ncontinue;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![
+                    ("'break' outside of a loop", 1),
+                    ("'continue' outside of a loop", 3)
+                ]
+            ),
+            _ => panic!("Should have rejected break and continue outside a loop."),
+        }
+
+        match parse_program("while (True) say 1; break;") {
+            Err(errors) => assert_eq!(located(&errors), vec![("'break' outside of a loop", 1)]),
+            _ => panic!("Should have rejected break after the loop."),
+        }
+    }
+
+    #[test]
+    fn test_for() {
+        let statements = parse_program("for (var i = 0; i < 3; i = i + 1) say i;").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(block (var i 0) (while (< i 3) (block (say i) (= i (+ i 1)))))"]
+        );
+
+        let statements = parse_program("for (i = 0; i < 3;) { say i; }").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(block (= i 0) (while (< i 3) (block (say i))))"]
+        );
+    }
+
+    #[test]
+    fn test_infinite_for() {
+        let statements = parse_program("for (;;) break;").unwrap();
+        assert_eq!(statements, vec!["(while True (break))"]);
+    }
+
+    #[test]
+    fn test_for_with_only_condition() {
+        let statements = parse_program("for (; going;) say 1;").unwrap();
+        assert_eq!(statements, vec!["(while going (say 1))"]);
+    }
+
+    #[test]
+    fn test_for_errors() {
+        match parse_program("for (var i = 0 i < 3; i = i + 1) say i;") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected ';' after for-loop initializer", 1)
+            ),
+            _ => panic!("Should have rejected for without ';' after the initializer."),
+        }
+
+        match parse_program("for (;\ni < 3 i = i + 1) say i;") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected ';' after for-loop condition", 2)
+            ),
+            _ => panic!("Should have rejected for without ';' after the condition."),
+        }
+
+        match parse_program("for (;; i = i + 1 say i;") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected ')' after for-loop increment", 1)
+            ),
+            _ => panic!("Should have rejected for without ')'."),
+        }
+    }
+
+    #[test]
+    fn test_logical_precedence() {
+        let parsed_expression = parse_source("a or b and c").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(or a (and b c))");
+
+        let parsed_expression = parse_source("a and b or c and d").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(or (and a b) (and c d))");
+
+        let parsed_expression = parse_source("a or b or c").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(or (or a b) c)");
+
+        let parsed_expression = parse_source("a == 1 and b | 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(and (== a 1) (| b 2))");
+
+        let parsed_expression = parse_source("x = a ?? b or c ? 1 : 2").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(= x (?: (?? a (or b c)) 1 2))"
+        );
+    }
+
+    #[test]
+    fn test_lambda_block_body() {
+        let parsed_expression = parse_source("fun (a, b) { return a + b; }").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(lambda (a b) (return (+ a b)))"
+        );
+
+        let statements = parse_program("var add = fun (a, b) { return a + b; };").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(var add (lambda (a b) (return (+ a b))))"]
+        );
+    }
+
+    #[test]
+    fn test_function_declaration() {
+        let statements = parse_program("fun greet() { say \"hi\"; }").unwrap();
+        assert_eq!(statements, vec!["(fun greet () (say hi))"]);
+
+        let statements = parse_program("fun add(a, b) { return a + b; }").unwrap();
+        assert_eq!(statements, vec!["(fun add (a b) (return (+ a b)))"]);
+
+        let statements = parse_program("fun (a) -> a;").unwrap();
+        assert_eq!(statements, vec!["(lambda (a) (return a))"]);
+    }
+
+    #[test]
+    fn test_function_declaration_errors() {
+        match parse_program("fun add(a, b { return a + b; }") {
+            Err(errors) => assert_eq!(location(&errors[0]), ("Expected ')' after parameters", 1)),
+            _ => panic!("Should have rejected parameters without ')'."),
+        }
+
+        match parse_program("fun add(a, b)\nreturn a + b;") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected '{' before function body", 2)
+            ),
+            _ => panic!("Should have rejected function without a body."),
+        }
+    }
+
+    #[test]
+    fn test_call() {
+        let parsed_expression = parse_source("f()").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call f)");
+
+        let parsed_expression = parse_source("add(1, 2 + 3)").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call add 1 (+ 2 3))");
+
+        let parsed_expression = parse_source("f(1)(2)").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call (call f 1) 2)");
+
+        let parsed_expression = parse_source("fns[0](1)[2]").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(index (call (index fns 0) 1) 2)"
+        );
+    }
+
+    #[test]
+    fn test_call_missing_paren() {
+        match parse_source("add(1, 2\n+ 3;") {
+            Err(error) => assert_eq!(location(&error), ("Expected ')' after arguments", 2)),
+            _ => panic!("Should have rejected call without ')'."),
+        }
+    }
+
+    #[test]
+    fn test_argument_limit() {
+        let arguments = vec!["1"; 256].join(", ");
+        match parse_program(&format!("f({});\nsay 1 +;", arguments)) {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![
+                    ("Can't have more than 255 arguments", 1),
+                    ("Expected expression", 2)
+                ]
+            ),
+            _ => panic!("Should have rejected 256 arguments."),
+        }
+
+        let params = (0..256)
+            .map(|i| format!("p{}", i))
+            .collect::<Vec<String>>()
+            .join(", ");
+        match parse_program(&format!("fun f({}) {{}}", params)) {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Can't have more than 255 parameters", 1)]
+            ),
+            _ => panic!("Should have rejected 256 parameters."),
+        }
+
+        let arguments = vec!["1"; 255].join(", ");
+        assert!(parse_program(&format!("f({});", arguments)).is_ok());
+    }
+
+    #[test]
+    fn test_return() {
+        let statements = parse_program("fun f() { return; }").unwrap();
+        assert_eq!(statements, vec!["(fun f () (return))"]);
+
+        // The resolver rejects it, but a `return` anywhere parses.
+        let statements = parse_program("return 1;").unwrap();
+        assert_eq!(statements, vec!["(return 1)"]);
+    }
+
+    #[test]
+    fn test_loops_do_not_extend_into_functions() {
+        match parse_program("while (True) { fun f() { break; } }") {
+            Err(errors) => assert_eq!(location(&errors[0]), ("'break' outside of a loop", 1)),
+            _ => panic!("Should have rejected break inside a function inside a loop."),
+        }
+
+        assert!(parse_program("fun f() { while (True) break; }").is_ok());
+    }
+
+    #[test]
+    fn test_class() {
+        let statements =
+            parse_program("class Point {\n  init(x) { this.x = x; }\n  show() { say this.x; }\n}")
+                .unwrap();
+        assert_eq!(
+            statements,
+            vec!["(class Point (fun init (x) (set this x x)) (fun show () (say (get this x))))"]
+        );
+
+        let statements = parse_program("class Empty {}").unwrap();
+        assert_eq!(statements, vec!["(class Empty)"]);
+    }
+
+    #[test]
+    fn test_class_errors() {
+        match parse_program("class Point\n  init() {}\n}") {
+            Err(errors) => assert_eq!(location(&errors[0]), ("Expected '{' before class body", 2)),
+            _ => panic!("Should have rejected class without an opening brace."),
+        }
+
+        match parse_program("class Point {\n  init() {}\n") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected '}' after class body", 1)]),
+            _ => panic!("Should have rejected class without a closing brace."),
+        }
+
+        match parse_program("class Point { var x; }") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected method name in class body", 1)
+            ),
+            _ => panic!("Should have rejected a field declaration."),
+        }
+    }
+
+    #[test]
+    fn test_get_and_set() {
+        let parsed_expression = parse_source("a.b.c").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(get (get a b) c)");
+
+        let parsed_expression = parse_source("a.b = 1").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(set a b 1)");
+
+        let parsed_expression = parse_source("a.b(1).c = d.e = 2").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(set (call (get a b) 1) c (set d e 2))"
+        );
+
+        match parse_source("a.\n1") {
+            Err(error) => assert_eq!(location(&error), ("Expected property name after '.'", 2)),
+            _ => panic!("Should have rejected '.' without a name."),
+        }
+    }
+
+    #[test]
+    fn test_this_outside_class() {
+        // The resolver rejects these, but they parse.
+        let statements = parse_program("say this;\nfun f() { return super.f(); }").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(say this)", "(fun f () (return (call (super f))))"]
+        );
+    }
+
+    #[test]
+    fn test_superclass() {
+        let statements = parse_program("class B < A {\n  init() { super.init(1); }\n}").unwrap();
+        assert_eq!(
+            statements,
+            vec!["(class B < A (fun init () (call (super init) 1)))"]
+        );
+
+        match parse_program("class B < {}") {
+            Err(errors) => assert_eq!(
+                location(&errors[0]),
+                ("Expected superclass name after '<'", 1)
+            ),
+            _ => panic!("Should have rejected '<' without a superclass."),
+        }
+    }
+
+    #[test]
+    fn test_super_errors() {
+        match parse_program("class B < A { f() { super; } }") {
+            Err(errors) => assert_eq!(location(&errors[0]), ("Expected '.' after 'super'", 1)),
+            _ => panic!("Should have rejected 'super' without '.'."),
+        }
+
+        match parse_program("class B < A { f() { super.\n1; } }") {
+            Err(errors) => assert_eq!(location(&errors[0]), ("Expected superclass method name", 2)),
+            _ => panic!("Should have rejected 'super.' without a method name."),
+        }
+    }
+
+    #[test]
+    fn test_recovers_after_errors() {
+        let source = "say 1;\nvar = 2;\nsay 3;\nsay (4;\n{ say 5; say 6 +; }\nsay 7;";
+        let mut parser = Parser::new(Lexer::new(source).scan_tokens().unwrap());
+        let (statements, errors) = parser.parse_lossy();
+
+        assert_eq!(
+            located(&errors),
+            vec![
+                ("Expected variable name after 'var'", 2),
+                ("Expected ')' after expression", 4),
+                ("Expected expression", 5),
+            ]
+        );
+        let statements: Vec<String> = statements
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect();
+        assert_eq!(
+            statements,
+            vec!["(say 1)", "(say 3)", "(block (say 5))", "(say 7)"]
+        );
+    }
+
+    #[test]
+    fn test_recovers_after_stray_brace() {
+        match parse_program("say 1;\n}\nsay 2;\n}") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected expression", 2), ("Expected expression", 4)]
+            ),
+            _ => panic!("Should have rejected stray braces."),
+        }
+    }
+
+    #[test]
+    fn test_reports_every_statement_error() {
+        match parse_program(
+            "say 1 +;
+say 2;
+(3;",
+        ) {
+            Err(errors) => assert_eq!(errors.len(), 2),
+            _ => panic!("Should have rejected both bad statements."),
+        }
+    }
+
+    #[test]
+    fn test_conditional() {
+        let parsed_expression = parse_source("1 < 2 ? \"yes\" : \"no\"").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?: (< 1 2) yes no)");
+    }
+
+    #[test]
+    fn test_nested_conditional_is_right_associative() {
+        let parsed_expression = parse_source("True ? 1 : False ? 2 : 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?: True 1 (?: False 2 3))");
+
+        let parsed_expression = parse_source("True ? False ? 1 : 2 : 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?: True (?: False 1 2) 3)");
+    }
+
+    #[test]
+    fn test_conditional_missing_colon() {
+        let result = parse_source("True ?\n 1 2");
+        match result {
+            Err(error) => assert_eq!(
+                location(&error),
+                ("Expected ':' after then-branch of conditional", 2)
+            ),
+            _ => panic!("Should have rejected conditional without ':'."),
+        }
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        let parsed_expression = parse_source("1 | 2 & 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(| 1 (& 2 3))");
+
+        let parsed_expression = parse_source("1 ^ 2 | 3 ^ 4").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(| (^ 1 2) (^ 3 4))");
+
+        let parsed_expression = parse_source("~1 & 2 == 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(& (~ 1) (== 2 2))");
+    }
+
+    #[test]
+    fn test_shift_precedence() {
+        let parsed_expression = parse_source("1 << 2 + 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(<< 1 (+ 2 3))");
+
+        let parsed_expression = parse_source("1 >> 2 < 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(< (>> 1 2) 3)");
+    }
+
+    #[test]
+    fn test_power_is_right_associative() {
+        let parsed_expression = parse_source("2 ** 3 ** 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(** 2 (** 3 2))");
+
+        let parsed_expression = parse_source("2 * 3 ** 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(* 2 (** 3 2))");
+    }
+
+    #[test]
+    fn test_power_with_prefix_operator() {
+        let parsed_expression = parse_source("~2 ** 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(** (~ 2) 2)");
+
+        let parsed_expression = parse_source("2 ** ~2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(** 2 (~ 2))");
+    }
+
+    #[test]
+    fn test_unary_minus() {
+        let parsed_expression = parse_source("-5").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(- 5)");
+
+        let parsed_expression = parse_source("-(1 + 2)").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(- (group (+ 1 2)))");
+
+        let parsed_expression = parse_source("-1 + 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(+ (- 1) 2)");
+
+        let parsed_expression = parse_source("--1").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(- (- 1))");
+
+        let parsed_expression = parse_source("-2 ** 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(** (- 2) 2)");
+    }
+
+    #[test]
+    fn test_bang_equal_is_not_unary() {
+        match parse_program("!= 1;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Binary operator '!=' missing left-hand operand", 1)]
+            ),
+            _ => panic!("Should have rejected '!=' as a prefix operator."),
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        let parsed_expression = parse_source("\"abc\"[0]").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(index abc 0)");
+
+        let parsed_expression = parse_source("\"abc\"[1 + 1] + 1").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(+ (index abc (+ 1 1)) 1)");
+    }
+
+    #[test]
+    fn test_chained_index() {
+        let parsed_expression = parse_source("(\"abc\")[0][1]").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(index (index (group abc) 0) 1)"
+        );
+    }
+
+    #[test]
+    fn test_index_missing_bracket() {
+        let result = parse_source("\"abc\"[0\n+ 1");
+        match result {
+            Err(error) => assert_eq!(location(&error), ("Expected ']' after index", 2)),
+            _ => panic!("Should have rejected index without ']'."),
+        }
+    }
+
+    #[test]
+    fn test_array_literal() {
+        let parsed_expression = parse_source("[1, 2, 3]").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(array 1 2 3)");
+
+        let parsed_expression = parse_source("[]").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(array)");
+
+        let parsed_expression = parse_source("[1, 2,]").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(array 1 2)");
+
+        let parsed_expression = parse_source("[[1], [2]][0]").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(index (array (array 1) (array 2)) 0)"
+        );
+    }
+
+    #[test]
+    fn test_array_literal_errors() {
+        match parse_source("[1, 2\n") {
+            Err(error) => assert_eq!(location(&error), ("Expected ']' after array elements", 2)),
+            _ => panic!("Should have rejected unterminated array."),
+        }
+
+        match parse_source("\n[,1]") {
+            Err(error) => assert_eq!(location(&error), ("Unexpected ',' in array literal", 2)),
+            _ => panic!("Should have rejected stray comma."),
+        }
+
+        match parse_source("[1,,2]") {
+            Err(error) => assert_eq!(location(&error), ("Unexpected ',' in array literal", 1)),
+            _ => panic!("Should have rejected doubled comma."),
+        }
+    }
+
+    #[test]
+    fn test_null_coalescing_is_right_associative() {
+        let parsed_expression = parse_source("Nil ?? Nil ?? 3").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?? Nil (?? Nil 3))");
+
+        let parsed_expression = parse_source("Nil ?? 1 | 2 ? 3 : 4").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?: (?? Nil (| 1 2)) 3 4)");
+    }
+
+    #[test]
+    fn test_lambda() {
+        let parsed_expression = parse_source("fun () -> 1").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(lambda () (return 1))");
+
+        let parsed_expression = parse_source("fun (a, b) -> a + b").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(lambda (a b) (return (+ a b)))"
+        );
+
+        let parsed_expression = parse_source("(a, b) -> a + b").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(lambda (a b) (return (+ a b)))"
+        );
+
+        let parsed_expression = parse_source("(a) + 1").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(+ (group a) 1)");
+
+        let parsed_expression = parse_source("() -> fun (c) -> 3").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(lambda () (return (lambda (c) (return 3))))"
+        );
+
+        let parsed_expression = parse_source("(1) - 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(- (group 1) 2)");
+    }
+
+    #[test]
+    fn test_lambda_errors() {
+        match parse_source("fun\n -> 1") {
+            Err(error) => assert_eq!(location(&error), ("Expected '(' after 'fun'", 2)),
+            _ => panic!("Should have rejected lambda without a parameter list."),
+        }
+
+        match parse_source("fun (a, 1) -> 1") {
+            Err(error) => assert_eq!(location(&error), ("Expected parameter name", 1)),
+            _ => panic!("Should have rejected non-identifier parameter."),
+        }
+
+        match parse_source("fun (a) 1") {
+            Err(error) => assert_eq!(
+                location(&error),
+                ("Expected '->' or '{' after parameters", 1)
+            ),
+            _ => panic!("Should have rejected lambda without '->'."),
+        }
+
+        match parse_program("(a) { 1; }") {
+            Err(errors) => assert_eq!(location(&errors[0]), ("Expected ';' after expression", 1)),
+            _ => panic!("Should have parsed '(a)' as a grouping."),
+        }
+    }
+
+    #[test]
+    fn test_empty_token_stream() {
+        let mut parser = Parser::new(vec![]);
+        match parser.expression() {
+            Err(error) => assert_eq!(location(&error), ("Unexpected end of input", 1)),
+            _ => panic!("Should have rejected an empty token stream."),
+        }
+    }
+
+    #[test]
+    fn test_truncated_expression() {
+        match parse_source("1 +") {
+            Err(error) => assert_eq!(
+                error.to_string(),
+                "[line 1] Error at end: Unexpected end of input."
+            ),
+            _ => panic!("Should have rejected a dangling operator."),
+        }
+    }
+
+    #[test]
+    fn test_token_stream_without_eof() {
+        let mut tokens = Lexer::new("(1 + 2").scan_tokens().unwrap();
+        tokens.pop();
+
+        let mut parser = Parser::new(tokens.clone());
+        assert!(parser.expression().is_err());
+
+        tokens.pop();
+        let mut parser = Parser::new(tokens);
+        match parser.expression() {
+            Err(error) => assert_eq!(location(&error), ("Unexpected end of input", 1)),
+            _ => panic!("Should have rejected a truncated stream."),
+        }
+
+        let mut tokens = Lexer::new("1 + 2").scan_tokens().unwrap();
+        tokens.pop();
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.expression().unwrap().to_string(), "(+ 1 2)");
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // Runs on the test thread's default stack: the nesting limit is what
+        // keeps the parser from overflowing it.
+        let source = "(".repeat(5000);
+        match parse_source(&source) {
+            Err(error) => assert_eq!(
+                location(&error),
+                ("Expression nesting too deep (limit 256)", 1)
+            ),
+            _ => panic!("Should have rejected deeply nested parentheses."),
+        }
+
+        let source = format!("{}1", "-".repeat(5000));
+        assert!(parse_source(&source).is_err());
+
+        let source = format!("{}2", "2 ** ".repeat(5000));
+        assert!(parse_source(&source).is_err());
+
+        let source = format!("{}1{};", "[".repeat(5000), "]".repeat(5000));
+        assert!(parse_program(&source).is_err());
+
+        for source in [
+            format!("f{}", "()".repeat(5000)),
+            format!("x{}", "[0]".repeat(5000)),
+            format!("x{}", ".y".repeat(5000)),
+            format!("x{}", " |> f".repeat(5000)),
+        ] {
+            match parse_source(&source) {
+                Err(error) => assert_eq!(
+                    location(&error),
+                    ("Expression nesting too deep (limit 256)", 1)
+                ),
+                _ => panic!("Should have rejected a long postfix chain."),
+            }
+        }
+
+        // Operator chains are the exception: every pass walks them in a loop.
+        let source = format!("1{}", " + 1".repeat(10_000));
+        assert_eq!(parse_source(&source).unwrap().depth(), 10_001);
+        let source = format!("a{}", " or b and c".repeat(10_000));
+        assert!(parse_source(&source).is_ok());
+
+        let source = format!("{}1{}", "(".repeat(255), ")".repeat(255));
+        let expected = format!("{}1{}", "(group ".repeat(255), ")".repeat(255));
+        assert_eq!(parse_source(&source).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn test_missing_left_operand() {
+        match parse_program("* 3;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Binary operator '*' missing left-hand operand", 1)]
+            ),
+            _ => panic!("Should have rejected '*' without a left operand."),
+        }
+
+        match parse_program("say 1;\n== 1 == 2;\nsay 2 +;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![
+                    ("Binary operator '==' missing left-hand operand", 2),
+                    ("Expected expression", 3),
+                ]
+            ),
+            _ => panic!("Should have rejected '==' without a left operand."),
+        }
+
+        match parse_program("var x = * 3 + 4 > and 5;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![
+                    ("Binary operator '*' missing left-hand operand", 1),
+                    ("Binary operator 'and' missing left-hand operand", 1),
+                ]
+            ),
+            _ => panic!("Should have rejected operators without left operands."),
+        }
+
+        assert_eq!(parse_source("-3").unwrap().to_string(), "(- 3)");
+        assert_eq!(parse_source("!True").unwrap().to_string(), "(! True)");
+    }
+
+    #[test]
+    fn test_large_token_stream() {
+        let source = "say x + 2 * (y - 3);\n".repeat(10_000);
+        let tokens = Lexer::new(&source).scan_tokens().unwrap();
+        assert!(tokens.len() > 100_000);
+
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        assert_eq!(statements.len(), 10_000);
+        assert_eq!(
+            statements[9999].to_string(),
+            "(say (+ x (* 2 (group (- y 3)))))"
+        );
+    }
+
+    #[test]
+    fn test_comma() {
+        let parsed_expression = parse_source("a, b, c").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(, (, a b) c)");
+
+        let parsed_expression = parse_source("x = 1, y = 2").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(, (= x 1) (= y 2))");
+
+        let parsed_expression = parse_source("a ? b, c : d").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?: a (, b c) d)");
+
+        assert_eq!(parse_program("say 1, 2;").unwrap(), vec!["(say (, 1 2))"]);
+    }
+
+    #[test]
+    fn test_comma_separates_items() {
+        let parsed_expression = parse_source("f(a, b)").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call f a b)");
+
+        let parsed_expression = parse_source("f((a, b))").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call f (group (, a b)))");
+
+        let parsed_expression = parse_source("[a, b]").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(array a b)");
+
+        let parsed_expression = parse_source("[(a, b), c]").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(array (group (, a b)) c)");
+
+        let parsed_expression = parse_source("map(fun(x) -> x, xs)").unwrap();
+        assert_eq!(
+            parsed_expression.to_string(),
+            "(call map (lambda (x) (return x)) xs)"
+        );
+    }
+
+    #[test]
+    fn test_precedence_table() {
+        let source =
+            "say a = b ? c ?? d or e and f | g ^ h & i == j < k << l + m * n ** -o[p](q).r : s, t;
+say x = y = 1 - 2 - 3 / 4 % 5 ** 6 ** 7 >= ~8 != !9 >> 10 & 11 | 12 ^ 13 ?? 14 ?? 15;
+say a ? b : c ? d : e, f = g.h = i;
+say -a.b(c)[d] ** 2 < 3 or 4 and 5 or 6;";
+
+        assert_eq!(
+            parse_program(source).unwrap(),
+            vec![
+                "(say (, (= a (?: b (?? c (or d (and e (| f (^ g (& h (== i (< j (<< k (+ l (* m (** n (- (get (call (index o p) q) r)))))))))))))) s)) t))",
+                "(say (= x (= y (?? (| (& (!= (>= (- (- 1 2) (% (/ 3 4) (** 5 (** 6 7)))) (~ 8)) (>> (! 9) 10)) 11) (^ 12 13)) (?? 14 15)))))",
+                "(say (, (?: a b (?: c d e)) (= f (set g h i))))",
+                "(say (or (or (< (** (- (index (call (get a b) c) d)) 2) 3) (and 4 5)) 6))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pipe() {
+        let parsed_expression = parse_source("x |> f").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call f x)");
+
+        let parsed_expression = parse_source("x |> f |> g").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call g (call f x))");
+
+        let parsed_expression = parse_source("1 + 2 |> f ?? g").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call (?? f g) (+ 1 2))");
+
+        let parsed_expression = parse_source("x |> f ? a : b").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(?: (call f x) a b)");
+
+        let parsed_expression = parse_source("xs |> map(g)").unwrap();
+        assert_eq!(parsed_expression.to_string(), "(call (call map g) xs)");
+
+        match parse_program("|> f;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Binary operator '|>' missing left-hand operand", 1)]
+            ),
+            _ => panic!("Should have rejected '|>' without a left operand."),
+        }
+    }
+
+    fn parse_repl_line(source: &str) -> Result<ReplLine, Vec<ParserError>> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse_repl_line()
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let tokens = Lexer::new("1 + 2").scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+        assert_eq!(expression.to_string(), "(+ 1 2)");
+
+        let tokens = Lexer::new("1 + 2;").scan_tokens().unwrap();
+        match Parser::new(tokens).parse_expression() {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected end of expression", 1)]),
+            _ => panic!("Should have rejected a trailing ';'."),
+        }
+    }
+
+    #[test]
+    fn test_repl_line_statements() {
+        match parse_repl_line("var x = 3; say x;") {
+            Ok(ReplLine::Statements(statements)) => {
+                let statements: Vec<String> = statements
+                    .iter()
+                    .map(|statement| statement.to_string())
+                    .collect();
+                assert_eq!(statements, vec!["(var x 3)", "(say x)"]);
+            }
+            _ => panic!("Should have parsed statements."),
+        }
+    }
+
+    #[test]
+    fn test_repl_line_bare_expression() {
+        match parse_repl_line("1 + 2") {
+            Ok(ReplLine::Expression(expression)) => assert_eq!(expression.to_string(), "(+ 1 2)"),
+            _ => panic!("Should have parsed a bare expression."),
+        }
+
+        match parse_repl_line("x = 1, 2") {
+            Ok(ReplLine::Expression(expression)) => {
+                assert_eq!(expression.to_string(), "(, (= x 1) 2)")
+            }
+            _ => panic!("Should have parsed a bare expression."),
+        }
+    }
+
+    #[test]
+    fn test_repl_line_errors() {
+        match parse_repl_line("var x = 3") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected ';' after variable declaration", 1)]
+            ),
+            _ => panic!("Should have rejected a declaration without ';'."),
+        }
+
+        match parse_repl_line("1 + 2 3") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected ';' after expression", 1)]),
+            _ => panic!("Should have rejected two expressions."),
+        }
+
+        match parse_repl_line("say 1 +") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Unexpected end of input", 1)]),
+            _ => panic!("Should have rejected an incomplete expression."),
+        }
+    }
+
+    #[test]
+    fn test_do_while() {
+        assert_eq!(
+            parse_program("do { say i; i = i + 1; } while (i < 3);").unwrap(),
+            vec!["(do-while (block (say i) (= i (+ i 1))) (< i 3))"]
+        );
+        assert_eq!(
+            parse_program("do if (x) break; else continue; while (True);").unwrap(),
+            vec!["(do-while (if x (break) (continue)) True)"]
+        );
+    }
+
+    #[test]
+    fn test_do_while_errors() {
+        match parse_program("do {\n say 1;\n}\nsay 2;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected 'while' after do-while body", 4)]
+            ),
+            _ => panic!("Should have rejected do without while."),
+        }
+
+        match parse_program("do say 1;\nwhile (False)\nsay 2;") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected ';' after do-while condition", 2)]
+            ),
+            _ => panic!("Should have rejected do-while without ';'."),
+        }
+
+        match parse_program("do say 1; while (False); break;") {
+            Err(errors) => assert_eq!(located(&errors), vec![("'break' outside of a loop", 1)]),
+            _ => panic!("Should have rejected break after the loop."),
+        }
+    }
+
+    #[test]
+    fn test_match() {
+        let source = "match (x) {\n  1 => say \"one\";\n  \"a\" => { say 2; }\n  else => say 3;\n}";
+        assert_eq!(
+            parse_program(source).unwrap(),
+            vec!["(match x (1 (say one)) (a (block (say 2))) (else (say 3)))"]
+        );
+
+        assert_eq!(
+            parse_program("match (f(y)) { y + 1 => say y; }").unwrap(),
+            vec!["(match (call f y) ((+ y 1) (say y)))"]
+        );
+        assert_eq!(parse_program("match (x) {}").unwrap(), vec!["(match x)"]);
+    }
+
+    #[test]
+    fn test_match_errors() {
+        match parse_program("match (x) {\n  1 say 1;\n}") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Expected '=>' after match pattern", 2)]
+            ),
+            _ => panic!("Should have rejected an arm without '=>'."),
+        }
+
+        match parse_program("match (x) {\n  else => say 1;\n  else => say 2;\n}\nsay 3") {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![
+                    ("Duplicate 'else' arm in match", 3),
+                    ("Expected ';' after value", 5)
+                ]
+            ),
+            _ => panic!("Should have rejected a second else arm."),
+        }
+
+        match parse_program("match (x) {\n  1 => say 1;\n") {
+            Err(errors) => assert_eq!(located(&errors), vec![("Expected '}' after match arms", 1)]),
+            _ => panic!("Should have rejected an unclosed match."),
+        }
+    }
+
+    #[test]
+    fn test_check_sequence() {
+        let parser = Parser::new(Lexer::new("(a, b) -> a").scan_tokens().unwrap());
+
+        assert!(parser.check(&LeftParen));
+        assert!(parser.check_sequence(&[LeftParen, Identifier, Comma]));
+        assert!(!parser.check_sequence(&[LeftParen, Comma]));
+        assert!(parser.check_sequence(&[]));
+
+        let parser = Parser::new(Lexer::new("a").scan_tokens().unwrap());
+        assert!(parser.check_sequence(&[Identifier, TokenType::Eof, TokenType::Eof]));
+    }
+
+    #[test]
+    fn test_rewind() {
+        let tokens = Lexer::new("x = 1;\nsay x;").scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+
+        let checkpoint = parser.checkpoint();
+        assert_eq!(parser.expression().unwrap().to_string(), "(= x 1)");
+        assert!(parser.check(&SemiColon));
+        parser.rewind(checkpoint);
+        assert_eq!(parser.checkpoint(), checkpoint);
+
+        let statements = parser.parse_program().unwrap();
+        let statements: Vec<String> = statements
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect();
+        assert_eq!(statements, vec!["(= x 1)", "(say x)"]);
+    }
+
+    #[test]
+    fn test_rewind_after_failed_attempt() {
+        let tokens = Lexer::new("say (1 + 2);").scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+
+        let checkpoint = parser.checkpoint();
+        parser.advance();
+        parser.advance();
+        assert!(parser.expression().is_ok());
+        assert!(parser.parse_expression().is_err());
+        parser.rewind(checkpoint);
+
+        let statements = parser.parse_program().unwrap();
+        assert_eq!(statements[0].to_string(), "(say (group (+ 1 2)))");
+    }
+
+    #[test]
+    fn test_empty_grouping() {
+        match parse_source("()") {
+            Err(error) => assert_eq!(
+                error.to_string(),
+                "[line 1] Error at '(': Empty grouping '()' is not a valid expression."
+            ),
+            _ => panic!("Should have rejected an empty grouping."),
+        }
+
+        match parse_source("1 +\n(   \n)") {
+            Err(error) => assert_eq!(
+                location(&error),
+                ("Empty grouping '()' is not a valid expression", 2)
+            ),
+            _ => panic!("Should have rejected an empty grouping."),
+        }
+
+        assert_eq!(parse_source("(1)").unwrap().to_string(), "(group 1)");
+        assert_eq!(parse_source("f()").unwrap().to_string(), "(call f)");
+        assert_eq!(
+            parse_source("() -> 1").unwrap().to_string(),
+            "(lambda () (return 1))"
+        );
+    }
+
+    #[test]
+    fn test_expression_lines() {
+        let source = "say [\n  1,\n  (2 +\n   3)[0]\n];";
+        let statements = Parser::new(Lexer::new(source).scan_tokens().unwrap())
+            .parse_program()
+            .unwrap();
+        let array = match &statements[0] {
+            Statement::Say(array) => array,
+            _ => panic!("Expected a say statement."),
+        };
+        assert_eq!(array.line(), 1);
+
+        let elements = match array {
+            Expression::ArrayLiteral { elements, .. } => elements,
+            _ => panic!("Expected an array literal."),
+        };
+        assert_eq!(elements[0].line(), 2);
+        // An index is at its `[`.
+        assert_eq!(elements[1].line(), 4);
+
+        let (grouping, index) = match &elements[1] {
+            Expression::Index { object, index, .. } => (object, index),
+            _ => panic!("Expected an index expression."),
+        };
+        assert_eq!(grouping.line(), 3);
+        assert_eq!(index.line(), 4);
+
+        let (two, three) = match &**grouping {
+            Expression::Grouping { expression, .. } => match &**expression {
+                Expression::Binary { left, right, .. } => (left, right),
+                _ => panic!("Expected a binary expression."),
+            },
+            _ => panic!("Expected a grouping."),
+        };
+        assert_eq!(two.line(), 3);
+        assert_eq!(three.line(), 4);
+    }
+
+    #[test]
+    fn test_lines_of_synthesised_nodes() {
+        let source = "for (;\n;) say 1;\nvar f = fun\n(x) -> x;\nvar g = (x)\n -> x;";
+        let statements = Parser::new(Lexer::new(source).scan_tokens().unwrap())
+            .parse_program()
+            .unwrap();
+
+        match &statements[0] {
+            // The missing condition is at the `;` where it would have been.
+            Statement::While { condition, .. } => assert_eq!(condition.line(), 2),
+            _ => panic!("Expected a while loop."),
+        }
+        match &statements[1] {
+            Statement::Var {
+                initializer: Some(lambda),
+                ..
+            } => assert_eq!(lambda.line(), 3),
+            _ => panic!("Expected a var declaration."),
+        }
+        match &statements[2] {
+            Statement::Var {
+                initializer: Some(lambda),
+                ..
+            } => assert_eq!(lambda.line(), 5),
+            _ => panic!("Expected a var declaration."),
+        }
+    }
+
+    #[test]
+    fn test_malformed_literal() {
+        let mut tokens = Lexer::new("say 1 + 2;").scan_tokens().unwrap();
+        tokens[3].literal = None;
+
+        match Parser::new(tokens).parse_program() {
+            Err(errors) => assert_eq!(
+                located(&errors),
+                vec![("Couldn't unwrap None as a number", 1)]
+            ),
+            _ => panic!("Should have rejected a number without a value."),
+        }
+    }
+
+    #[test]
+    fn test_number_precision() {
+        // Neither of these survives a round trip through f32.
+        assert_eq!(parse_source("16777217").unwrap().to_string(), "16777217");
+        assert_eq!(
+            parse_source("0.1234567891234").unwrap().to_string(),
+            "0.1234567891234"
+        );
     }
 }
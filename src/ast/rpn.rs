@@ -0,0 +1,151 @@
+use crate::ast::expression::Expression;
+use crate::ast::statement::Statement;
+use crate::lexer::token::TokenType;
+
+/// Prints expressions in Reverse Polish Notation, operands before their
+/// operator: `(1 + 2) * (4 - 3)` is `1 2 + 4 3 - *`.
+///
+/// Unary minus prints as `neg`, since `-` is taken by subtraction and `~` is
+/// already bitwise not. Calls and arrays record how many operands they take,
+/// as in `f 1 2 call/2`.
+pub struct RpnPrinter;
+
+impl RpnPrinter {
+    /// Written with a stack of words still to come rather than by recursion,
+    /// so a very deep tree prints without overflowing the call stack.
+    pub fn print(&self, expression: &Expression) -> String {
+        use Word::{Node, Text};
+
+        let mut words = Vec::new();
+        let mut stack = vec![Node(expression)];
+        while let Some(word) = stack.pop() {
+            let expression = match word {
+                Text(text) => {
+                    words.push(text);
+                    continue;
+                }
+                Node(expression) => expression,
+            };
+            let pieces = match expression {
+                Expression::ArrayLiteral { elements, .. } => {
+                    let mut pieces: Vec<Word> = elements.iter().map(Node).collect();
+                    pieces.push(Text(format!("array/{}", elements.len())));
+                    pieces
+                }
+                Expression::Assign { name, value, .. } => {
+                    vec![Node(value), Text(format!("={}", name.lexeme))]
+                }
+                Expression::Binary {
+                    left,
+                    operator,
+                    right,
+                }
+                | Expression::Logical {
+                    left,
+                    operator,
+                    right,
+                } => vec![Node(left), Node(right), Text(operator.lexeme.clone())],
+                Expression::Call {
+                    callee, arguments, ..
+                } => {
+                    let mut pieces = vec![Node(callee)];
+                    pieces.extend(arguments.iter().map(Node));
+                    pieces.push(Text(format!("call/{}", arguments.len())));
+                    pieces
+                }
+                Expression::Conditional {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    ..
+                } => vec![
+                    Node(condition),
+                    Node(then_branch),
+                    Node(else_branch),
+                    Text("?:".to_string()),
+                ],
+                Expression::Get { object, name } => {
+                    vec![Node(object), Text(format!(".{}", name.lexeme))]
+                }
+                Expression::Grouping { expression, .. } => vec![Node(expression)],
+                Expression::Index { object, index, .. } => {
+                    vec![Node(object), Node(index), Text("index".to_string())]
+                }
+                Expression::Lambda { .. } => vec![Text(expression.to_string())],
+                Expression::Literal { value, .. } => vec![Text(value.to_string())],
+                Expression::Set {
+                    object,
+                    name,
+                    value,
+                } => vec![
+                    Node(object),
+                    Node(value),
+                    Text(format!(".{}=", name.lexeme)),
+                ],
+                Expression::Super { method, .. } => {
+                    vec![Text(format!("super.{}", method.lexeme))]
+                }
+                Expression::This { .. } => vec![Text("this".to_string())],
+                Expression::Unary { operator, right } => {
+                    if operator.token_type == TokenType::Minus {
+                        vec![Node(right), Text("neg".to_string())]
+                    } else {
+                        vec![Node(right), Text(operator.lexeme.clone())]
+                    }
+                }
+                Expression::Variable { name, .. } => vec![Text(name.lexeme.clone())],
+            };
+            stack.extend(pieces.into_iter().rev());
+        }
+
+        words.join(" ")
+    }
+
+    /// Only expression and `say` statements have an RPN form; anything else
+    /// is printed as the usual Lisp-style tree.
+    pub fn print_statement(&self, statement: &Statement) -> String {
+        match statement {
+            Statement::Expression(expression) => self.print(expression),
+            Statement::Say(expression) => format!("{} say", self.print(expression)),
+            statement => statement.to_string(),
+        }
+    }
+}
+
+/// A word of the output still to be written, or a subtree still to be
+/// turned into words.
+enum Word<'a> {
+    Node(&'a Expression),
+    Text(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parser::Parser;
+    use crate::lexer::lexer::Lexer;
+
+    fn rpn(source: &str) -> String {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        RpnPrinter.print(&Parser::new(tokens).parse_expression().unwrap())
+    }
+
+    #[test]
+    fn test_rpn() {
+        assert_eq!(rpn("(1 + 2) * (4 - 3)"), "1 2 + 4 3 - *");
+        assert_eq!(rpn("a = b or c ? 1 : 2"), "b c or 1 2 ?: =a");
+        assert_eq!(rpn("f(x, [1, 2])[0]"), "f x 1 2 array/2 call/2 0 index");
+    }
+
+    #[test]
+    fn test_rpn_unary() {
+        assert_eq!(rpn("-(2 - -3)"), "2 3 neg - neg");
+        assert_eq!(rpn("!-x == ~y"), "x neg ! y ~ ==");
+    }
+
+    #[test]
+    fn test_rpn_long_chain() {
+        let source = format!("1{}", " - 1".repeat(100_000));
+        assert_eq!(rpn(&source), format!("1{}", " 1 -".repeat(100_000)));
+    }
+}
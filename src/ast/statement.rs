@@ -0,0 +1,262 @@
+use crate::ast::expression::{json_array, json_object, Expression};
+use crate::lexer::token::{json_string, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Block(Vec<Statement>),
+    Break {
+        keyword: Token,
+    },
+    /// Each method is a `Statement::Function`. The superclass, if any, is a
+    /// `Expression::Variable`.
+    Class {
+        name: Token,
+        superclass: Option<Expression>,
+        methods: Vec<Statement>,
+    },
+    /// A `const` binding, which always has an initializer.
+    Const {
+        name: Token,
+        initializer: Expression,
+    },
+    Continue {
+        keyword: Token,
+    },
+    /// A loop whose body runs once before the condition is first checked.
+    DoWhile {
+        body: Box<Statement>,
+        condition: Expression,
+    },
+    Expression(Expression),
+    Function {
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Statement>,
+    },
+    If {
+        condition: Expression,
+        then_branch: Box<Statement>,
+        else_branch: Option<Box<Statement>>,
+    },
+    /// Each arm pairs a pattern with the statement run when the subject
+    /// matches it. `default` is the `else` arm.
+    Match {
+        subject: Expression,
+        arms: Vec<(Expression, Statement)>,
+        default: Option<Box<Statement>>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expression>,
+    },
+    Say(Expression),
+    Var {
+        name: Token,
+        initializer: Option<Expression>,
+    },
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+    },
+}
+
+impl Statement {
+    /// Renders the statement as a JSON object with a `type`, holding its
+    /// expressions as `Expression::to_json` does.
+    pub fn to_json(&self) -> String {
+        let optional = |expression: &Option<Expression>| match expression {
+            Some(expression) => expression.to_json(),
+            None => "null".to_string(),
+        };
+        let name = |token: &Token| json_string(&token.lexeme);
+        let (kind, fields) = match self {
+            Statement::Block(statements) => (
+                "block",
+                vec![("statements", json_array(statements, Statement::to_json))],
+            ),
+            Statement::Break { .. } => ("break", vec![]),
+            Statement::Class {
+                name: class_name,
+                superclass,
+                methods,
+            } => (
+                "class",
+                vec![
+                    ("name", name(class_name)),
+                    ("superclass", optional(superclass)),
+                    ("methods", json_array(methods, Statement::to_json)),
+                ],
+            ),
+            Statement::Const {
+                name: const_name,
+                initializer,
+            } => (
+                "const",
+                vec![
+                    ("name", name(const_name)),
+                    ("initializer", initializer.to_json()),
+                ],
+            ),
+            Statement::Continue { .. } => ("continue", vec![]),
+            Statement::DoWhile { body, condition } => (
+                "do_while",
+                vec![("body", body.to_json()), ("condition", condition.to_json())],
+            ),
+            Statement::Expression(expression) => {
+                ("expression", vec![("expression", expression.to_json())])
+            }
+            Statement::Function {
+                name: function_name,
+                params,
+                body,
+            } => (
+                "function",
+                vec![
+                    ("name", name(function_name)),
+                    ("params", json_array(params, name)),
+                    ("body", json_array(body, Statement::to_json)),
+                ],
+            ),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => (
+                "if",
+                vec![
+                    ("condition", condition.to_json()),
+                    ("then", then_branch.to_json()),
+                    (
+                        "else",
+                        else_branch
+                            .as_ref()
+                            .map_or("null".to_string(), |branch| branch.to_json()),
+                    ),
+                ],
+            ),
+            Statement::Match {
+                subject,
+                arms,
+                default,
+            } => {
+                let arm = |(pattern, body): &(Expression, Statement)| {
+                    json_object(&[("pattern", pattern.to_json()), ("body", body.to_json())])
+                };
+                (
+                    "match",
+                    vec![
+                        ("subject", subject.to_json()),
+                        ("arms", json_array(arms, arm)),
+                        (
+                            "default",
+                            default
+                                .as_ref()
+                                .map_or("null".to_string(), |default| default.to_json()),
+                        ),
+                    ],
+                )
+            }
+            Statement::Return { value, .. } => ("return", vec![("value", optional(value))]),
+            Statement::Say(expression) => ("say", vec![("expression", expression.to_json())]),
+            Statement::Var {
+                name: var_name,
+                initializer,
+            } => (
+                "var",
+                vec![
+                    ("name", name(var_name)),
+                    ("initializer", optional(initializer)),
+                ],
+            ),
+            Statement::While { condition, body } => (
+                "while",
+                vec![("condition", condition.to_json()), ("body", body.to_json())],
+            ),
+        };
+
+        let mut object = vec![("type", json_string(kind))];
+        object.extend(fields);
+        json_object(&object)
+    }
+}
+
+impl std::fmt::Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Statement::Block(statements) => {
+                write!(f, "(block")?;
+                for statement in statements {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, ")")
+            }
+            Statement::Break { .. } => write!(f, "(break)"),
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                write!(f, "(class {}", name.lexeme)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass)?;
+                }
+                for method in methods {
+                    write!(f, " {}", method)?;
+                }
+                write!(f, ")")
+            }
+            Statement::Const { name, initializer } => {
+                write!(f, "(const {} {})", name.lexeme, initializer)
+            }
+            Statement::Continue { .. } => write!(f, "(continue)"),
+            Statement::DoWhile { body, condition } => {
+                write!(f, "(do-while {} {})", body, condition)
+            }
+            Statement::Expression(expression) => write!(f, "{}", expression),
+            Statement::Function { name, params, body } => {
+                let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+                write!(f, "(fun {} ({})", name.lexeme, params.join(" "))?;
+                for statement in body {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, ")")
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match else_branch {
+                Some(else_branch) => {
+                    write!(f, "(if {} {} {})", condition, then_branch, else_branch)
+                }
+                None => write!(f, "(if {} {})", condition, then_branch),
+            },
+            Statement::Match {
+                subject,
+                arms,
+                default,
+            } => {
+                write!(f, "(match {}", subject)?;
+                for (pattern, body) in arms {
+                    write!(f, " ({} {})", pattern, body)?;
+                }
+                if let Some(default) = default {
+                    write!(f, " (else {})", default)?;
+                }
+                write!(f, ")")
+            }
+            Statement::Return { value, .. } => match value {
+                Some(value) => write!(f, "(return {})", value),
+                None => write!(f, "(return)"),
+            },
+            Statement::Say(expression) => write!(f, "(say {})", expression),
+            Statement::Var { name, initializer } => match initializer {
+                Some(initializer) => write!(f, "(var {} {})", name.lexeme, initializer),
+                None => write!(f, "(var {} nil)", name.lexeme),
+            },
+            Statement::While { condition, body } => {
+                write!(f, "(while {} {})", condition, body)
+            }
+        }
+    }
+}
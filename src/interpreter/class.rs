@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::error::RuntimeError;
+use crate::interpreter::function::{Callable, OliFunction};
+use crate::interpreter::interpreter::Interpreter;
+use crate::interpreter::value::Value;
+use crate::lexer::token::Token;
+
+/// A class declared in a program. Calling it makes an instance.
+pub struct OliClass {
+    pub name: String,
+    pub superclass: Option<Rc<OliClass>>,
+    pub methods: HashMap<String, Rc<OliFunction>>,
+}
+
+impl OliClass {
+    /// Looks in this class, then up the chain of superclasses.
+    pub fn find_method(&self, name: &str) -> Option<Rc<OliFunction>> {
+        match (self.methods.get(name), &self.superclass) {
+            (Some(method), _) => Some(method.clone()),
+            (None, Some(superclass)) => superclass.find_method(name),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Implemented for the `Rc` so each instance can share its class.
+impl Callable for Rc<OliClass> {
+    /// A class takes the arguments its `init` method does.
+    fn arity(&self) -> usize {
+        self.find_method("init")
+            .map_or(0, |initializer| initializer.arity())
+    }
+
+    /// Makes an instance and runs `init` on it, giving back the instance.
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = Value::Instance(Rc::new(RefCell::new(Instance {
+            class: self.clone(),
+            fields: HashMap::new(),
+        })));
+        if let Some(initializer) = self.find_method("init") {
+            initializer
+                .bind(instance.clone())
+                .call(interpreter, paren, arguments)?;
+        }
+        Ok(instance)
+    }
+}
+
+/// Classes are only ever equal to themselves.
+impl PartialEq for OliClass {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for OliClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for OliClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+/// An object made by calling a class, holding its own fields.
+pub struct Instance {
+    pub class: Rc<OliClass>,
+    pub fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    /// Fields shadow methods. `instance` is the value holding `self`, which
+    /// a method is bound to.
+    pub fn get(&self, name: &Token, instance: &Value) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        match self.class.find_method(&name.lexeme) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance.clone())))),
+            None => Err(RuntimeError::new(
+                name.clone(),
+                &format!("Undefined property '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+/// Instances are only ever equal to themselves.
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Fields can hold the instance itself, so neither format shows them.
+impl std::fmt::Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
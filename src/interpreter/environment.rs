@@ -0,0 +1,307 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::error::RuntimeError;
+use crate::interpreter::value::Value;
+use crate::lexer::token::Token;
+
+struct Binding {
+    value: Value,
+    constant: bool,
+}
+
+/// The variables defined in one scope, by name. A scope's environment is
+/// shared, so a closure can keep it alive after the scope ends.
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Binding>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    /// The global scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A scope nested inside `enclosing`, which lookups fall back to.
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Defines a variable in this scope, replacing any earlier one of the
+    /// same name here and shadowing any in the scopes around it.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.bind(name, value, false);
+    }
+
+    /// Defines a variable that can't be assigned to afterwards.
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        self.bind(name, value, true);
+    }
+
+    /// Defines a variable declared in the program, which can replace an
+    /// earlier one of the same name here unless that one is a constant.
+    pub fn declare(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        self.declare_binding(name, value, false)
+    }
+
+    /// Defines a constant declared in the program, on the same terms as
+    /// `declare`.
+    pub fn declare_constant(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        self.declare_binding(name, value, true)
+    }
+
+    fn declare_binding(
+        &mut self,
+        name: &Token,
+        value: Value,
+        constant: bool,
+    ) -> Result<(), RuntimeError> {
+        if self
+            .values
+            .get(&name.lexeme)
+            .is_some_and(|binding| binding.constant)
+        {
+            return Err(RuntimeError::new(
+                name.clone(),
+                &format!("Can't redeclare constant '{}'.", name.lexeme),
+            ));
+        }
+        self.bind(&name.lexeme, value, constant);
+        Ok(())
+    }
+
+    fn bind(&mut self, name: &str, value: Value, constant: bool) {
+        self.values
+            .insert(name.to_string(), Binding { value, constant });
+    }
+
+    /// The variables defined in this scope alone, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self
+            .values
+            .iter()
+            .map(|(name, binding)| (name.clone(), binding.value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// The variable of that name in this scope alone, if there is one.
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.values.get(name).map(|binding| binding.value.clone())
+    }
+
+    /// Looks the name up in the innermost scope defining it.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match (self.values.get(&name.lexeme), &self.enclosing) {
+            (Some(binding), _) => Ok(binding.value.clone()),
+            (None, Some(enclosing)) => enclosing.borrow().get(name),
+            (None, None) => Err(undefined(name)),
+        }
+    }
+
+    /// Assigns to the variable in the innermost scope defining it. Assigning
+    /// never declares: the variable must already exist.
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        match (self.values.get_mut(&name.lexeme), &self.enclosing) {
+            (Some(binding), _) => binding.assign(name, value),
+            (None, Some(enclosing)) => enclosing.borrow_mut().assign(name, value),
+            (None, None) => Err(undefined(name)),
+        }
+    }
+
+    /// Looks the name up exactly `distance` scopes out, where the resolver
+    /// found its declaration.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) => match self.values.get(&name.lexeme) {
+                Some(binding) => Ok(binding.value.clone()),
+                None => Err(undefined(name)),
+            },
+            (_, Some(enclosing)) => enclosing.borrow().get_at(distance - 1, name),
+            (_, None) => Err(undefined(name)),
+        }
+    }
+
+    /// Assigns to the variable exactly `distance` scopes out.
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Token,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) => match self.values.get_mut(&name.lexeme) {
+                Some(binding) => binding.assign(name, value),
+                None => Err(undefined(name)),
+            },
+            (_, Some(enclosing)) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            (_, None) => Err(undefined(name)),
+        }
+    }
+}
+
+impl Binding {
+    fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if self.constant {
+            return Err(RuntimeError::new(
+                name.clone(),
+                &format!("Can't assign to constant '{}'.", name.lexeme),
+            ));
+        }
+        self.value = value;
+        Ok(())
+    }
+}
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError::new(
+        name.clone(),
+        &format!("Undefined variable '{}'.", name.lexeme),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::expression::LiteralValue;
+    use crate::lexer::token::TokenType;
+
+    fn name(lexeme: &str, line: usize) -> Token {
+        Token::new(
+            TokenType::Identifier,
+            lexeme.to_string(),
+            None,
+            line,
+            0,
+            (0, 0),
+        )
+    }
+
+    fn number(x: i64) -> Value {
+        Value::Literal(LiteralValue::Integer(x))
+    }
+
+    #[test]
+    fn test_define_and_get() {
+        let mut environment = Environment::new();
+        environment.define("x", number(1));
+        assert_eq!(environment.get(&name("x", 1)).unwrap(), number(1));
+
+        environment.define("x", number(2));
+        assert_eq!(environment.get(&name("x", 1)).unwrap(), number(2));
+    }
+
+    #[test]
+    fn test_undefined() {
+        let mut environment = Environment::new();
+        assert_eq!(
+            environment.get(&name("x", 3)).unwrap_err().to_string(),
+            "[line 3] Undefined variable 'x'."
+        );
+        assert_eq!(
+            environment
+                .assign(&name("y", 4), number(1))
+                .unwrap_err()
+                .to_string(),
+            "[line 4] Undefined variable 'y'."
+        );
+        assert!(environment.get(&name("y", 5)).is_err());
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut environment = Environment::new();
+        environment.define("x", number(1));
+        environment.assign(&name("x", 1), number(2)).unwrap();
+        assert_eq!(environment.get(&name("x", 1)).unwrap(), number(2));
+
+        environment.define_constant("limit", number(10));
+        assert_eq!(
+            environment
+                .assign(&name("limit", 2), number(11))
+                .unwrap_err()
+                .to_string(),
+            "[line 2] Can't assign to constant 'limit'."
+        );
+        assert_eq!(environment.get(&name("limit", 2)).unwrap(), number(10));
+    }
+
+    #[test]
+    fn test_declare() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .declare(&name("x", 1), number(1))
+            .unwrap();
+        globals
+            .borrow_mut()
+            .declare_constant(&name("x", 2), number(2))
+            .unwrap();
+        assert_eq!(
+            globals
+                .borrow_mut()
+                .declare(&name("x", 3), number(3))
+                .unwrap_err()
+                .to_string(),
+            "[line 3] Can't redeclare constant 'x'."
+        );
+        assert!(globals
+            .borrow_mut()
+            .declare_constant(&name("x", 4), number(4))
+            .is_err());
+        assert_eq!(globals.borrow().get(&name("x", 5)).unwrap(), number(2));
+
+        // A constant only guards its own scope; an inner one can shadow it.
+        let mut local = Environment::with_enclosing(globals.clone());
+        local.declare(&name("x", 6), number(6)).unwrap();
+        assert_eq!(local.get(&name("x", 6)).unwrap(), number(6));
+    }
+
+    #[test]
+    fn test_enclosing() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("x", number(1));
+        globals.borrow_mut().define("y", number(1));
+
+        let mut local = Environment::with_enclosing(globals.clone());
+        local.define("x", number(2));
+        local.assign(&name("y", 1), number(3)).unwrap();
+
+        assert_eq!(local.get(&name("x", 1)).unwrap(), number(2));
+        assert_eq!(globals.borrow().get(&name("x", 1)).unwrap(), number(1));
+        assert_eq!(globals.borrow().get(&name("y", 1)).unwrap(), number(3));
+        assert!(local.assign(&name("z", 1), number(1)).is_err());
+    }
+
+    #[test]
+    fn test_at_distance() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("x", number(1));
+        let outer = Rc::new(RefCell::new(Environment::with_enclosing(globals.clone())));
+        outer.borrow_mut().define_constant("limit", number(5));
+        let mut inner = Environment::with_enclosing(outer.clone());
+        inner.define("x", number(2));
+
+        assert_eq!(inner.get_at(0, &name("x", 1)).unwrap(), number(2));
+        assert_eq!(inner.get_at(2, &name("x", 1)).unwrap(), number(1));
+        inner.assign_at(2, &name("x", 1), number(3)).unwrap();
+        assert_eq!(globals.borrow().get(&name("x", 1)).unwrap(), number(3));
+
+        assert!(inner.get_at(1, &name("x", 1)).is_err());
+        assert!(inner.get_at(3, &name("x", 1)).is_err());
+        assert_eq!(
+            inner
+                .assign_at(1, &name("limit", 7), number(6))
+                .unwrap_err()
+                .to_string(),
+            "[line 7] Can't assign to constant 'limit'."
+        );
+    }
+}
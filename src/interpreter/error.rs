@@ -0,0 +1,39 @@
+use crate::interpreter::value::Value;
+use crate::lexer::token::Token;
+
+/// An error raised while running a program, such as an operator applied to
+/// values of the wrong type, reported at the token where it happened.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    pub token: Token,
+}
+
+impl RuntimeError {
+    pub fn new(token: Token, message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            token,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[line {}] {}", self.token.line_number, self.message)
+    }
+}
+
+/// Why statements stopped running before the end: an error, or a `return`
+/// carrying its value back to the call.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
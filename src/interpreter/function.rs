@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::expression::LiteralValue;
+use crate::ast::statement::Statement;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::error::{RuntimeError, Unwind};
+use crate::interpreter::interpreter::Interpreter;
+use crate::interpreter::value::Value;
+use crate::lexer::token::Token;
+
+/// Anything a program can call. The interpreter checks the number of
+/// arguments against `arity` before calling, so `call` always gets the
+/// right number.
+pub trait Callable {
+    fn arity(&self) -> usize;
+
+    /// Errors that don't come from a token of their own are reported at
+    /// `paren`, the call's closing parenthesis.
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+}
+
+/// A function declared in a program, along with the scope it was declared
+/// in, which its body sees when it runs.
+pub struct OliFunction {
+    pub name: String,
+    pub params: Rc<[Token]>,
+    pub body: Rc<[Statement]>,
+    pub closure: Rc<RefCell<Environment>>,
+    /// Whether this is a class's `init` method, which gives back the
+    /// instance it's bound to however it returns.
+    pub is_initializer: bool,
+}
+
+impl OliFunction {
+    /// The method with `this` bound to `instance`, in a scope of its own
+    /// between the method's closure and its body.
+    pub fn bind(&self, instance: Value) -> OliFunction {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define("this", instance);
+        OliFunction {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: self.body.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl Callable for OliFunction {
+    fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        for (param, argument) in self.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+        let value = match interpreter.execute_block(&self.body, environment) {
+            Ok(()) => Value::Literal(LiteralValue::Nil),
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+        };
+        // The resolver allows only a bare `return` in an initializer.
+        match self.closure.borrow().lookup("this") {
+            Some(instance) if self.is_initializer => Ok(instance),
+            _ => Ok(value),
+        }
+    }
+}
+
+/// Functions are only ever equal to themselves.
+impl PartialEq for OliFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for OliFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for OliFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name)
+    }
+}
+
+/// What a native function does with its arguments. An error message is
+/// reported at the call.
+pub type NativeFn = fn(&[Value]) -> Result<Value, String>;
+
+/// A function written in Rust and installed with `Interpreter::define_native`.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl Callable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments).map_err(|message| RuntimeError::new(paren.clone(), &message))
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
@@ -0,0 +1,1290 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::ast::expression::{Expression, LiteralValue};
+use crate::ast::operators;
+use crate::ast::statement::Statement;
+use crate::interpreter::class::OliClass;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::error::{RuntimeError, Unwind};
+use crate::interpreter::function::{Callable, NativeFn, NativeFunction, OliFunction};
+use crate::interpreter::natives;
+use crate::interpreter::value::Value;
+use crate::lexer::token::{Token, TokenType};
+
+/// How deeply calls may nest before the program stops with a stack overflow
+/// error, however little stack they take.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// How much stack a program's calls may take by default before it stops with
+/// a stack overflow error. That leaves room to spare on a thread with the
+/// 2 MiB Rust gives threads by default, where it's enough for around a
+/// hundred calls unoptimised and several hundred optimised. A thread with a
+/// bigger stack can allow more; see `set_stack_limit`.
+pub const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+/// Evaluates the tree directly, one node at a time. Globals live as long as
+/// the interpreter, so a REPL session keeps one for all its lines.
+///
+/// What `say` prints goes to the interpreter's output, which is stdout unless
+/// it's given another writer.
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    /// The innermost scope, which is `globals` outside any block.
+    environment: Rc<RefCell<Environment>>,
+    output: Box<dyn Write>,
+    /// How many calls are running, one inside the next.
+    call_depth: usize,
+    /// How many bytes of stack calls may take, counted from `stack_base`.
+    stack_limit: usize,
+    /// Where the stack was when the outermost running call was made.
+    stack_base: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// An interpreter with the builtin functions defined, printing to
+    /// stdout.
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// An interpreter with the builtin functions defined, printing to
+    /// `output`.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Self {
+            environment: globals.clone(),
+            globals,
+            output: Box::new(output),
+            call_depth: 0,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            stack_base: 0,
+        };
+        for &(name, arity, function) in natives::NATIVES {
+            interpreter.define_native(name, arity, function);
+        }
+        interpreter
+    }
+
+    /// Lets calls take up to `bytes` of stack, counting from the outermost
+    /// one, before the program stops with a stack overflow error. The thread
+    /// running the interpreter needs that much to spare, and some more for
+    /// the expressions between one call and the next.
+    pub fn set_stack_limit(&mut self, bytes: usize) {
+        self.stack_limit = bytes;
+    }
+
+    /// Where `say` prints, for a REPL to echo values to the same place.
+    pub fn output(&mut self) -> &mut dyn Write {
+        self.output.as_mut()
+    }
+
+    /// The globals the program has defined, sorted by name, leaving out the
+    /// builtin functions.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.globals
+            .borrow()
+            .bindings()
+            .into_iter()
+            .filter(|(_, value)| !matches!(value, Value::Native(_)))
+            .collect()
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.environment.borrow_mut().define(name, value);
+    }
+
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        self.environment.borrow_mut().define_constant(name, value);
+    }
+
+    /// Installs a Rust function as a global the program can call.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction {
+            name: name.to_string(),
+            arity,
+            function,
+        };
+        self.globals
+            .borrow_mut()
+            .define(name, Value::Native(Rc::new(native)));
+    }
+
+    pub fn execute(&mut self, statement: &Statement) -> Result<(), RuntimeError> {
+        match self.execute_statement(statement) {
+            Ok(()) => Ok(()),
+            Err(Unwind::Error(error)) => Err(error),
+            // The resolver only accepts `return` inside a function body.
+            Err(Unwind::Return(_)) => Ok(()),
+        }
+    }
+
+    /// Like `evaluate`, hands each kind of statement to a method of its own,
+    /// since every call made in a program adds this frame to the stack.
+    fn execute_statement(&mut self, statement: &Statement) -> Result<(), Unwind> {
+        match statement {
+            Statement::Block(statements) => self.block(statements),
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => self.class(name, superclass.as_ref(), methods),
+            Statement::Const { name, initializer } => self.variable(name, Some(initializer), true),
+            Statement::DoWhile { body, condition } => self.do_while(body, condition),
+            Statement::Expression(expression) => self.expression_statement(expression),
+            Statement::Function { name, params, body } => {
+                self.function_declaration(name, params, body)
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.if_statement(condition, then_branch, else_branch.as_deref()),
+            Statement::Return { value, .. } => self.return_statement(value.as_ref()),
+            Statement::Say(expression) => self.say(expression),
+            Statement::Var { name, initializer } => {
+                self.variable(name, initializer.as_ref(), false)
+            }
+            Statement::While { condition, body } => self.while_loop(condition, body),
+            Statement::Break { keyword } | Statement::Continue { keyword } => {
+                Err(unsupported(keyword, statement).into())
+            }
+            Statement::Match { subject, .. } => Err(unsupported(subject.token(), statement).into()),
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement]) -> Result<(), Unwind> {
+        let enclosing = self.environment.clone();
+        self.execute_block(statements, Environment::with_enclosing(enclosing))
+    }
+
+    /// A `var`, which starts out Nil without an initializer, or a `const`.
+    fn variable(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expression>,
+        constant: bool,
+    ) -> Result<(), Unwind> {
+        let value = match initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Literal(LiteralValue::Nil),
+        };
+        let mut environment = self.environment.borrow_mut();
+        if constant {
+            environment.declare_constant(name, value)?;
+        } else {
+            environment.declare(name, value)?;
+        }
+        Ok(())
+    }
+
+    fn do_while(&mut self, body: &Statement, condition: &Expression) -> Result<(), Unwind> {
+        self.execute_statement(body)?;
+        self.while_loop(condition, body)
+    }
+
+    fn expression_statement(&mut self, expression: &Expression) -> Result<(), Unwind> {
+        self.evaluate(expression)?;
+        Ok(())
+    }
+
+    fn function_declaration(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Statement],
+    ) -> Result<(), Unwind> {
+        let function = self.function(&name.lexeme, params, body);
+        self.environment
+            .borrow_mut()
+            .declare(name, Value::Function(Rc::new(function)))?;
+        Ok(())
+    }
+
+    fn if_statement(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Statement,
+        else_branch: Option<&Statement>,
+    ) -> Result<(), Unwind> {
+        if self.evaluate(condition)?.is_truthy() {
+            self.execute_statement(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute_statement(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn return_statement(&mut self, value: Option<&Expression>) -> Result<(), Unwind> {
+        let value = match value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Literal(LiteralValue::Nil),
+        };
+        Err(Unwind::Return(value))
+    }
+
+    /// The line is written whole once the value is known, so an error leaves
+    /// nothing half printed. A failed write, as to a pipe whose reader has
+    /// gone, isn't the program's error.
+    fn say(&mut self, expression: &Expression) -> Result<(), Unwind> {
+        let text = self.evaluate(expression)?.stringify(false);
+        let _ = writeln!(self.output, "{}", text);
+        Ok(())
+    }
+
+    fn while_loop(&mut self, condition: &Expression, body: &Statement) -> Result<(), Unwind> {
+        while self.evaluate(condition)?.is_truthy() {
+            self.execute_statement(body)?;
+        }
+        Ok(())
+    }
+
+    fn class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expression>,
+        methods: &[Statement],
+    ) -> Result<(), Unwind> {
+        let superclass = match superclass {
+            Some(expression) => match self.evaluate(expression)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        expression.token().clone(),
+                        "Superclass must be a class.",
+                    )
+                    .into())
+                }
+            },
+            None => None,
+        };
+
+        // Methods of a subclass close over a scope holding `super`.
+        let enclosing = self.environment.clone();
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(enclosing.clone());
+            environment.define("super", Value::Class(superclass.clone()));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+        let methods = methods
+            .iter()
+            .filter_map(|method| match method {
+                Statement::Function { name, params, body } => Some((
+                    name.lexeme.clone(),
+                    Rc::new(OliFunction {
+                        is_initializer: name.lexeme == "init",
+                        ..self.function(&name.lexeme, params, body)
+                    }),
+                )),
+                _ => None,
+            })
+            .collect();
+        self.environment = enclosing;
+
+        let class = OliClass {
+            name: name.lexeme.clone(),
+            superclass,
+            methods,
+        };
+        self.environment
+            .borrow_mut()
+            .declare(name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    /// Runs the statements in `environment`, then goes back to the current
+    /// scope whether they finished or failed.
+    pub(crate) fn execute_block(
+        &mut self,
+        statements: &[Statement],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute_statement(statement));
+        self.environment = previous;
+        result
+    }
+
+    /// Finds a variable where the resolver said it would be.
+    fn look_up(&self, name: &Token, distance: Option<usize>) -> Result<Value, RuntimeError> {
+        match distance {
+            Some(distance) => self.environment.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    /// A function closing over the current scope.
+    fn function(&self, name: &str, params: &[Token], body: &[Statement]) -> OliFunction {
+        OliFunction {
+            name: name.to_string(),
+            params: params.into(),
+            body: body.into(),
+            closure: self.environment.clone(),
+            is_initializer: false,
+        }
+    }
+
+    /// Calls `callee`, reporting errors about the call itself at `paren`.
+    fn call(
+        &mut self,
+        callee: Value,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let callable: &dyn Callable = match &callee {
+            Value::Function(function) => function.as_ref(),
+            Value::Native(function) => function.as_ref(),
+            Value::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::new(
+                    paren.clone(),
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+        if arguments.len() != callable.arity() {
+            return Err(RuntimeError::new(
+                paren.clone(),
+                &format!(
+                    "Expected {} arguments but got {}.",
+                    callable.arity(),
+                    arguments.len()
+                ),
+            ));
+        }
+        if self.call_depth == 0 {
+            self.stack_base = stack_position();
+        }
+        if self.call_depth == MAX_CALL_DEPTH
+            || stack_position().abs_diff(self.stack_base) > self.stack_limit
+        {
+            return Err(RuntimeError::new(paren.clone(), "Stack overflow."));
+        }
+
+        self.call_depth += 1;
+        let result = callable.call(self, paren, arguments);
+        self.call_depth -= 1;
+        result
+    }
+
+    /// Each kind of expression with operands to work through is evaluated by
+    /// a method of its own. That keeps this frame, which every level of
+    /// nesting adds to the stack, small even in unoptimised builds.
+    pub fn evaluate(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        match expression {
+            Expression::Assign {
+                name,
+                value,
+                distance,
+            } => self.assign(name, value, *distance),
+            Expression::Binary { .. } | Expression::Logical { .. } => {
+                self.operator_chain(expression)
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.call_expression(callee, paren, arguments),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => self.conditional(condition, then_branch, else_branch),
+            Expression::Grouping { expression, .. } => self.evaluate(expression),
+            Expression::Get { object, name } => self.get(object, name),
+            Expression::Lambda { params, body, .. } => self.lambda(params, body),
+            Expression::Literal { value, .. } => Ok(Value::Literal(value.clone())),
+            Expression::Unary { operator, right } => self.unary(operator, right),
+            Expression::Set {
+                object,
+                name,
+                value,
+            } => self.set(object, name, value),
+            Expression::Super {
+                keyword,
+                method,
+                distance,
+            } => self.super_method(keyword, method, *distance),
+            Expression::This { keyword, distance } => self.look_up(keyword, *distance),
+            Expression::Variable { name, distance } => self.look_up(name, *distance),
+            Expression::ArrayLiteral { .. } | Expression::Index { .. } => {
+                Err(unsupported_expression(expression))
+            }
+        }
+    }
+
+    fn lambda(&self, params: &[Token], body: &[Statement]) -> Result<Value, RuntimeError> {
+        Ok(Value::Function(Rc::new(
+            self.function("lambda", params, body),
+        )))
+    }
+
+    fn assign(
+        &mut self,
+        name: &Token,
+        value: &Expression,
+        distance: Option<usize>,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.evaluate(value)?;
+        match distance {
+            Some(distance) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(distance, name, value.clone())?
+            }
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    /// Binary and logical operators, worked through from the leftmost
+    /// operand of their chain in a loop, since a long chain nests too deep
+    /// to recurse down.
+    fn operator_chain(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        let (leftmost, links) = expression.operator_chain();
+        let mut value = self.evaluate(leftmost)?;
+        for (operator, right) in links {
+            value = self.operation(value, operator, right)?;
+        }
+        Ok(value)
+    }
+
+    /// Applies an operator to its left operand's value, evaluating the right
+    /// operand only if the operator needs it. `and` and `or` give back
+    /// whichever operand decided them, not a bool.
+    fn operation(
+        &mut self,
+        left: Value,
+        operator: &Token,
+        right: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        match operator.token_type {
+            TokenType::Comma => self.evaluate(right),
+            // The right operand is only needed when the left one is Nil.
+            TokenType::QuestionQuestion => match left {
+                Value::Literal(LiteralValue::Nil) => self.evaluate(right),
+                left => Ok(left),
+            },
+            TokenType::Or if left.is_truthy() => Ok(left),
+            TokenType::And if !left.is_truthy() => Ok(left),
+            TokenType::Or | TokenType::And => self.evaluate(right),
+            _ => {
+                let right = self.evaluate(right)?;
+                binary(operator, &left, &right)
+            }
+        }
+    }
+
+    fn call_expression(
+        &mut self,
+        callee: &Expression,
+        paren: &Token,
+        arguments: &[Expression],
+    ) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+        self.call(callee, paren, arguments)
+    }
+
+    fn conditional(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Expression,
+        else_branch: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        if self.evaluate(condition)?.is_truthy() {
+            self.evaluate(then_branch)
+        } else {
+            self.evaluate(else_branch)
+        }
+    }
+
+    fn get(&mut self, object: &Expression, name: &Token) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => {
+                let receiver = Value::Instance(instance.clone());
+                let value = instance.borrow().get(name, &receiver);
+                value
+            }
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn unary(&mut self, operator: &Token, right: &Expression) -> Result<Value, RuntimeError> {
+        let right = self.evaluate(right)?;
+        unary(operator, &right)
+    }
+
+    fn set(
+        &mut self,
+        object: &Expression,
+        name: &Token,
+        value: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => {
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have fields.",
+            )),
+        }
+    }
+
+    /// The method comes from the class above the one that declared the
+    /// method running, however far down `this` is.
+    fn super_method(
+        &mut self,
+        keyword: &Token,
+        method: &Token,
+        distance: Option<usize>,
+    ) -> Result<Value, RuntimeError> {
+        let superclass = match self.look_up(keyword, distance) {
+            Ok(Value::Class(superclass)) => superclass,
+            _ => {
+                return Err(RuntimeError::new(
+                    keyword.clone(),
+                    "Can't use 'super' in a class with no superclass.",
+                ))
+            }
+        };
+        let this = Token {
+            token_type: TokenType::This,
+            lexeme: "this".to_string(),
+            ..keyword.clone()
+        };
+        // `this` is bound in the scope just inside the one holding `super`.
+        let instance = self.look_up(&this, distance.map(|distance| distance - 1))?;
+        match superclass.find_method(&method.lexeme) {
+            Some(found) => Ok(Value::Function(Rc::new(found.bind(instance)))),
+            None => Err(RuntimeError::new(
+                method.clone(),
+                &format!("Undefined property '{}'.", method.lexeme),
+            )),
+        }
+    }
+}
+
+/// Applies a binary operator to operands already evaluated. It's kept apart
+/// from evaluating them so its frame isn't on the stack while they are.
+fn binary(operator: &Token, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+    let result = match (left, right) {
+        (Value::Literal(left), Value::Literal(right)) => {
+            operators::binary(operator.token_type, left, right)
+        }
+        _ => match operator.token_type {
+            TokenType::EqualEqual => Ok(operators::boolean(left.is_equal(right))),
+            TokenType::BangEqual => Ok(operators::boolean(!left.is_equal(right))),
+            _ => Err(operators::operand_error(
+                operator.token_type,
+                left.type_name(),
+                right.type_name(),
+            )),
+        },
+    };
+    result
+        .map(Value::Literal)
+        .map_err(|message| RuntimeError::new(operator.clone(), &message))
+}
+
+/// Applies a unary operator, as `binary` does a binary one.
+fn unary(operator: &Token, right: &Value) -> Result<Value, RuntimeError> {
+    let result = match right {
+        Value::Literal(right) => operators::unary(operator.token_type, right),
+        right if operator.token_type == TokenType::Bang => {
+            Ok(operators::boolean(!right.is_truthy()))
+        }
+        _ => Err(operators::unary_operand_error(operator.token_type)),
+    };
+    result
+        .map(Value::Literal)
+        .map_err(|message| RuntimeError::new(operator.clone(), &message))
+}
+
+/// Roughly where the top of the stack is, as the address of a local. Only
+/// the distance between two positions means anything.
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+fn unsupported(token: &Token, statement: &Statement) -> RuntimeError {
+    RuntimeError::new(token.clone(), &format!("Can't run {} yet.", statement))
+}
+
+fn unsupported_expression(expression: &Expression) -> RuntimeError {
+    RuntimeError::new(
+        expression.token().clone(),
+        &format!("Can't evaluate {} yet.", expression),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parser::Parser;
+    use crate::interpreter::resolver;
+    use crate::lexer::lexer::Lexer;
+    use crate::SharedBuffer;
+
+    fn evaluate(source: &str) -> Result<Value, RuntimeError> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let mut expression = Parser::new(tokens).parse_expression().unwrap();
+        resolver::resolve_expression(&mut expression).unwrap();
+        Interpreter::new().evaluate(&expression)
+    }
+
+    fn display(source: &str) -> String {
+        evaluate(source).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(display("(1 + 2) * 3"), "9");
+        assert_eq!(display("-(4 - 1.5)"), "-2.5");
+        assert_eq!(display("2 ** 3 ** 2 % 7"), "1");
+        assert_eq!(display("7 / 2 + 0.5"), "4");
+    }
+
+    #[test]
+    fn test_logic_and_comparison() {
+        assert_eq!(display("!Nil"), "True");
+        assert_eq!(display("!0"), "False");
+        assert_eq!(display("\"a\" == \"a\""), "True");
+        assert_eq!(display("Nil == False"), "False");
+        assert_eq!(display("1 == 1.0"), "True");
+    }
+
+    #[test]
+    fn test_other_operators() {
+        assert_eq!(display("1 < 2 ? \"yes\" : \"no\""), "yes");
+        assert_eq!(display("Nil ?? 3"), "3");
+        assert_eq!(display("False ?? 3"), "False");
+        assert_eq!(display("(1, 2)"), "2");
+        assert_eq!(display("\"n = \" + 4 / 2 + \"!\""), "n = 2!");
+        assert_eq!(display("6 & 3 | 1 << 4"), "18");
+    }
+
+    fn error(source: &str) -> String {
+        evaluate(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        assert_eq!(display("Nil or \"default\""), "default");
+        assert_eq!(display("1 and 2"), "2");
+        assert_eq!(display("0 or 2"), "0");
+        assert_eq!(display("Nil and 2"), "Nil");
+        assert_eq!(display("False or Nil or 3 and 4"), "4");
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var hit = 0; False and (hit = 1); True or (hit = 2); Nil ?? (hit = hit + 10);",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "hit"), Ok("10".to_string()));
+
+        run(
+            &mut interpreter,
+            "True and (hit = 1); False or (hit = hit + 2);",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "hit"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert_eq!(error("1 < \"a\""), "[line 1] Operands must be numbers.");
+        assert_eq!(error("-\"a\""), "[line 1] Operand must be a number.");
+        assert_eq!(error("[1, 2]"), "[line 1] Can't evaluate (array 1 2) yet.");
+        assert_eq!(error("1 + Nil"), "[line 1] Can't add number and nil.");
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(error("1 / 0"), "[line 1] Division by zero.");
+        assert_eq!(error("0 / 0"), "[line 1] Division by zero.");
+        assert_eq!(error("1.5 % 0.0"), "[line 1] Division by zero.");
+        assert_eq!(
+            self::error("(1 +\n  2) /\n  (3 - 3)"),
+            "[line 2] Division by zero."
+        );
+        assert_eq!(display("-0.0"), "0");
+        assert_eq!(display("0 * -1.5"), "0");
+        assert_eq!(display("10.0 ** 400 - 10.0 ** 400"), "NaN");
+    }
+
+    fn evaluate_in(interpreter: &mut Interpreter, source: &str) -> Result<String, String> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let mut expression = Parser::new(tokens).parse_expression().unwrap();
+        resolver::resolve_expression(&mut expression).unwrap();
+        match interpreter.evaluate(&expression) {
+            Ok(value) => Ok(value.to_string()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut interpreter = Interpreter::new();
+        let undefined = Err("[line 1] Undefined variable 'x'.".to_string());
+        assert_eq!(evaluate_in(&mut interpreter, "x"), undefined);
+        assert_eq!(evaluate_in(&mut interpreter, "x = 1"), undefined);
+
+        interpreter.define("x", Value::Literal(LiteralValue::Integer(1)));
+        interpreter.define_constant("limit", Value::Literal(LiteralValue::Integer(3)));
+        assert_eq!(
+            evaluate_in(&mut interpreter, "x = x + 1"),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "x * limit"),
+            Ok("6".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "limit = x"),
+            Err("[line 1] Can't assign to constant 'limit'.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_constant_redeclaration() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "var x = 1; const x = 2;").unwrap();
+        let redeclared = Err("[line 1] Can't redeclare constant 'x'.".to_string());
+        assert_eq!(run(&mut interpreter, "var x = 3;"), redeclared);
+        assert_eq!(run(&mut interpreter, "const x = 3;"), redeclared);
+        assert_eq!(run(&mut interpreter, "fun x() {}"), redeclared);
+        assert_eq!(run(&mut interpreter, "class x {}"), redeclared);
+        assert_eq!(
+            run(&mut interpreter, "x = 3;"),
+            Err("[line 1] Can't assign to constant 'x'.".to_string())
+        );
+        assert_eq!(evaluate_in(&mut interpreter, "x"), Ok("2".to_string()));
+
+        run(&mut interpreter, "{ var x = 4; x = 5; }").unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "x"), Ok("2".to_string()));
+    }
+
+    #[test]
+    fn test_runtime_error_lines() {
+        let error = evaluate("(1 +\n  2) *\n  (3 -\n  -True)").unwrap_err();
+        assert_eq!(error.token.lexeme, "-");
+        assert_eq!(error.to_string(), "[line 4] Operand must be a number.");
+
+        assert_eq!(
+            self::error("1 +\n2 +\n\n3 <\n\"a\""),
+            "[line 4] Operands must be numbers."
+        );
+    }
+
+    fn run(interpreter: &mut Interpreter, source: &str) -> Result<(), String> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let mut statements = Parser::new(tokens).parse_program().unwrap();
+        resolver::resolve(&mut statements).unwrap();
+        statements
+            .iter()
+            .try_for_each(|statement| interpreter.execute(statement))
+            .map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn test_block_shadowing() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var a = 1; var inner; { var a = 2; { inner = a; } }",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "inner"), Ok("2".to_string()));
+        assert_eq!(evaluate_in(&mut interpreter, "a"), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn test_block_assigns_outer_variable() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var a = 1; { { a = a + 1; } const b = a; }",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "a"), Ok("2".to_string()));
+        assert_eq!(
+            evaluate_in(&mut interpreter, "b"),
+            Err("[line 1] Undefined variable 'b'.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_block_error_restores_scope() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            run(
+                &mut interpreter,
+                "var a = 1; { var a = 2; var b = 3; { a + Nil; } }"
+            ),
+            Err("[line 1] Can't add number and nil.".to_string())
+        );
+        assert_eq!(evaluate_in(&mut interpreter, "a"), Ok("1".to_string()));
+        assert_eq!(
+            evaluate_in(&mut interpreter, "b"),
+            Err("[line 1] Undefined variable 'b'.".to_string())
+        );
+        run(&mut interpreter, "var b = a;").unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "b"), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn test_loops() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var sum = 0;
+            for (var i = 1; i <= 4; i = i + 1) sum = sum + i;
+            var n = 0;
+            do n = n + 1; while (False);",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "sum"), Ok("10".to_string()));
+        assert_eq!(evaluate_in(&mut interpreter, "n"), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn test_say_output() {
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        run(
+            &mut interpreter,
+            "var i = 0;
+            while (i < 3) {
+                say \"line \" + i;
+                i = i + 1;
+            }
+            say Nil;",
+        )
+        .unwrap();
+        assert_eq!(output.text(), "line 0\nline 1\nline 2\nNil\n");
+    }
+
+    #[test]
+    fn test_say_error_prints_nothing() {
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        let result = run(
+            &mut interpreter,
+            "fun fail() { return -\"x\"; }
+            say \"before\";
+            say \"during \" + fail();
+            say \"after\";",
+        );
+        assert_eq!(
+            result,
+            Err("[line 1] Operand must be a number.".to_string())
+        );
+        assert_eq!(output.text(), "before\n");
+    }
+
+    #[test]
+    fn test_if_on_nil() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var a; var b; if (Nil) a = 1; else a = 2; if (0) b = 3; elif (True) b = 4;",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "a"), Ok("2".to_string()));
+        assert_eq!(evaluate_in(&mut interpreter, "b"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn test_recursive_function() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); }",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "fib(10)"),
+            Ok("55".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "fib"),
+            Ok("<fn fib>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_return() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun find(target) {
+                var i = 0;
+                while (True) {
+                    if (i == target) return i * 10;
+                    i = i + 1;
+                }
+            }
+            fun nothing() { return; }
+            fun empty() {}",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "find(3)"),
+            Ok("30".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "nothing()"),
+            Ok("Nil".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "empty()"),
+            Ok("Nil".to_string())
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun counter() { var n = 0; return () -> n = n + 1; }
+            var next = counter();
+            next();",
+        )
+        .unwrap();
+        assert_eq!(evaluate_in(&mut interpreter, "next()"), Ok("2".to_string()));
+        assert_eq!(
+            evaluate_in(&mut interpreter, "5 |> fun (x) { return x * x; }"),
+            Ok("25".to_string())
+        );
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            run(&mut interpreter, "fun f(a, b) {}\nf(1);"),
+            Err("[line 2] Expected 2 arguments but got 1.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "3(1)"),
+            Err("[line 1] Can only call functions and classes.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "f + 1"),
+            Err("[line 1] Can't add function and number.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "f == f and !f == False"),
+            Ok("True".to_string())
+        );
+    }
+
+    #[test]
+    fn test_call_depth() {
+        // `MAX_CALL_DEPTH` calls need more than a test thread's stack when
+        // unoptimised, as they do on the CLI's main thread, and a limit to
+        // match.
+        let deep = std::thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(|| {
+                let mut interpreter = Interpreter::new();
+                interpreter.set_stack_limit(16 * 1024 * 1024);
+                assert_eq!(
+                    run(&mut interpreter, "fun f(n) {\n  return f(n + 1);\n}\nf(0);"),
+                    Err("[line 2] Stack overflow.".to_string())
+                );
+
+                // The depth unwinds with the error, so the full depth is
+                // still there to use.
+                run(
+                    &mut interpreter,
+                    "fun count(n) { if (n == 0) return 0; return count(n - 1) + 1; }",
+                )
+                .unwrap();
+                let depth = format!("count({})", MAX_CALL_DEPTH - 1);
+                assert_eq!(
+                    evaluate_in(&mut interpreter, &depth),
+                    Ok((MAX_CALL_DEPTH - 1).to_string())
+                );
+                let too_deep = format!("count({})", MAX_CALL_DEPTH);
+                assert_eq!(
+                    evaluate_in(&mut interpreter, &too_deep),
+                    Err("[line 1] Stack overflow.".to_string())
+                );
+            })
+            .unwrap();
+        deep.join().unwrap();
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        // Runs out of stack long before `MAX_CALL_DEPTH`, and unwinds so the
+        // next program can use it all again.
+        let mut interpreter = Interpreter::new();
+        interpreter.set_stack_limit(64 * 1024);
+        run(
+            &mut interpreter,
+            "fun count(n) { if (n == 0) return 0; return count(n - 1) + 1; }",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "count(500)"),
+            Err("[line 1] Stack overflow.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "count(2)"),
+            Ok("2".to_string())
+        );
+    }
+
+    fn double(arguments: &[Value]) -> Result<Value, String> {
+        match &arguments[0] {
+            Value::Literal(LiteralValue::Integer(x)) => {
+                Ok(Value::Literal(LiteralValue::Integer(x * 2)))
+            }
+            value => Err(format!("Can't double a {}.", value.type_name())),
+        }
+    }
+
+    #[test]
+    fn test_native_functions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("double", 1, double);
+        assert_eq!(
+            evaluate_in(&mut interpreter, "double(4) + double(1)"),
+            Ok("10".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "double"),
+            Ok("<native fn double>".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "double(\n\"a\")"),
+            Err("[line 2] Can't double a string.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "double(1, 2)"),
+            Err("[line 1] Expected 1 arguments but got 2.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "clock(1)"),
+            Err("[line 1] Expected 0 arguments but got 1.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut interpreter = Interpreter::new();
+        let tokens = Lexer::new("clock()").scan_tokens().unwrap();
+        let mut expression = Parser::new(tokens).parse_expression().unwrap();
+        resolver::resolve_expression(&mut expression).unwrap();
+        match interpreter.evaluate(&expression) {
+            Ok(Value::Literal(LiteralValue::Number(seconds))) => assert!(seconds > 1.0e9),
+            other => panic!("clock() returned {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_class_fields_and_methods() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "class Counter {
+                bump() { this.count = this.count + 1; return this; }
+            }
+            var c = Counter();
+            c.count = 1;
+            c.bump().bump();",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "c.count"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "Counter"),
+            Ok("<class Counter>".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "c"),
+            Ok("<Counter instance>".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "c == c and c != Counter()"),
+            Ok("True".to_string())
+        );
+        // A method taken off an instance stays bound to it.
+        run(&mut interpreter, "var bump = c.bump; bump();").unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "c.count"),
+            Ok("4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_class_initializer() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "class Point {
+                init(x, y) { this.x = x; this.y = y; return; }
+                sum() { return this.x + this.y; }
+            }
+            var p = Point(1, 2);",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "p.sum()"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "Point(1)"),
+            Err("[line 1] Expected 2 arguments but got 1.".to_string())
+        );
+
+        // Calling `init` again reinitializes and gives back the instance,
+        // even through an early bare `return`.
+        assert_eq!(
+            evaluate_in(&mut interpreter, "p.init(3, 4) == p"),
+            Ok("True".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "p.sum()"),
+            Ok("7".to_string())
+        );
+        let init = "class A { init() { return; } }";
+        run(&mut interpreter, init).unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "A().init()"),
+            Ok("<A instance>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "class A {
+                name() { return \"A\"; }
+                greet() { return \"hi from \" + this.name(); }
+            }
+            class B < A {
+                name() { return \"B<\" + super.name(); }
+            }
+            class C < B {
+                name() { return \"C<\" + super.name(); }
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "B().greet()"),
+            Ok("hi from B<A".to_string())
+        );
+        // `super` in B means A even when `this` is a C.
+        assert_eq!(
+            evaluate_in(&mut interpreter, "C().greet()"),
+            Ok("hi from C<B<A".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inherited_initializer() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "class Base { init(x) { this.x = x; } }
+            class Derived < Base {
+                init(x, y) { super.init(x); this.y = y; }
+            }
+            class Plain < Base {}
+            var d = Derived(1, 2);
+            var p = Plain(3);",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_in(&mut interpreter, "d.x + d.y + p.x"),
+            Ok("6".to_string())
+        );
+    }
+
+    #[test]
+    fn test_superclass_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            run(&mut interpreter, "var NotClass = 3;\nclass B < NotClass {}"),
+            Err("[line 2] Superclass must be a class.".to_string())
+        );
+        assert_eq!(
+            run(
+                &mut interpreter,
+                "class A {}\nclass B < A { f() { return super.\nmissing(); } }\nB().f();"
+            ),
+            Err("[line 3] Undefined property 'missing'.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_property_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            run(
+                &mut interpreter,
+                "class A {}\nvar a = A();\nsay a.\nmissing;"
+            ),
+            Err("[line 4] Undefined property 'missing'.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "a.count = a.count"),
+            Err("[line 1] Undefined property 'count'.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "(1).x"),
+            Err("[line 1] Only instances have properties.".to_string())
+        );
+        assert_eq!(
+            evaluate_in(&mut interpreter, "A.x = 1"),
+            Err("[line 1] Only instances have fields.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_error_aborts_loop() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            run(
+                &mut interpreter,
+                "var i = 0;\nwhile (True) {\n  i = i + 1;\n  if (i == 3) -Nil;\n}"
+            ),
+            Err("[line 4] Operand must be a number.".to_string())
+        );
+        assert_eq!(evaluate_in(&mut interpreter, "i"), Ok("3".to_string()));
+    }
+}
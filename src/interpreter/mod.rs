@@ -0,0 +1,9 @@
+pub mod class;
+pub mod environment;
+pub mod error;
+pub mod function;
+#[allow(clippy::module_inception)]
+pub mod interpreter;
+pub mod natives;
+pub mod resolver;
+pub mod value;
@@ -0,0 +1,134 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::expression::LiteralValue;
+use crate::interpreter::function::NativeFn;
+use crate::interpreter::value::Value;
+
+/// The builtins every interpreter starts with, as name, arity and function.
+pub const NATIVES: &[(&str, usize, NativeFn)] = &[
+    ("clock", 0, clock),
+    ("len", 1, len),
+    ("num", 1, num),
+    ("str", 1, str),
+    ("type", 1, type_of),
+];
+
+/// Seconds since the Unix epoch, with a fraction, so scripts can time
+/// themselves.
+fn clock(_: &[Value]) -> Result<Value, String> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+    Ok(Value::Literal(LiteralValue::Number(seconds)))
+}
+
+/// The number of characters in a string.
+fn len(arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::Literal(LiteralValue::StringValue(s)) => Ok(Value::Literal(LiteralValue::Integer(
+            s.chars().count() as i64,
+        ))),
+        value => Err(expected("len", "a string", value)),
+    }
+}
+
+/// Parses a string as a number, integral if it can be.
+fn num(arguments: &[Value]) -> Result<Value, String> {
+    let text = match &arguments[0] {
+        Value::Literal(LiteralValue::StringValue(s)) => s,
+        value => return Err(expected("num", "a string", value)),
+    };
+    let trimmed = text.trim();
+    if let Ok(x) = trimmed.parse::<i64>() {
+        return Ok(Value::Literal(LiteralValue::Integer(x)));
+    }
+    match trimmed.parse::<f64>() {
+        Ok(x) if x.is_finite() => Ok(Value::Literal(LiteralValue::Number(x))),
+        _ => Err(format!("Can't convert \"{}\" to a number.", text)),
+    }
+}
+
+/// The value as `say` would print it.
+fn str(arguments: &[Value]) -> Result<Value, String> {
+    Ok(string(arguments[0].to_string()))
+}
+
+fn type_of(arguments: &[Value]) -> Result<Value, String> {
+    Ok(string(arguments[0].type_name().to_string()))
+}
+
+fn string(s: String) -> Value {
+    Value::Literal(LiteralValue::StringValue(s))
+}
+
+fn expected(function: &str, expected: &str, value: &Value) -> String {
+    format!(
+        "{}() expects {}, not {}.",
+        function,
+        expected,
+        value.type_name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::parser::Parser;
+    use crate::interpreter::interpreter::Interpreter;
+    use crate::lexer::lexer::Lexer;
+
+    fn call(source: &str) -> Result<String, String> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+        match Interpreter::new().evaluate(&expression) {
+            Ok(value) => Ok(value.to_string()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_len() {
+        assert_eq!(call("len(\"héllo\")"), Ok("5".to_string()));
+        assert_eq!(call("len(\"\")"), Ok("0".to_string()));
+        assert_eq!(
+            call("len(12)"),
+            Err("[line 1] len() expects a string, not number.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_str() {
+        assert_eq!(call("str(2.5) + str(1 == 1)"), Ok("2.5True".to_string()));
+        assert_eq!(call("len(str(Nil))"), Ok("3".to_string()));
+        assert_eq!(call("str(3.0)"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn test_num() {
+        assert_eq!(call("num(\"42\") + 1"), Ok("43".to_string()));
+        assert_eq!(call("num(\" 2.5 \") * 2"), Ok("5".to_string()));
+        assert_eq!(call("num(\"-7\")"), Ok("-7".to_string()));
+        assert_eq!(
+            call("num(\"12abc\")"),
+            Err("[line 1] Can't convert \"12abc\" to a number.".to_string())
+        );
+        assert_eq!(
+            call("num(\"inf\")"),
+            Err("[line 1] Can't convert \"inf\" to a number.".to_string())
+        );
+        assert_eq!(
+            call("num(True)"),
+            Err("[line 1] num() expects a string, not bool.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_type() {
+        assert_eq!(call("type(1)"), Ok("number".to_string()));
+        assert_eq!(call("type(1.5)"), Ok("number".to_string()));
+        assert_eq!(call("type(\"a\")"), Ok("string".to_string()));
+        assert_eq!(call("type(False)"), Ok("bool".to_string()));
+        assert_eq!(call("type(Nil)"), Ok("nil".to_string()));
+        assert_eq!(call("type(type)"), Ok("function".to_string()));
+        assert_eq!(call("type((x) -> x)"), Ok("function".to_string()));
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+
+use crate::ast::error::ParserError;
+use crate::ast::expression::Expression;
+use crate::ast::statement::Statement;
+use crate::lexer::token::Token;
+
+/// Works out, before anything runs, which declaration every variable refers
+/// to, and records on the node how many scopes out that declaration is. The
+/// interpreter then goes straight to the right scope, so a closure keeps
+/// seeing the variable it captured even after another of the same name is
+/// declared.
+///
+/// Mistakes that don't need running to spot are reported here, all at once,
+/// among them `return`, `this` and `super` in the wrong place.
+pub fn resolve(statements: &mut [Statement]) -> Result<(), Vec<ParserError>> {
+    let mut resolver = Resolver::default();
+    resolver.statements(statements);
+    resolver.finish()
+}
+
+/// Resolves an expression on its own, as the REPL evaluates a bare one.
+pub fn resolve_expression(expression: &mut Expression) -> Result<(), Vec<ParserError>> {
+    let mut resolver = Resolver::default();
+    resolver.expression(expression);
+    resolver.finish()
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum FunctionKind {
+    #[default]
+    None,
+    Function,
+    /// A class's `init` method, which always gives back the instance.
+    Initializer,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum ClassKind {
+    #[default]
+    None,
+    Class,
+    Subclass,
+}
+
+#[derive(Default)]
+struct Resolver {
+    /// The names declared in each enclosing scope, innermost last, and
+    /// whether their initializer has finished. Globals aren't in here, since
+    /// they can be declared after the code that uses them.
+    scopes: Vec<HashMap<String, bool>>,
+    function: FunctionKind,
+    class: ClassKind,
+    errors: Vec<ParserError>,
+}
+
+impl Resolver {
+    fn finish(self) -> Result<(), Vec<ParserError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ParserError::new(token.clone(), message));
+    }
+
+    fn statements(&mut self, statements: &mut [Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                self.statements(statements);
+                self.scopes.pop();
+            }
+            Statement::Break { .. } | Statement::Continue { .. } => (),
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => self.class(name, superclass.as_mut(), methods),
+            Statement::Const { name, initializer } => {
+                self.declare(name);
+                self.expression(initializer);
+                self.define(name);
+            }
+            Statement::DoWhile { body, condition } => {
+                self.statement(body);
+                self.expression(condition);
+            }
+            Statement::Expression(expression) | Statement::Say(expression) => {
+                self.expression(expression)
+            }
+            Statement::Function { name, params, body } => {
+                self.declare(name);
+                self.define(name);
+                self.function(FunctionKind::Function, params, body);
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Statement::Match {
+                subject,
+                arms,
+                default,
+            } => {
+                self.expression(subject);
+                for (pattern, body) in arms {
+                    self.expression(pattern);
+                    self.statement(body);
+                }
+                if let Some(default) = default {
+                    self.statement(default);
+                }
+            }
+            Statement::Return { keyword, value } => {
+                if self.function == FunctionKind::None {
+                    self.error(keyword, "Can't return from top-level code");
+                }
+                if let Some(value) = value {
+                    if self.function == FunctionKind::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer");
+                    }
+                    self.expression(value);
+                }
+            }
+            Statement::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.define(name);
+            }
+            Statement::While { condition, body } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+        }
+    }
+
+    /// Mirrors the scopes the interpreter makes for a class: one holding
+    /// `super` if there's a superclass, then one holding `this` for each
+    /// bound method.
+    fn class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&mut Expression>,
+        methods: &mut [Statement],
+    ) {
+        self.declare(name);
+        self.define(name);
+
+        let enclosing = self.class;
+        self.class = ClassKind::Class;
+        if let Some(superclass) = superclass {
+            if let Expression::Variable {
+                name: superclass_name,
+                ..
+            } = superclass
+            {
+                if superclass_name.lexeme == name.lexeme {
+                    self.error(superclass_name, "A class can't inherit from itself");
+                }
+            }
+            self.expression(superclass);
+            self.class = ClassKind::Subclass;
+            self.scopes
+                .push(HashMap::from([("super".to_string(), true)]));
+        }
+
+        self.scopes
+            .push(HashMap::from([("this".to_string(), true)]));
+        for method in methods {
+            if let Statement::Function { name, params, body } = method {
+                let kind = if name.lexeme == "init" {
+                    FunctionKind::Initializer
+                } else {
+                    FunctionKind::Function
+                };
+                self.function(kind, params, body);
+            }
+        }
+        self.scopes.pop();
+
+        if self.class == ClassKind::Subclass {
+            self.scopes.pop();
+        }
+        self.class = enclosing;
+    }
+
+    /// Parameters and body share a scope, as they do when the function runs.
+    fn function(&mut self, kind: FunctionKind, params: &[Token], body: &mut [Statement]) {
+        let enclosing = self.function;
+        self.function = kind;
+        self.scopes.push(HashMap::new());
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.statements(body);
+        self.scopes.pop();
+        self.function = enclosing;
+    }
+
+    fn declare(&mut self, name: &Token) {
+        match self.scopes.last_mut() {
+            Some(scope) if scope.contains_key(&name.lexeme) => {
+                let message = format!("Already a variable named '{}' in this scope", name.lexeme);
+                self.error(name, &message);
+            }
+            Some(scope) => {
+                scope.insert(name.lexeme.clone(), false);
+            }
+            // Globals can be declared again, as the REPL often does, and
+            // an initializer can read the value being replaced.
+            None => (),
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    /// How many scopes out `name` is declared, or `None` for a global.
+    fn distance(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name))
+    }
+
+    /// Resolves a chain of binary or logical operators from its leftmost
+    /// operand in a loop, as the interpreter runs it, since a long chain
+    /// nests too deep to recurse down.
+    fn operator_chain(&mut self, expression: &mut Expression) {
+        let mut rights = Vec::new();
+        let mut leftmost = expression;
+        while let Expression::Binary { left, right, .. } | Expression::Logical { left, right, .. } =
+            leftmost
+        {
+            rights.push(right);
+            leftmost = left;
+        }
+
+        self.expression(leftmost);
+        for right in rights.into_iter().rev() {
+            self.expression(right);
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::ArrayLiteral { elements, .. } => {
+                elements
+                    .iter_mut()
+                    .for_each(|element| self.expression(element));
+            }
+            Expression::Assign {
+                name,
+                value,
+                distance,
+            } => {
+                self.expression(value);
+                *distance = self.distance(&name.lexeme);
+            }
+            Expression::Binary { .. } | Expression::Logical { .. } => {
+                self.operator_chain(expression)
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                arguments
+                    .iter_mut()
+                    .for_each(|argument| self.expression(argument));
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Get { object, .. } => self.expression(object),
+            Expression::Grouping { expression, .. } => self.expression(expression),
+            Expression::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Lambda { params, body, .. } => {
+                self.function(FunctionKind::Function, params, body)
+            }
+            Expression::Literal { .. } => (),
+            Expression::Set { object, value, .. } => {
+                self.expression(value);
+                self.expression(object);
+            }
+            Expression::Super {
+                keyword, distance, ..
+            } => {
+                match self.class {
+                    ClassKind::None => self.error(keyword, "Can't use 'super' outside of a class"),
+                    ClassKind::Class => {
+                        self.error(keyword, "Can't use 'super' in a class with no superclass")
+                    }
+                    ClassKind::Subclass => (),
+                }
+                *distance = self.distance("super");
+            }
+            Expression::This { keyword, distance } => {
+                if self.class == ClassKind::None {
+                    self.error(keyword, "Can't use 'this' outside of a class");
+                }
+                *distance = self.distance("this");
+            }
+            Expression::Unary { right, .. } => self.expression(right),
+            Expression::Variable { name, distance } => {
+                if let Some(scope) = self.scopes.last() {
+                    if scope.get(&name.lexeme) == Some(&false) {
+                        let message =
+                            format!("Can't read '{}' in its own initializer", name.lexeme);
+                        self.error(name, &message);
+                    }
+                }
+                *distance = self.distance(&name.lexeme);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parser::Parser;
+    use crate::interpreter::interpreter::Interpreter;
+    use crate::lexer::lexer::Lexer;
+
+    fn parse(source: &str) -> Vec<Statement> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse_program().unwrap()
+    }
+
+    fn errors(statements: &mut [Statement]) -> Vec<String> {
+        match resolve(statements) {
+            Ok(()) => vec![],
+            Err(errors) => errors.iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_distances() {
+        let mut statements = parse("var a; { var b; fun f(c) { a; b; c = 1; } }");
+        resolve(&mut statements).unwrap();
+        let Statement::Block(block) = &statements[1] else {
+            panic!("Expected a block");
+        };
+        let Statement::Function { body, .. } = &block[1] else {
+            panic!("Expected a function");
+        };
+        let distances: Vec<Option<usize>> = body
+            .iter()
+            .map(|statement| match statement {
+                Statement::Expression(Expression::Variable { distance, .. })
+                | Statement::Expression(Expression::Assign { distance, .. }) => *distance,
+                statement => panic!("Unexpected {}", statement),
+            })
+            .collect();
+        assert_eq!(distances, vec![None, Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_own_initializer() {
+        assert_eq!(
+            errors(&mut parse("var a = 1;\n{\n  var a = a + 1;\n}")),
+            vec!["[line 3] Error at 'a': Can't read 'a' in its own initializer."]
+        );
+        assert_eq!(
+            errors(&mut parse("fun f() {\n  var b = b;\n}")),
+            vec!["[line 2] Error at 'b': Can't read 'b' in its own initializer."]
+        );
+        // A global's initializer reads the global it's replacing.
+        assert!(errors(&mut parse("var a = 1; var a = a + 1;")).is_empty());
+        // A function can refer to the variable it's being assigned to.
+        assert!(errors(&mut parse("{ var f = fun () { return f; }; }")).is_empty());
+    }
+
+    #[test]
+    fn test_redeclaration() {
+        assert_eq!(
+            errors(&mut parse("{\n  var a = 1;\n  const a = 2;\n}")),
+            vec!["[line 3] Error at 'a': Already a variable named 'a' in this scope."]
+        );
+        assert_eq!(
+            errors(&mut parse("fun f(x, x) {}")),
+            vec!["[line 1] Error at 'x': Already a variable named 'x' in this scope."]
+        );
+        // Globals and shadowing in an inner scope are fine.
+        assert!(errors(&mut parse(
+            "var a = 1; var a = 2; { var a = 3; { var a = 4; } }"
+        ))
+        .is_empty());
+    }
+
+    #[test]
+    fn test_misplaced_keywords() {
+        assert_eq!(
+            errors(&mut parse("say 0;\n  return 1;")),
+            vec!["[line 2] Error at 'return': Can't return from top-level code."]
+        );
+        assert_eq!(
+            errors(&mut parse("say 0;\nfun f() { say this; }")),
+            vec!["[line 2] Error at 'this': Can't use 'this' outside of a class."]
+        );
+        assert_eq!(
+            errors(&mut parse("super.f();")),
+            vec!["[line 1] Error at 'super': Can't use 'super' outside of a class."]
+        );
+        assert_eq!(
+            errors(&mut parse("class A {\n  f() { super.f(); }\n}")),
+            vec!["[line 2] Error at 'super': Can't use 'super' in a class with no superclass."]
+        );
+        assert_eq!(
+            errors(&mut parse("class A < A {}")),
+            vec!["[line 1] Error at 'A': A class can't inherit from itself."]
+        );
+        assert_eq!(
+            errors(&mut parse("class A {\n  init() { return 5; }\n}")),
+            vec!["[line 2] Error at 'return': Can't return a value from an initializer."]
+        );
+        assert!(errors(&mut parse(
+            "class A { init() { if (True) return; var f = fun () { return 5; }; } }"
+        ))
+        .is_empty());
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        assert_eq!(
+            errors(&mut parse("{ var a = a; var a; }\nclass A < A {}")),
+            vec![
+                "[line 1] Error at 'a': Can't read 'a' in its own initializer.",
+                "[line 1] Error at 'a': Already a variable named 'a' in this scope.",
+                "[line 2] Error at 'A': A class can't inherit from itself.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_closure_keeps_its_binding() {
+        let mut statements = parse(
+            "var a = \"global\";
+            var seen = \"\";
+            {
+                fun show() { seen = seen + a + \" \"; }
+                show();
+                var a = \"block\";
+                show();
+            }",
+        );
+        resolve(&mut statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        for statement in &statements {
+            interpreter.execute(statement).unwrap();
+        }
+
+        let tokens = Lexer::new("seen").scan_tokens().unwrap();
+        let mut seen = Parser::new(tokens).parse_expression().unwrap();
+        resolve_expression(&mut seen).unwrap();
+        assert_eq!(
+            interpreter.evaluate(&seen).unwrap().to_string(),
+            "global global "
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::expression::LiteralValue;
+use crate::ast::operators;
+use crate::interpreter::class::{Instance, OliClass};
+use crate::interpreter::function::{NativeFunction, OliFunction};
+
+/// A value a running program can produce. Numbers, strings, booleans and
+/// `Nil` are the values literals have, so they share `LiteralValue`'s rules
+/// for truthiness and equality.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Literal(LiteralValue),
+    Function(Rc<OliFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<OliClass>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Literal(value) => value.is_truthy(),
+            Value::Function(_) | Value::Native(_) | Value::Class(_) | Value::Instance(_) => true,
+        }
+    }
+
+    pub fn is_equal(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Literal(a), Value::Literal(b)) => a.is_equal(b),
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// The name of the value's type, as error messages give it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Literal(value) => value.type_name(),
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+
+    /// The value as a user sees it, whether printed by `say` or echoed by the
+    /// REPL. Whole floats print without a fraction, so `2 * 1.5` shows as
+    /// `3`, and booleans and `Nil` as the keywords that write them. The REPL
+    /// quotes strings, so `"1"` can be told apart from `1`.
+    pub fn stringify(&self, repl: bool) -> String {
+        match self {
+            Value::Literal(LiteralValue::StringValue(s)) if repl => format!("\"{}\"", s),
+            Value::Literal(LiteralValue::StringValue(s)) => s.clone(),
+            Value::Literal(number @ (LiteralValue::Integer(_) | LiteralValue::Number(_))) => {
+                operators::number_text(number)
+            }
+            Value::Literal(value) => value.to_string(),
+            Value::Function(function) => function.to_string(),
+            Value::Native(function) => function.to_string(),
+            Value::Class(class) => class.to_string(),
+            Value::Instance(instance) => instance.borrow().to_string(),
+        }
+    }
+}
+
+/// The value as `say` prints it.
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.stringify(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn both(value: LiteralValue) -> (String, String) {
+        let value = Value::Literal(value);
+        (value.stringify(false), value.stringify(true))
+    }
+
+    fn same(text: &str) -> (String, String) {
+        (text.to_string(), text.to_string())
+    }
+
+    #[test]
+    fn test_stringify_numbers() {
+        assert_eq!(both(LiteralValue::Integer(3)), same("3"));
+        assert_eq!(both(LiteralValue::Integer(-12)), same("-12"));
+        assert_eq!(both(LiteralValue::Number(3.0)), same("3"));
+        assert_eq!(both(LiteralValue::Number(2.5)), same("2.5"));
+        assert_eq!(both(LiteralValue::Number(-0.125)), same("-0.125"));
+    }
+
+    #[test]
+    fn test_stringify_keywords() {
+        assert_eq!(both(LiteralValue::True), same("True"));
+        assert_eq!(both(LiteralValue::False), same("False"));
+        assert_eq!(both(LiteralValue::Nil), same("Nil"));
+    }
+
+    #[test]
+    fn test_stringify_functions() {
+        let native = Value::Native(Rc::new(NativeFunction {
+            name: "clock".to_string(),
+            arity: 0,
+            function: |_| Ok(Value::Literal(LiteralValue::Nil)),
+        }));
+        assert_eq!(native.stringify(false), "<native fn clock>");
+        assert_eq!(native.stringify(true), "<native fn clock>");
+    }
+
+    #[test]
+    fn test_stringify_strings() {
+        assert_eq!(
+            both(LiteralValue::StringValue("hi there".to_string())),
+            ("hi there".to_string(), "\"hi there\"".to_string())
+        );
+        assert_eq!(
+            both(LiteralValue::StringValue(String::new())),
+            (String::new(), "\"\"".to_string())
+        );
+        assert_eq!(
+            Value::Literal(LiteralValue::StringValue("1".to_string())).to_string(),
+            "1"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnrecognizedCharacter,
+    /// A U+FEFF byte order mark anywhere but the very start of the source.
+    ByteOrderMark,
+    UnterminatedString,
+    UnterminatedComment,
+    /// A malformed escape sequence inside a string, with the reason.
+    InvalidEscape(String),
+    /// A number literal that can't be turned into a value, with the reason.
+    InvalidNumber(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub line: usize,
+    pub column: usize,
+    pub lexeme: String,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.kind {
+            LexErrorKind::UnrecognizedCharacter => write!(
+                f,
+                "Unrecognized char at line {}, column {}: {}",
+                self.line, self.column, self.lexeme
+            ),
+            LexErrorKind::ByteOrderMark => write!(
+                f,
+                "Unexpected byte order mark (U+FEFF) at line {}, column {}",
+                self.line, self.column
+            ),
+            LexErrorKind::UnterminatedString => write!(
+                f,
+                "Unterminated string starting at line {}, column {}.",
+                self.line, self.column
+            ),
+            LexErrorKind::UnterminatedComment => write!(
+                f,
+                "Unterminated block comment starting at line {}.",
+                self.line
+            ),
+            LexErrorKind::InvalidEscape(reason) | LexErrorKind::InvalidNumber(reason) => {
+                write!(f, "{} at line {}: {}", reason, self.line, self.lexeme)
+            }
+        }
+    }
+}
@@ -1,99 +1,138 @@
-use std::collections::HashMap;
-
+use crate::lexer::error::{LexError, LexErrorKind};
 use crate::lexer::token::{LiteralValue, Token, TokenType};
 
-pub struct Lexer {
-    source: String,
-    tokens: Vec<Token>,
+/// Scans a borrowed source string. `start` and `current` are byte offsets into
+/// the source, and always sit on char boundaries.
+pub struct Lexer<'a> {
+    source: &'a str,
+    pending: Option<Token>,
+    finished: bool,
     start: usize,
     current: usize,
     line: usize,
-    keywords: HashMap<&'static str, TokenType>,
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    keep_comments: bool,
 }
 
-fn get_keywords() -> HashMap<&'static str, TokenType> {
-    HashMap::from([
-        ("and", TokenType::And),
-        ("class", TokenType::Class),
-        ("else", TokenType::Else),
-        ("False", TokenType::False),
-        ("for", TokenType::For),
-        ("fun", TokenType::Function),
-        ("if", TokenType::If),
-        ("Nil", TokenType::Nil),
-        ("or", TokenType::Or),
-        ("say", TokenType::Say),
-        ("return", TokenType::Return),
-        ("super", TokenType::Super),
-        ("this", TokenType::This),
-        ("True", TokenType::True),
-        ("var", TokenType::Variable),
-        ("while", TokenType::While),
-    ])
+fn keyword(text: &str) -> Option<TokenType> {
+    let token_type = match text {
+        "and" => TokenType::And,
+        "break" => TokenType::Break,
+        "class" => TokenType::Class,
+        "const" => TokenType::Const,
+        "continue" => TokenType::Continue,
+        "do" => TokenType::Do,
+        "elif" => TokenType::Elif,
+        "else" => TokenType::Else,
+        "False" => TokenType::False,
+        "for" => TokenType::For,
+        "fun" => TokenType::Function,
+        "if" => TokenType::If,
+        "match" => TokenType::Match,
+        "Nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "say" => TokenType::Say,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "this" => TokenType::This,
+        "True" => TokenType::True,
+        "var" => TokenType::Variable,
+        "while" => TokenType::While,
+        _ => return None,
+    };
+
+    Some(token_type)
 }
 
-impl Lexer {
-    pub fn new(source: &str) -> Self {
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        // Some editors prefix files with a UTF-8 byte order mark; skip one.
+        let bom = if source.starts_with('\u{FEFF}') {
+            '\u{FEFF}'.len_utf8()
+        } else {
+            0
+        };
+        // A `#!` interpreter line is only allowed as the very first line. Its
+        // newline is left in place so the following line still counts as 2.
+        let shebang = if source[bom..].starts_with("#!") {
+            source[bom..]
+                .find(['\n', '\r'])
+                .map_or(source.len(), |end| bom + end)
+        } else {
+            bom
+        };
+
         Self {
-            source: source.to_string(),
-            tokens: vec![],
-            start: 0,
-            current: 0,
+            source,
+            pending: None,
+            finished: false,
+            start: shebang,
+            current: shebang,
             line: 1,
-            keywords: get_keywords(),
+            column: source[bom..shebang].chars().count() + 1,
+            start_line: 1,
+            start_column: 1,
+            keep_comments: false,
         }
     }
 
-    pub fn scan_tokens(self: &mut Self) -> Result<Vec<Token>, String> {
-        let mut errors = vec![];
-        while !self.is_at_end() {
-            self.start = self.current;
-            match self.scan_token() {
-                Ok(_) => (),
-                Err(msg) => errors.push(msg),
-            }
+    /// Like `new`, but comments come through as `Comment` tokens instead of
+    /// being skipped, for tools like formatters that need to keep them. The
+    /// literal holds the comment text without its delimiters.
+    pub fn new_with_trivia(source: &'a str) -> Self {
+        Self {
+            keep_comments: true,
+            ..Self::new(source)
         }
+    }
 
-        self.tokens.push(Token {
-            token_type: TokenType::Eof,
-            lexeme: "".to_string(),
-            literal: None,
-            line_number: self.line,
-        });
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<LexError>> {
+        let (tokens, errors) = self.scan_tokens_lossy();
 
-        if errors.len() > 0 {
-            let mut joined_errors = "".to_string();
-            errors.iter().for_each(|msg| {
-                joined_errors.push_str(&msg);
-                joined_errors.push_str("\n");
-            });
-            return Err(joined_errors);
+        if !errors.is_empty() {
+            return Err(errors);
         }
 
-        Ok(self.tokens.clone())
+        Ok(tokens)
     }
 
-    fn is_at_end(self: &Self) -> bool {
+    /// Scans the whole source, keeping every token that could be produced
+    /// alongside the errors, for callers like editors that want a best-effort
+    /// token stream. The Eof token is always present.
+    pub fn scan_tokens_lossy(&mut self) -> (Vec<Token>, Vec<LexError>) {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        (tokens, errors)
+    }
+
+    fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    fn is_digit(self: &Self, ch: char) -> bool {
-        let u_char = ch as u8;
-        u_char >= '0' as u8 && u_char <= '9' as u8
+    fn is_digit(&self, ch: char) -> bool {
+        ch.is_ascii_digit()
     }
 
-    fn is_alphabetical(self: &Self, ch: char) -> bool {
-        let u_char = ch as u8;
-        (u_char >= 'a' as u8 && u_char <= 'z' as u8)
-            || (u_char >= 'A' as u8 && u_char <= 'Z' as u8)
-            || (u_char == '_' as u8)
+    /// Non-ASCII letters (`café`, `名前`) may start identifiers too, but only
+    /// ASCII digits count as digits.
+    fn is_alphabetical(&self, ch: char) -> bool {
+        ch.is_ascii_alphabetic() || ch == '_' || (!ch.is_ascii() && ch.is_alphabetic())
     }
 
-    fn is_alpha_numeric(self: &Self, ch: char) -> bool {
+    fn is_alpha_numeric(&self, ch: char) -> bool {
         self.is_alphabetical(ch) || self.is_digit(ch)
     }
 
-    fn scan_token(self: &mut Self) -> Result<(), String> {
+    fn scan_token(&mut self) -> Result<(), LexError> {
         let c = self.advance();
 
         match c {
@@ -101,12 +140,49 @@ impl Lexer {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
+            '-' => {
+                let token = if self.char_match('>') {
+                    TokenType::Arrow
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token(token);
+            }
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::SemiColon),
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                let token = if self.char_match('*') {
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                };
+                self.add_token(token);
+            }
+            '%' => self.add_token(TokenType::Percent),
+            '?' => {
+                let token = if self.char_match('?') {
+                    TokenType::QuestionQuestion
+                } else {
+                    TokenType::Question
+                };
+                self.add_token(token);
+            }
+            ':' => self.add_token(TokenType::Colon),
+            '&' => self.add_token(TokenType::Ampersand),
+            '|' => {
+                let token = if self.char_match('>') {
+                    TokenType::PipeGreater
+                } else {
+                    TokenType::Pipe
+                };
+                self.add_token(token);
+            }
+            '^' => self.add_token(TokenType::Caret),
+            '~' => self.add_token(TokenType::Tilde),
             '!' => {
                 let token = if self.char_match('=') {
                     // !=
@@ -119,6 +195,8 @@ impl Lexer {
             '=' => {
                 let token = if self.char_match('=') {
                     TokenType::EqualEqual
+                } else if self.char_match('>') {
+                    TokenType::FatArrow
                 } else {
                     TokenType::Equal
                 };
@@ -127,6 +205,8 @@ impl Lexer {
             '<' => {
                 let token = if self.char_match('=') {
                     TokenType::LessEqual
+                } else if self.char_match('<') {
+                    TokenType::LessLess
                 } else {
                     TokenType::Less
                 };
@@ -135,6 +215,8 @@ impl Lexer {
             '>' => {
                 let token = if self.char_match('=') {
                     TokenType::GreaterEqual
+                } else if self.char_match('>') {
+                    TokenType::GreaterGreater
                 } else {
                     TokenType::Greater
                 };
@@ -143,25 +225,32 @@ impl Lexer {
             '/' => {
                 if self.char_match('/') {
                     loop {
-                        if self.peek() == '\n' || self.is_at_end() {
+                        if self.peek() == '\n' || self.peek() == '\r' || self.is_at_end() {
                             break;
                         }
                         self.advance();
                     }
+                    self.add_comment(2, 0);
+                } else if self.char_match('*') {
+                    self.block_comment()?;
+                    self.add_comment(2, 2);
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             }
-            ' ' | '\r' | '\t' => {}
-            '\n' => self.line += 1,
-            '"' => self.string()?,
+            // Line counting for '\n' and '\r' happens in `advance`.
+            ' ' | '\r' | '\t' | '\n' => {}
+            '\u{FEFF}' => return Err(self.error(LexErrorKind::ByteOrderMark)),
+            '"' | '\'' => self.string(c)?,
             c => {
-                if self.is_digit(c) {
+                if c == 'r' && self.peek() == '"' {
+                    self.raw_string()?;
+                } else if self.is_digit(c) {
                     self.number()?;
                 } else if self.is_alphabetical(c) {
                     self.identifier();
                 } else {
-                    return Err(format!("Unrecognized char at line {}: {}", self.line, c));
+                    return Err(self.error(LexErrorKind::UnrecognizedCharacter));
                 }
             }
         }
@@ -169,160 +258,446 @@ impl Lexer {
         Ok(())
     }
 
-    fn peek(self: &Self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+    fn peek(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
-    fn peek_next(self: &Self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-
-        self.source.chars().nth(self.current + 1).unwrap()
+    fn peek_next(&self) -> char {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
-    fn identifier(self: &mut Self) {
+    fn identifier(&mut self) {
         while self.is_alpha_numeric(self.peek()) {
             self.advance();
         }
 
-        let substring = &self.source[self.start..self.current];
-        if let Some(&token_type) = self.keywords.get(substring) {
+        let substring = self.substring(self.start, self.current);
+        if let Some(token_type) = keyword(&substring) {
             self.add_token(token_type)
         } else {
-            self.add_token(TokenType::Identifier);
+            self.add_token_literal(
+                TokenType::Identifier,
+                Some(LiteralValue::IdentifierValue(substring)),
+            );
         }
     }
 
-    fn number(self: &mut Self) -> Result<(), String> {
-        while self.is_digit(self.peek()) {
+    fn number(&mut self) -> Result<(), LexError> {
+        if self.source[self.start..].starts_with('0') {
+            match self.peek() {
+                'b' => return self.radix_number(2, "binary"),
+                'o' => return self.radix_number(8, "octal"),
+                'x' => return self.radix_number(16, "hex"),
+                _ => (),
+            }
+        }
+
+        let mut is_float = false;
+        while self.is_digit(self.peek()) || self.peek() == '_' {
             self.advance();
         }
         // Look for a decimal point
         if self.peek() == '.' && self.is_digit(self.peek_next()) {
             self.advance(); // Consume the decimal point
+            is_float = true;
 
-            while self.is_digit(self.peek()) {
+            while self.is_digit(self.peek()) || self.peek() == '_' {
                 self.advance();
             }
         }
-        let substring = &self.source[self.start..self.current];
-        let value = substring.parse::<f64>();
-        match value {
+        let substring = self.substring(self.start, self.current);
+        self.check_separators(&substring, 10)?;
+        let digits = substring.replace('_', "");
+        let literal = if is_float {
+            match digits.parse::<f64>() {
+                Ok(value) => LiteralValue::FValue(value),
+                Err(_) => {
+                    return Err(self.error(LexErrorKind::InvalidNumber(
+                        "Could not parse number".to_string(),
+                    )))
+                }
+            }
+        } else {
+            match digits.parse::<i64>() {
+                Ok(value) => LiteralValue::IntValue(value),
+                Err(_) => {
+                    return Err(self.error(LexErrorKind::InvalidNumber(
+                        "Integer literal out of range".to_string(),
+                    )))
+                }
+            }
+        };
+        self.add_token_literal(TokenType::Number, Some(literal));
+
+        Ok(())
+    }
+
+    /// Scans a `0b`, `0o` or `0x` prefixed integer. Any identifier characters
+    /// directly following the prefix belong to the literal, so `0b102` is
+    /// reported as a bad binary literal rather than split into two tokens.
+    fn radix_number(&mut self, radix: u32, name: &str) -> Result<(), LexError> {
+        self.advance(); // Consume the radix prefix
+
+        while self.is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+
+        let substring = self.substring(self.start, self.current);
+        let digits = &substring[2..];
+        if digits.is_empty() {
+            return Err(self.error(LexErrorKind::InvalidNumber(format!(
+                "Missing digits in {} literal",
+                name
+            ))));
+        }
+        if let Some(bad) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            return Err(self.error(LexErrorKind::InvalidNumber(format!(
+                "Invalid digit '{}' in {} literal",
+                bad, name
+            ))));
+        }
+        self.check_separators(digits, radix)?;
+
+        match i64::from_str_radix(&digits.replace('_', ""), radix) {
             Ok(value) => {
-                self.add_token_literal(TokenType::Number, Some(LiteralValue::FValue(value)))
+                self.add_token_literal(TokenType::Number, Some(LiteralValue::IntValue(value)))
+            }
+            Err(_) => {
+                return Err(self.error(LexErrorKind::InvalidNumber(
+                    "Integer literal out of range".to_string(),
+                )))
             }
-            Err(_) => return Err(format!("Could not parse number: {}", substring)),
         }
 
         Ok(())
     }
 
-    fn string(self: &mut Self) -> Result<(), String> {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    /// Underscores may only appear between two digits, so `1_000` is fine but
+    /// `100_`, `1__0` and `1_.5` are rejected.
+    fn check_separators(&self, digits: &str, radix: u32) -> Result<(), LexError> {
+        let chars: Vec<char> = digits.chars().collect();
+        for (i, &c) in chars.iter().enumerate() {
+            if c != '_' {
+                continue;
+            }
+            let before = i > 0 && chars[i - 1].is_digit(radix);
+            let after = i + 1 < chars.len() && chars[i + 1].is_digit(radix);
+            if !before || !after {
+                return Err(self.error(LexErrorKind::InvalidNumber(
+                    "Misplaced '_' separator in number literal".to_string(),
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn block_comment(&mut self) -> Result<(), LexError> {
+        let start_line = self.line;
+        let mut depth = 1;
+
+        loop {
+            if self.is_at_end() {
+                return Err(LexError {
+                    kind: LexErrorKind::UnterminatedComment,
+                    line: start_line,
+                    column: self.start_column,
+                    lexeme: "/*".to_string(),
+                });
+            }
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.advance();
+                self.advance();
+                depth += 1;
+                continue;
+            }
+            if self.peek() == '*' && self.peek_next() == '/' {
+                self.advance();
+                self.advance();
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+                continue;
             }
             self.advance();
         }
+    }
+
+    /// Scans a string literal delimited by `quote`, which may be either `"`
+    /// or `'`. The other quote character needs no escaping inside it.
+    fn string(&mut self, quote: char) -> Result<(), LexError> {
+        let mut value = String::new();
+        let mut error = None;
+
+        while self.peek() != quote && !self.is_at_end() {
+            let offset = self.current;
+            let column = self.column;
+            let c = self.advance();
+            match c {
+                '\\' => match self.escape() {
+                    Ok(ch) => value.push(ch),
+                    Err(reason) => {
+                        error.get_or_insert(LexError {
+                            kind: LexErrorKind::InvalidEscape(reason),
+                            line: self.line,
+                            column,
+                            lexeme: self.substring(offset, self.current),
+                        });
+                    }
+                },
+                c => value.push(c),
+            }
+        }
         if self.is_at_end() {
-            return Err("Unterminated string.".to_string());
+            return Err(self.unterminated_string());
         }
 
         self.advance();
-        let value = &self.source[self.start + 1..self.current - 1];
+        if let Some(error) = error {
+            return Err(error);
+        }
         self.add_token_literal(
             TokenType::StringLiteral,
-            Some(LiteralValue::StringValue(value.to_string())),
+            Some(LiteralValue::StringValue(value)),
         );
 
         Ok(())
     }
 
-    fn char_match(self: &mut Self, c: char) -> bool {
+    /// Scans an `r"..."` literal, taking everything up to the closing quote
+    /// verbatim with no escape processing.
+    fn raw_string(&mut self) -> Result<(), LexError> {
+        self.advance(); // Consume the opening quote
+
+        while self.peek() != '"' && !self.is_at_end() {
+            self.advance();
+        }
         if self.is_at_end() {
-            return false;
+            return Err(self.unterminated_string());
         }
-        if self.source.chars().nth(self.current).unwrap() != c {
+
+        self.advance();
+        let value = self.substring(self.start + 2, self.current - 1);
+        self.add_token_literal(
+            TokenType::StringLiteral,
+            Some(LiteralValue::StringValue(value)),
+        );
+
+        Ok(())
+    }
+
+    /// Decodes the escape sequence following a backslash inside a string. On
+    /// failure the reason is returned and the caller builds the LexError.
+    fn escape(&mut self) -> Result<char, String> {
+        if self.is_at_end() {
+            // Reported as an unterminated string by the caller.
+            return Ok('\\');
+        }
+
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            'u' => self.unicode_escape(),
+            _ => Err("Invalid escape sequence".to_string()),
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        if !self.char_match('{') {
+            return Err("Expected '{' after '\\u' in unicode escape".to_string());
+        }
+
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+
+        if !self.char_match('}') {
+            return Err("Expected '}' to close unicode escape".to_string());
+        }
+        if digits.is_empty() || digits.len() > 6 {
+            return Err("Unicode escape must contain 1 to 6 hex digits".to_string());
+        }
+
+        let code = u32::from_str_radix(&digits, 16).unwrap();
+        char::from_u32(code).ok_or_else(|| "Invalid unicode code point".to_string())
+    }
+
+    fn char_match(&mut self, c: char) -> bool {
+        if self.is_at_end() {
             return false;
+        }
+        if self.peek() != c {
+            false
         } else {
-            self.current += 1;
-            return true;
+            self.current += c.len_utf8();
+            self.column += 1;
+            true
         }
     }
 
-    fn advance(self: &mut Self) -> char {
-        let c = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+    fn advance(&mut self) -> char {
+        let c = self.peek();
+        self.current += c.len_utf8();
+        // A '\r' on its own (old Mac line endings) ends a line too; in "\r\n"
+        // the '\n' does.
+        if c == '\n' || (c == '\r' && self.peek() != '\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
 
         c
     }
 
-    fn add_token(self: &mut Self, token_type: TokenType) {
+    fn substring(&self, start: usize, end: usize) -> String {
+        self.source[start..end].to_string()
+    }
+
+    fn error(&self, kind: LexErrorKind) -> LexError {
+        LexError {
+            kind,
+            line: self.line,
+            column: self.start_column,
+            lexeme: self.substring(self.start, self.current),
+        }
+    }
+
+    /// Points at the opening quote rather than at the end of input, where the
+    /// scan for the closing quote gave up.
+    fn unterminated_string(&self) -> LexError {
+        LexError {
+            kind: LexErrorKind::UnterminatedString,
+            line: self.start_line,
+            column: self.start_column,
+            lexeme: self.substring(self.start, self.current),
+        }
+    }
+
+    /// Emits the comment just scanned when trivia is kept. `open` and
+    /// `close` are the byte lengths of its delimiters.
+    fn add_comment(&mut self, open: usize, close: usize) {
+        if !self.keep_comments {
+            return;
+        }
+
+        let text = self.substring(self.start + open, self.current - close);
+        self.add_token_literal(TokenType::Comment, Some(LiteralValue::StringValue(text)));
+        if let Some(token) = self.pending.as_mut() {
+            // A block comment can span lines; report where it started.
+            token.line_number = self.start_line;
+        }
+    }
+
+    fn add_token(&mut self, token_type: TokenType) {
         self.add_token_literal(token_type, None);
     }
 
-    fn add_token_literal(self: &mut Self, token_type: TokenType, literal: Option<LiteralValue>) {
-        let text = self.source[self.start..self.current].to_string();
+    fn add_token_literal(&mut self, token_type: TokenType, literal: Option<LiteralValue>) {
+        let text = self.substring(self.start, self.current);
 
-        self.tokens.push(Token {
-            token_type: token_type,
+        self.pending = Some(Token {
+            token_type,
             lexeme: text,
-            literal: literal,
+            literal,
             line_number: self.line,
+            column: self.start_column,
+            span: (self.start, self.current),
         });
     }
 }
 
+/// Yields tokens lazily, one `scan_token` at a time, ending with the Eof
+/// token. Errors are yielded in-stream and scanning carries on after them.
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        while !self.is_at_end() {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
+            if let Err(error) = self.scan_token() {
+                return Some(Err(error));
+            }
+            if let Some(token) = self.pending.take() {
+                return Some(Ok(token));
+            }
+        }
+
+        self.finished = true;
+        Some(Ok(Token {
+            token_type: TokenType::Eof,
+            lexeme: "".to_string(),
+            literal: None,
+            line_number: self.line,
+            column: self.column,
+            span: (self.current, self.current),
+        }))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn handle_one_char_tokens() {
-        let source = "(( )) }{";
+        let source = "(( )) }{ ][";
         let mut lexer = Lexer::new(source);
-        let _ = lexer.scan_tokens();
+        let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(lexer.tokens.len(), 7);
-        assert_eq!(lexer.tokens[0].token_type, TokenType::LeftParen);
-        assert_eq!(lexer.tokens[1].token_type, TokenType::LeftParen);
-        assert_eq!(lexer.tokens[2].token_type, TokenType::RightParen);
-        assert_eq!(lexer.tokens[3].token_type, TokenType::RightParen);
-        assert_eq!(lexer.tokens[4].token_type, TokenType::RightBrace);
-        assert_eq!(lexer.tokens[5].token_type, TokenType::LeftBrace);
-        assert_eq!(lexer.tokens[6].token_type, TokenType::Eof);
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(tokens[0].token_type, TokenType::LeftParen);
+        assert_eq!(tokens[1].token_type, TokenType::LeftParen);
+        assert_eq!(tokens[2].token_type, TokenType::RightParen);
+        assert_eq!(tokens[3].token_type, TokenType::RightParen);
+        assert_eq!(tokens[4].token_type, TokenType::RightBrace);
+        assert_eq!(tokens[5].token_type, TokenType::LeftBrace);
+        assert_eq!(tokens[6].token_type, TokenType::RightBracket);
+        assert_eq!(tokens[7].token_type, TokenType::LeftBracket);
+        assert_eq!(tokens[8].token_type, TokenType::Eof);
     }
 
     #[test]
     fn handle_two_char_tokens() {
         let source = "! != == >= <=";
         let mut lexer = Lexer::new(source);
-        let _ = lexer.scan_tokens();
+        let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(lexer.tokens.len(), 6);
-        assert_eq!(lexer.tokens[0].token_type, TokenType::Bang);
-        assert_eq!(lexer.tokens[1].token_type, TokenType::BangEqual);
-        assert_eq!(lexer.tokens[2].token_type, TokenType::EqualEqual);
-        assert_eq!(lexer.tokens[3].token_type, TokenType::GreaterEqual);
-        assert_eq!(lexer.tokens[4].token_type, TokenType::LessEqual);
-        assert_eq!(lexer.tokens[5].token_type, TokenType::Eof);
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[0].token_type, TokenType::Bang);
+        assert_eq!(tokens[1].token_type, TokenType::BangEqual);
+        assert_eq!(tokens[2].token_type, TokenType::EqualEqual);
+        assert_eq!(tokens[3].token_type, TokenType::GreaterEqual);
+        assert_eq!(tokens[4].token_type, TokenType::LessEqual);
+        assert_eq!(tokens[5].token_type, TokenType::Eof);
     }
 
     #[test]
     fn handle_string_literal() {
         let source = r#""ABC""#;
         let mut lexer = Lexer::new(source);
-        let _ = lexer.scan_tokens().unwrap();
+        let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(lexer.tokens.len(), 2);
-        assert_eq!(lexer.tokens[0].token_type, TokenType::StringLiteral);
-        assert_eq!(lexer.tokens[1].token_type, TokenType::Eof);
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::StringLiteral);
+        assert_eq!(tokens[1].token_type, TokenType::Eof);
 
-        match lexer.tokens[0].literal.as_ref().unwrap() {
+        match tokens[0].literal.as_ref().unwrap() {
             LiteralValue::StringValue(val) => assert_eq!(val, "ABC"),
             _ => panic!("Incorrect literal type"),
         }
@@ -334,7 +709,7 @@ mod tests {
         let mut lexer = Lexer::new(source);
         let result = lexer.scan_tokens();
         match result {
-            Err(_) => (),
+            Err(errors) => assert_eq!(errors[0].kind, LexErrorKind::UnterminatedString),
             _ => panic!("Should have recognised unterminated string."),
         }
     }
@@ -343,11 +718,11 @@ mod tests {
     fn handle_multiline_string() {
         let source = "\"ABC\ndef\"";
         let mut lexer = Lexer::new(source);
-        let _ = lexer.scan_tokens().unwrap();
+        let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(lexer.tokens.len(), 2);
-        assert_eq!(lexer.tokens[0].token_type, TokenType::StringLiteral);
-        match lexer.tokens[0].literal.as_ref().unwrap() {
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::StringLiteral);
+        match tokens[0].literal.as_ref().unwrap() {
             LiteralValue::StringValue(val) => assert_eq!(*val, "ABC\ndef"),
             _ => panic!("Incorrect literal type"),
         }
@@ -357,23 +732,23 @@ mod tests {
     fn handle_number_literals() {
         let source = "123.123\n321.0\n5";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
-        assert_eq!(lexer.tokens.len(), 4);
+        let tokens = lexer.scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 4);
 
-        for i in 0..3 {
-            assert_eq!(lexer.tokens[i].token_type, TokenType::Number);
+        for token in &tokens[0..3] {
+            assert_eq!(token.token_type, TokenType::Number);
         }
 
-        match lexer.tokens[0].literal.clone().unwrap() {
+        match tokens[0].literal.clone().unwrap() {
             LiteralValue::FValue(val) => assert_eq!(val, 123.123),
             _ => panic!("Incorrect literal type"),
         }
-        match lexer.tokens[1].literal.clone().unwrap() {
+        match tokens[1].literal.clone().unwrap() {
             LiteralValue::FValue(val) => assert_eq!(val, 321.0),
             _ => panic!("Incorrect literal type"),
         }
-        match lexer.tokens[2].literal.clone().unwrap() {
-            LiteralValue::FValue(val) => assert_eq!(val, 5.0),
+        match tokens[2].literal.clone().unwrap() {
+            LiteralValue::IntValue(val) => assert_eq!(val, 5),
             _ => panic!("Incorrect literal type"),
         }
     }
@@ -382,39 +757,927 @@ mod tests {
     fn handle_identifier() {
         let source = "this_is_a_variable = 12;";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 5);
 
-        assert_eq!(lexer.tokens.len(), 5);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Equal);
+        assert_eq!(tokens[2].token_type, TokenType::Number);
+        assert_eq!(tokens[3].token_type, TokenType::SemiColon);
+        assert_eq!(tokens[4].token_type, TokenType::Eof);
 
-        assert_eq!(lexer.tokens[0].token_type, TokenType::Identifier);
-        assert_eq!(lexer.tokens[1].token_type, TokenType::Equal);
-        assert_eq!(lexer.tokens[2].token_type, TokenType::Number);
-        assert_eq!(lexer.tokens[3].token_type, TokenType::SemiColon);
-        assert_eq!(lexer.tokens[4].token_type, TokenType::Eof);
+        match tokens[0].literal.as_ref().unwrap() {
+            LiteralValue::IdentifierValue(val) => assert_eq!(val, "this_is_a_variable"),
+            _ => panic!("Incorrect literal type"),
+        }
     }
 
     #[test]
     fn handle_reserved_keywords() {
         let source = "var this_is_a_var = 12;\n while True { say 3};";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 13);
+
+        assert_eq!(tokens[0].token_type, TokenType::Variable);
+        assert!(tokens[0].literal.is_none());
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        match tokens[1].literal.as_ref().unwrap() {
+            LiteralValue::IdentifierValue(val) => assert_eq!(val, "this_is_a_var"),
+            _ => panic!("Incorrect literal type"),
+        }
+        assert_eq!(tokens[2].token_type, TokenType::Equal);
+        assert_eq!(tokens[3].token_type, TokenType::Number);
+        assert_eq!(tokens[4].token_type, TokenType::SemiColon);
+
+        assert_eq!(tokens[5].token_type, TokenType::While);
+        assert!(tokens[5].literal.is_none());
+        assert_eq!(tokens[6].token_type, TokenType::True);
+        assert!(tokens[6].literal.is_none());
+        assert_eq!(tokens[7].token_type, TokenType::LeftBrace);
+        assert_eq!(tokens[8].token_type, TokenType::Say);
+        assert_eq!(tokens[9].token_type, TokenType::Number);
+        assert_eq!(tokens[10].token_type, TokenType::RightBrace);
+        assert_eq!(tokens[11].token_type, TokenType::SemiColon);
+
+        assert_eq!(tokens[12].token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn handle_non_ascii_source() {
+        let source = "// 日本語のコメント\nvar café = \"naïve 🎉\";";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[0].token_type, TokenType::Variable);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme, "café");
+        assert_eq!(tokens[1].line_number, 2);
+        assert_eq!(tokens[3].token_type, TokenType::StringLiteral);
+        assert_eq!(tokens[3].lexeme, "\"naïve 🎉\"");
+
+        match tokens[3].literal.as_ref().unwrap() {
+            LiteralValue::StringValue(val) => assert_eq!(val, "naïve 🎉"),
+            _ => panic!("Incorrect literal type"),
+        }
+    }
+
+    #[test]
+    fn handle_non_ascii_unrecognized_char() {
+        let source = "1 € 2";
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens_lossy();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LexErrorKind::UnrecognizedCharacter);
+        assert_eq!(errors[0].lexeme, "€");
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[1].lexeme, "2");
+    }
+
+    #[test]
+    fn handle_non_ascii_chars_with_ascii_low_bytes() {
+        // Each of these has a codepoint whose low byte is an ASCII digit or
+        // letter: U+2030 ('0'), U+2041 ('A'), U+0661 ('a'), U+FF11 ('\x11').
+        for source in ["\u{2030}", "\u{2041}", "\u{0661}", "\u{FF11}"] {
+            let mut lexer = Lexer::new(source);
+            let errors = lexer.scan_tokens().unwrap_err();
+            assert_eq!(errors[0].kind, LexErrorKind::UnrecognizedCharacter);
+        }
+
+        // U+0131 (dotless i, low byte '1') is a letter, so it is an identifier.
+        let mut lexer = Lexer::new("\u{0131}x");
+        let tokens = lexer.scan_tokens().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[0].lexeme, "\u{0131}x");
+
+        // Non-ASCII digits don't continue an identifier or a number.
+        let mut lexer = Lexer::new("x\u{0661}");
+        let (tokens, errors) = lexer.scan_tokens_lossy();
+        assert_eq!(tokens[0].lexeme, "x");
+        assert_eq!(errors.len(), 1);
+
+        let mut lexer = Lexer::new("1\u{0660}");
+        let (tokens, errors) = lexer.scan_tokens_lossy();
+        assert_eq!(tokens[0].lexeme, "1");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn handle_large_source() {
+        let line = "var total = total + 123.45; // running sum\n";
+        let repetitions = 1024 * 1024 / line.len();
+        let source = line.repeat(repetitions);
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), repetitions * 7 + 1);
+        assert_eq!(tokens[tokens.len() - 1].token_type, TokenType::Eof);
+        assert_eq!(tokens[tokens.len() - 1].line_number, repetitions + 1);
+    }
+
+    #[test]
+    fn handle_columns_after_tabs() {
+        let source = "\tvar\tx = 1;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].column, 2);
+        assert_eq!(tokens[1].column, 6);
+        assert_eq!(tokens[2].column, 8);
+        assert_eq!(tokens[3].column, 10);
+        assert_eq!(tokens[4].column, 11);
+    }
+
+    #[test]
+    fn handle_columns_after_two_char_tokens() {
+        let source = "a >= b != c";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(lexer.tokens.len(), 13);
+        assert_eq!(tokens[1].column, 3);
+        assert_eq!(tokens[2].column, 6);
+        assert_eq!(tokens[3].column, 8);
+        assert_eq!(tokens[4].column, 11);
+    }
+
+    #[test]
+    fn handle_columns_after_multiline_string() {
+        let source = "x = \"ab\ncd\"; y\n  z";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(lexer.tokens[0].token_type, TokenType::Variable);
-        assert_eq!(lexer.tokens[1].token_type, TokenType::Identifier);
-        assert_eq!(lexer.tokens[2].token_type, TokenType::Equal);
-        assert_eq!(lexer.tokens[3].token_type, TokenType::Number);
-        assert_eq!(lexer.tokens[4].token_type, TokenType::SemiColon);
+        assert_eq!(tokens[2].token_type, TokenType::StringLiteral);
+        assert_eq!(tokens[2].column, 5);
+        assert_eq!(tokens[3].line_number, 2);
+        assert_eq!(tokens[3].column, 4);
+        assert_eq!(tokens[4].line_number, 2);
+        assert_eq!(tokens[4].column, 6);
+        assert_eq!(tokens[5].line_number, 3);
+        assert_eq!(tokens[5].column, 3);
+    }
 
-        assert_eq!(lexer.tokens[5].token_type, TokenType::While);
-        assert_eq!(lexer.tokens[6].token_type, TokenType::True);
-        assert_eq!(lexer.tokens[7].token_type, TokenType::LeftBrace);
-        assert_eq!(lexer.tokens[8].token_type, TokenType::Say);
-        assert_eq!(lexer.tokens[9].token_type, TokenType::Number);
-        assert_eq!(lexer.tokens[10].token_type, TokenType::RightBrace);
-        assert_eq!(lexer.tokens[11].token_type, TokenType::SemiColon);
+    #[test]
+    fn handle_error_columns() {
+        let source = "1 +\n  @";
+        let mut lexer = Lexer::new(source);
+        let result = lexer.scan_tokens();
+
+        match result {
+            Err(errors) => {
+                assert_eq!(errors[0].line, 2);
+                assert_eq!(errors[0].column, 3);
+                assert!(errors[0].to_string().contains("line 2, column 3"));
+            }
+            _ => panic!("Should have rejected '@'."),
+        }
+    }
+
+    #[test]
+    fn handle_spans_reconstruct_source() {
+        let source = "var naïve = (1 + 2.5) >= x;\n\tsay \"🎉\";  ";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let mut reconstructed = String::new();
+        let mut previous_end = 0;
+        for token in &tokens {
+            let (start, end) = token.span;
+            let skipped = &source[previous_end..start];
+            assert!(skipped.chars().all(char::is_whitespace));
+            assert_eq!(&source[start..end], token.lexeme);
+
+            reconstructed.push_str(skipped);
+            reconstructed.push_str(&source[start..end]);
+            previous_end = end;
+        }
+
+        assert_eq!(reconstructed, source);
+        assert_eq!(tokens[tokens.len() - 1].span, (source.len(), source.len()));
+    }
+
+    #[test]
+    fn handle_multiline_string_span() {
+        let source = "x = \"ab\ncd\";";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[2].span, (4, 11));
+        assert_eq!(&source[4..11], "\"ab\ncd\"");
+        assert_eq!(tokens[3].span, (11, 12));
+    }
+
+    #[test]
+    fn handle_multiline_block_comment() {
+        let source = "1 /* first\nsecond\nthird */ + 2";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token_type, TokenType::Number);
+        assert_eq!(tokens[1].token_type, TokenType::Plus);
+        assert_eq!(tokens[1].line_number, 3);
+        assert_eq!(tokens[2].token_type, TokenType::Number);
+    }
+
+    #[test]
+    fn handle_block_comment_containing_line_comment() {
+        let source = "/* a // b */ say";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::Say);
+    }
+
+    #[test]
+    fn handle_comments_as_trivia() {
+        let source = "var a = 1; // one\n/* two\nlines */ say a;";
+        let default_types: Vec<TokenType> = Lexer::new(source)
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| token.token_type)
+            .collect();
+        let trivia = Lexer::new_with_trivia(source).scan_tokens().unwrap();
+
+        let comments: Vec<&Token> = trivia
+            .iter()
+            .filter(|token| token.token_type == TokenType::Comment)
+            .collect();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].lexeme, "// one");
+        assert_eq!(comments[0].line_number, 1);
+        assert_eq!(comments[1].lexeme, "/* two\nlines */");
+        assert_eq!(comments[1].line_number, 2);
+        match &comments[1].literal {
+            Some(LiteralValue::StringValue(text)) => assert_eq!(text, " two\nlines "),
+            _ => panic!("Comment should carry its text"),
+        }
+
+        let without_comments: Vec<TokenType> = trivia
+            .iter()
+            .map(|token| token.token_type)
+            .filter(|token_type| *token_type != TokenType::Comment)
+            .collect();
+        assert_eq!(without_comments, default_types);
+    }
+
+    #[test]
+    fn handle_unterminated_block_comment() {
+        let source = "1;\n/* never\nclosed";
+        let mut lexer = Lexer::new(source);
+        let result = lexer.scan_tokens();
+
+        match result {
+            Err(errors) => {
+                assert_eq!(errors[0].kind, LexErrorKind::UnterminatedComment);
+                assert!(errors[0].to_string().contains("starting at line 2"));
+            }
+            _ => panic!("Should have recognised unterminated block comment."),
+        }
+    }
+
+    #[test]
+    fn handle_nested_block_comments() {
+        let source = "/* outer /* inner */ still outer */ 1 /* a /* b /* c */ */ */ 2";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].lexeme, "1");
+        assert_eq!(tokens[1].lexeme, "2");
+    }
+
+    #[test]
+    fn handle_unbalanced_nested_block_comment() {
+        let source = "/* outer\n/* inner */\nstill outer";
+        let mut lexer = Lexer::new(source);
+        let result = lexer.scan_tokens();
+
+        match result {
+            Err(errors) => {
+                assert_eq!(errors[0].kind, LexErrorKind::UnterminatedComment);
+                assert_eq!(errors[0].line, 1);
+            }
+            _ => panic!("Should have recognised unterminated block comment."),
+        }
+    }
+
+    fn string_value(source: &str) -> Result<String, LexError> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().map_err(|errors| errors[0].clone())?;
+
+        match tokens[0].literal.clone() {
+            Some(LiteralValue::StringValue(val)) => Ok(val),
+            _ => panic!("Incorrect literal type"),
+        }
+    }
+
+    #[test]
+    fn handle_basic_escapes() {
+        let value = string_value(r#""a\tb\n\"c\" \\""#).unwrap();
+        assert_eq!(value, "a\tb\n\"c\" \\");
+    }
+
+    #[test]
+    fn handle_unicode_escapes() {
+        assert_eq!(string_value(r#""\u{e9}t\u{00E9}""#).unwrap(), "été");
+        assert_eq!(string_value(r#""\u{1F600}""#).unwrap(), "😀");
+    }
+
+    #[test]
+    fn handle_malformed_unicode_escapes() {
+        let empty = string_value("1;\n\"\\u{}\"").unwrap_err();
+        assert_eq!(empty.line, 2);
+        assert_eq!(empty.lexeme, r"\u{}");
+        assert!(empty.to_string().contains("1 to 6 hex digits at line 2"));
+
+        let missing_brace = string_value(r#""\u1F600""#).unwrap_err();
+        assert!(matches!(missing_brace.kind, LexErrorKind::InvalidEscape(_)));
+        assert!(missing_brace.to_string().contains("Expected '{'"));
+
+        let surrogate = string_value(r#""\u{D800}""#).unwrap_err();
+        assert_eq!(surrogate.column, 2);
+        assert!(surrogate
+            .to_string()
+            .contains("Invalid unicode code point at line 1"));
+    }
+
+    #[test]
+    fn handle_raw_string() {
+        let source = r#"r"C:\path\to\n" radius r"#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token_type, TokenType::StringLiteral);
+        assert_eq!(tokens[0].lexeme, r#"r"C:\path\to\n""#);
+        match tokens[0].literal.as_ref().unwrap() {
+            LiteralValue::StringValue(val) => assert_eq!(val, r"C:\path\to\n"),
+            _ => panic!("Incorrect literal type"),
+        }
+
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme, "radius");
+        assert_eq!(tokens[2].token_type, TokenType::Identifier);
+        assert_eq!(tokens[2].lexeme, "r");
+    }
+
+    #[test]
+    fn handle_unterminated_raw_string() {
+        let source = r#"r"\d+"#;
+        let mut lexer = Lexer::new(source);
+        let result = lexer.scan_tokens();
+
+        match result {
+            Err(errors) => assert_eq!(errors[0].kind, LexErrorKind::UnterminatedString),
+            _ => panic!("Should have recognised unterminated raw string."),
+        }
+    }
+
+    #[test]
+    fn handle_integer_and_float_literals() {
+        let source = "5 5.0 9223372036854775807";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        match tokens[0].literal.clone().unwrap() {
+            LiteralValue::IntValue(val) => assert_eq!(val, 5),
+            _ => panic!("Incorrect literal type"),
+        }
+        match tokens[1].literal.clone().unwrap() {
+            LiteralValue::FValue(val) => assert_eq!(val, 5.0),
+            _ => panic!("Incorrect literal type"),
+        }
+        match tokens[2].literal.clone().unwrap() {
+            LiteralValue::IntValue(val) => assert_eq!(val, i64::MAX),
+            _ => panic!("Incorrect literal type"),
+        }
+    }
+
+    #[test]
+    fn handle_integer_overflow() {
+        let source = "1;\n9223372036854775808";
+        let mut lexer = Lexer::new(source);
+        let result = lexer.scan_tokens();
+
+        match result {
+            Err(errors) => {
+                assert!(matches!(errors[0].kind, LexErrorKind::InvalidNumber(_)));
+                assert_eq!(errors[0].line, 2);
+                assert_eq!(errors[0].lexeme, "9223372036854775808");
+            }
+            _ => panic!("Should have rejected out of range integer."),
+        }
+    }
+
+    #[test]
+    fn handle_radix_literals() {
+        let source = "0b1010 0o755 0xFF 0";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 5);
+        let expected = [10, 493, 255, 0];
+        for (token, value) in tokens.iter().zip(expected) {
+            assert_eq!(token.token_type, TokenType::Number);
+            match token.literal.clone().unwrap() {
+                LiteralValue::IntValue(val) => assert_eq!(val, value),
+                _ => panic!("Incorrect literal type"),
+            }
+        }
+    }
+
+    #[test]
+    fn handle_invalid_radix_digits() {
+        let mut lexer = Lexer::new("0b102");
+        match lexer.scan_tokens() {
+            Err(errors) => assert_eq!(
+                errors[0].to_string(),
+                "Invalid digit '2' in binary literal at line 1: 0b102"
+            ),
+            _ => panic!("Should have rejected binary literal."),
+        }
+
+        let mut lexer = Lexer::new("\n0o9");
+        match lexer.scan_tokens() {
+            Err(errors) => assert_eq!(
+                errors[0].to_string(),
+                "Invalid digit '9' in octal literal at line 2: 0o9"
+            ),
+            _ => panic!("Should have rejected octal literal."),
+        }
+    }
+
+    #[test]
+    fn handle_radix_literal_followed_by_identifier() {
+        let mut lexer = Lexer::new("0b101abc");
+        let (tokens, errors) = lexer.scan_tokens_lossy();
+        assert_eq!(
+            errors[0].kind,
+            LexErrorKind::InvalidNumber("Invalid digit 'a' in binary literal".to_string())
+        );
+        assert_eq!(tokens.len(), 1);
+
+        let mut lexer = Lexer::new("0b101 abc");
+        let tokens = lexer.scan_tokens().unwrap();
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn handle_digit_separators() {
+        let source = "1_000_000 1.234_567 0b1010_1010 0xFF_FF";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0].lexeme, "1_000_000");
+        match tokens[0].literal.clone().unwrap() {
+            LiteralValue::IntValue(val) => assert_eq!(val, 1_000_000),
+            _ => panic!("Incorrect literal type"),
+        }
+        match tokens[1].literal.clone().unwrap() {
+            LiteralValue::FValue(val) => assert_eq!(val, 1.234_567),
+            _ => panic!("Incorrect literal type"),
+        }
+        match tokens[2].literal.clone().unwrap() {
+            LiteralValue::IntValue(val) => assert_eq!(val, 0b1010_1010),
+            _ => panic!("Incorrect literal type"),
+        }
+        match tokens[3].literal.clone().unwrap() {
+            LiteralValue::IntValue(val) => assert_eq!(val, 0xFF_FF),
+            _ => panic!("Incorrect literal type"),
+        }
+    }
+
+    #[test]
+    fn handle_leading_underscore_is_identifier() {
+        let mut lexer = Lexer::new("_100");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[0].lexeme, "_100");
+    }
+
+    #[test]
+    fn handle_misplaced_separators() {
+        for source in ["100_", "1__0", "1_.5", "0b_101"] {
+            let mut lexer = Lexer::new(source);
+            match lexer.scan_tokens() {
+                Err(errors) => assert_eq!(
+                    errors[0].to_string(),
+                    format!(
+                        "Misplaced '_' separator in number literal at line 1: {}",
+                        source
+                    )
+                ),
+                _ => panic!("Should have rejected {}", source),
+            }
+        }
+    }
+
+    #[test]
+    fn handle_percent() {
+        let mut lexer = Lexer::new("x % 2");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[1].token_type, TokenType::Percent);
+        assert_eq!(tokens[1].lexeme, "%");
+    }
+
+    #[test]
+    fn handle_conditional_tokens() {
+        let mut lexer = Lexer::new("a ? b : c");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[1].token_type, TokenType::Question);
+        assert_eq!(tokens[3].token_type, TokenType::Colon);
+    }
+
+    #[test]
+    fn handle_bitwise_tokens() {
+        let source = "& | ^ ~ << >> <<= < <= > >=";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let expected = [
+            TokenType::Ampersand,
+            TokenType::Pipe,
+            TokenType::Caret,
+            TokenType::Tilde,
+            TokenType::LessLess,
+            TokenType::GreaterGreater,
+            TokenType::LessLess,
+            TokenType::Equal,
+            TokenType::Less,
+            TokenType::LessEqual,
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (token, token_type) in tokens.iter().zip(expected) {
+            assert_eq!(token.token_type, token_type);
+        }
+    }
+
+    #[test]
+    fn handle_pipe_greater() {
+        let mut lexer = Lexer::new("x |> f | > g");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::PipeGreater);
+        assert_eq!(tokens[1].lexeme, "|>");
+        assert_eq!(tokens[3].token_type, TokenType::Pipe);
+        assert_eq!(tokens[4].token_type, TokenType::Greater);
+    }
+
+    #[test]
+    fn handle_fat_arrow() {
+        let mut lexer = Lexer::new("1 => a == b = > c");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::FatArrow);
+        assert_eq!(tokens[1].lexeme, "=>");
+        assert_eq!(tokens[3].token_type, TokenType::EqualEqual);
+        assert_eq!(tokens[5].token_type, TokenType::Equal);
+        assert_eq!(tokens[6].token_type, TokenType::Greater);
+    }
+
+    #[test]
+    fn handle_star_star() {
+        let mut lexer = Lexer::new("2 ** 3 * 4 ***");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::StarStar);
+        assert_eq!(tokens[3].token_type, TokenType::Star);
+        assert_eq!(tokens[5].token_type, TokenType::StarStar);
+        assert_eq!(tokens[6].token_type, TokenType::Star);
+    }
+
+    #[test]
+    fn handle_arrow() {
+        let mut lexer = Lexer::new("(a) -> a - 1 --> - >");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[3].token_type, TokenType::Arrow);
+        assert_eq!(tokens[3].lexeme, "->");
+        assert_eq!(tokens[5].token_type, TokenType::Minus);
+        assert_eq!(tokens[7].token_type, TokenType::Minus);
+        assert_eq!(tokens[8].token_type, TokenType::Arrow);
+        assert_eq!(tokens[9].token_type, TokenType::Minus);
+        assert_eq!(tokens[10].token_type, TokenType::Greater);
+    }
+
+    #[test]
+    fn handle_question_question() {
+        let mut lexer = Lexer::new("a ?? b ? c : d ? ?");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::QuestionQuestion);
+        assert_eq!(tokens[1].lexeme, "??");
+        assert_eq!(tokens[3].token_type, TokenType::Question);
+        assert_eq!(tokens[7].token_type, TokenType::Question);
+        assert_eq!(tokens[8].token_type, TokenType::Question);
+    }
+
+    #[test]
+    fn handle_loop_control_keywords() {
+        let mut lexer = Lexer::new("break; continue; breaking");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Break);
+        assert_eq!(tokens[2].token_type, TokenType::Continue);
+        assert_eq!(tokens[4].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn handle_elif_keyword() {
+        let mut lexer = Lexer::new("if elif else elifant");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::If);
+        assert_eq!(tokens[1].token_type, TokenType::Elif);
+        assert_eq!(tokens[2].token_type, TokenType::Else);
+        assert_eq!(tokens[3].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn handle_const_keyword() {
+        let mut lexer = Lexer::new("const LIMIT = 10;");
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Const);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn handle_single_quoted_string_literal() {
+        let source = "'ABC'";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::StringLiteral);
+        assert_eq!(tokens[1].token_type, TokenType::Eof);
+
+        match tokens[0].literal.as_ref().unwrap() {
+            LiteralValue::StringValue(val) => assert_eq!(val, "ABC"),
+            _ => panic!("Incorrect literal type"),
+        }
+    }
+
+    #[test]
+    fn handle_unterminated_single_quoted_string() {
+        let source = "'ABC\"";
+        let mut lexer = Lexer::new(source);
+        let result = lexer.scan_tokens();
+        match result {
+            Err(errors) => assert_eq!(errors[0].kind, LexErrorKind::UnterminatedString),
+            _ => panic!("Should have recognised unterminated string."),
+        }
+    }
+
+    #[test]
+    fn handle_multiline_single_quoted_string() {
+        let source = "'ABC\ndef'";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::StringLiteral);
+        match tokens[0].literal.as_ref().unwrap() {
+            LiteralValue::StringValue(val) => assert_eq!(*val, "ABC\ndef"),
+            _ => panic!("Incorrect literal type"),
+        }
+    }
+
+    #[test]
+    fn handle_mixed_quotes() {
+        assert_eq!(string_value(r#"'say "hi"'"#).unwrap(), "say \"hi\"");
+        assert_eq!(string_value(r#""it's""#).unwrap(), "it's");
+        assert_eq!(string_value(r"'it\'s'").unwrap(), "it's");
+    }
+
+    #[test]
+    fn handle_lazy_iteration() {
+        let source = "var x = 1;\n".repeat(10_000);
+        let mut lexer = Lexer::new(&source);
+        let tokens: Vec<Token> = lexer.by_ref().take(3).map(Result::unwrap).collect();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[2].token_type, TokenType::Equal);
+        assert_eq!(lexer.current, 7);
+    }
+
+    #[test]
+    fn handle_errors_in_stream() {
+        let lexer = Lexer::new("1 @ 2");
+        let results: Vec<Result<Token, LexError>> = lexer.collect();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().lexeme, "1");
+        assert_eq!(results[1].as_ref().unwrap_err().lexeme, "@");
+        assert_eq!(results[2].as_ref().unwrap().lexeme, "2");
+        assert_eq!(results[3].as_ref().unwrap().token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn handle_lossy_scan() {
+        let source = "var x = 1 @ + $ 2;";
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens_lossy();
+
+        let expected = [
+            TokenType::Variable,
+            TokenType::Identifier,
+            TokenType::Equal,
+            TokenType::Number,
+            TokenType::Plus,
+            TokenType::Number,
+            TokenType::SemiColon,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (token, token_type) in tokens.iter().zip(expected) {
+            assert_eq!(token.token_type, token_type);
+        }
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].lexeme, "@");
+        assert_eq!(errors[1].lexeme, "$");
+    }
+
+    #[test]
+    fn handle_shebang() {
+        let source = "#!/usr/bin/env oli\nvar x = 1;\nsay x;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(tokens[0].token_type, TokenType::Variable);
+        assert_eq!(tokens[0].line_number, 2);
+        assert_eq!(tokens[0].span, (19, 22));
+        assert_eq!(tokens[5].token_type, TokenType::Say);
+        assert_eq!(tokens[5].line_number, 3);
+    }
+
+    #[test]
+    fn handle_hash_outside_shebang() {
+        let mut lexer = Lexer::new(" #!/usr/bin/env oli");
+        assert!(lexer.scan_tokens().is_err());
+
+        let mut lexer = Lexer::new("#!/usr/bin/env oli\n#!again");
+        match lexer.scan_tokens() {
+            Err(errors) => {
+                assert_eq!(errors[0].kind, LexErrorKind::UnrecognizedCharacter);
+                assert_eq!(errors[0].lexeme, "#");
+                assert_eq!(errors[0].line, 2);
+            }
+            _ => panic!("Should have rejected '#'."),
+        }
+    }
+
+    #[test]
+    fn handle_leading_byte_order_mark() {
+        let plain = "var x = 1;";
+        let with_bom = "\u{FEFF}var x = 1;";
+
+        let plain_tokens = Lexer::new(plain).scan_tokens().unwrap();
+        let bom_tokens = Lexer::new(with_bom).scan_tokens().unwrap();
+
+        assert_eq!(plain_tokens.len(), bom_tokens.len());
+        for (plain, bom) in plain_tokens.iter().zip(&bom_tokens) {
+            assert_eq!(plain.token_type, bom.token_type);
+            assert_eq!(plain.lexeme, bom.lexeme);
+            assert_eq!(plain.line_number, bom.line_number);
+            assert_eq!(plain.column, bom.column);
+        }
+        assert_eq!(bom_tokens[0].span, (3, 6));
+    }
+
+    #[test]
+    fn handle_byte_order_mark_mid_file() {
+        let mut lexer = Lexer::new("var x = 1;\n\u{FEFF}");
+        match lexer.scan_tokens() {
+            Err(errors) => {
+                assert_eq!(errors[0].kind, LexErrorKind::ByteOrderMark);
+                assert_eq!(errors[0].line, 2);
+                assert!(errors[0].to_string().contains("byte order mark"));
+            }
+            _ => panic!("Should have rejected a mid-file byte order mark."),
+        }
+    }
+
+    #[test]
+    fn handle_unterminated_string_location() {
+        let source = "var a = 1;\nvar b = 2;\nvar name = \"unclosed\nstill going\n";
+        let mut lexer = Lexer::new(source);
+        match lexer.scan_tokens() {
+            Err(errors) => {
+                assert_eq!(errors[0].kind, LexErrorKind::UnterminatedString);
+                assert_eq!(
+                    errors[0].to_string(),
+                    "Unterminated string starting at line 3, column 12."
+                );
+            }
+            _ => panic!("Should have recognised unterminated string."),
+        }
+    }
+
+    #[test]
+    fn handle_terminated_multiline_string_location() {
+        let source = "\"a\nb\nc\" 1";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].column, 1);
+        assert_eq!(tokens[1].line_number, 3);
+        assert_eq!(tokens[1].column, 4);
+    }
+
+    #[test]
+    fn handle_every_keyword() {
+        let source = "and break class const continue do elif else False for fun if match Nil \
+                      or say return super this True var while";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let expected = [
+            TokenType::And,
+            TokenType::Break,
+            TokenType::Class,
+            TokenType::Const,
+            TokenType::Continue,
+            TokenType::Do,
+            TokenType::Elif,
+            TokenType::Else,
+            TokenType::False,
+            TokenType::For,
+            TokenType::Function,
+            TokenType::If,
+            TokenType::Match,
+            TokenType::Nil,
+            TokenType::Or,
+            TokenType::Say,
+            TokenType::Return,
+            TokenType::Super,
+            TokenType::This,
+            TokenType::True,
+            TokenType::Variable,
+            TokenType::While,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (token, token_type) in tokens.iter().zip(expected) {
+            assert_eq!(token.token_type, token_type);
+        }
+
+        let mut lexer = Lexer::new("true false nil And");
+        let tokens = lexer.scan_tokens().unwrap();
+        for token in &tokens[0..4] {
+            assert_eq!(token.token_type, TokenType::Identifier);
+        }
+    }
+
+    #[test]
+    fn handle_line_endings() {
+        let program = [
+            "var a = 1; // first",
+            "/* block",
+            "comment */ say \"two",
+            "lines\";",
+            "say a;",
+        ];
+        let lines = |ending: &str| -> Vec<(TokenType, usize)> {
+            let source = program.join(ending);
+            Lexer::new(&source)
+                .scan_tokens()
+                .unwrap()
+                .iter()
+                .map(|token| (token.token_type, token.line_number))
+                .collect()
+        };
+
+        let unix = lines("\n");
+        assert_eq!(unix.last(), Some(&(TokenType::Eof, 5)));
+        assert_eq!(lines("\r\n"), unix);
+        assert_eq!(lines("\r"), unix);
+    }
+
+    #[test]
+    fn handle_bare_carriage_return_error_line() {
+        let mut lexer = Lexer::new("1;\r2;\r@");
+        let errors = lexer.scan_tokens().unwrap_err();
 
-        assert_eq!(lexer.tokens[12].token_type, TokenType::Eof);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(errors[0].column, 1);
     }
 }
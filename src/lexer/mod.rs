@@ -1,2 +1,4 @@
+pub mod error;
+#[allow(clippy::module_inception)]
 pub mod lexer;
 pub mod token;
@@ -5,6 +5,8 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -12,30 +14,53 @@ pub enum TokenType {
     SemiColon,
     Slash,
     Star,
+    Percent,
+    Question,
+    Colon,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or Two Character Tokens
+    Arrow,
+    FatArrow,
+    StarStar,
+    QuestionQuestion,
+    PipeGreater,
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
     Greater,
     GreaterEqual,
+    GreaterGreater,
     Less,
     LessEqual,
+    LessLess,
 
     // Literals
     Identifier,
     StringLiteral,
     Number,
 
+    // Trivia, only produced by `Lexer::new_with_trivia`
+    Comment,
+
     // Keywords
     And,
+    Break,
     Class,
+    Const,
+    Continue,
+    Do,
+    Elif,
     Else,
     False,
     Function,
     For,
     If,
+    Match,
     Nil,
     Or,
     Return,
@@ -57,7 +82,8 @@ impl std::fmt::Display for TokenType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
     IntValue(i64),
     FValue(f64),
@@ -71,6 +97,9 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<LiteralValue>,
     pub line_number: usize,
+    pub column: usize,
+    /// Byte offsets of the lexeme in the original source, end exclusive.
+    pub span: (usize, usize),
 }
 
 impl Token {
@@ -79,16 +108,200 @@ impl Token {
         lexeme: String,
         literal: Option<LiteralValue>,
         line_number: usize,
+        column: usize,
+        span: (usize, usize),
     ) -> Self {
         Self {
             token_type,
             lexeme,
             literal,
             line_number,
+            column,
+            span,
+        }
+    }
+}
+
+/// Tokens are equal if they read the same, wherever they are in the source,
+/// so a tree built by hand can be compared with a parsed one.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type
+            && self.lexeme == other.lexeme
+            && self.literal == other.literal
+    }
+}
+
+/// Formats as e.g. `IDENTIFIER 'count' [line 3]`.
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut name = String::new();
+        for (i, ch) in format!("{:?}", self.token_type).chars().enumerate() {
+            if ch.is_uppercase() && i > 0 {
+                name.push('_');
+            }
+            name.push(ch.to_ascii_uppercase());
+        }
+
+        write!(f, "{} '{}' [line {}]", name, self.lexeme, self.line_number)
+    }
+}
+
+impl LiteralValue {
+    fn to_json(&self) -> String {
+        match self {
+            LiteralValue::IntValue(value) => value.to_string(),
+            LiteralValue::FValue(value) => value.to_string(),
+            LiteralValue::StringValue(value) | LiteralValue::IdentifierValue(value) => {
+                json_string(value)
+            }
+        }
+    }
+}
+
+impl Token {
+    /// Renders the token as a JSON object with `type`, `lexeme`, `literal`,
+    /// `line` and `span` keys.
+    pub fn to_json(&self) -> String {
+        let literal = match &self.literal {
+            Some(literal) => literal.to_json(),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"type\":{},\"lexeme\":{},\"literal\":{},\"line\":{},\"span\":[{},{}]}}",
+            json_string(&self.token_type.to_string()),
+            json_string(&self.lexeme),
+            literal,
+            self.line_number,
+            self.span.0,
+            self.span.1
+        )
+    }
+}
+
+/// Renders a token stream as a JSON array, one token object per element.
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    let objects: Vec<String> = tokens.iter().map(|token| token.to_json()).collect();
+    format!("[{}]", objects.join(","))
+}
+
+/// Writes a string as a double-quoted Oli literal that lexes back to it.
+pub(crate) fn string_lexeme(value: &str) -> String {
+    let mut escaped = String::from('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            ch => escaped.push(ch),
         }
     }
+    escaped.push('"');
+    escaped
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::from('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let token = Token::new(
+            TokenType::Identifier,
+            "count".to_string(),
+            Some(LiteralValue::IdentifierValue("count".to_string())),
+            3,
+            5,
+            (20, 25),
+        );
+        assert_eq!(token.to_string(), "IDENTIFIER 'count' [line 3]");
+
+        let token = Token::new(
+            TokenType::GreaterEqual,
+            ">=".to_string(),
+            None,
+            1,
+            1,
+            (0, 2),
+        );
+        assert_eq!(token.to_string(), "GREATER_EQUAL '>=' [line 1]");
+    }
+
+    #[test]
+    fn test_equality_ignores_position() {
+        let token = |line_number, column, span| {
+            Token::new(
+                TokenType::Number,
+                "1".to_string(),
+                Some(LiteralValue::IntValue(1)),
+                line_number,
+                column,
+                span,
+            )
+        };
+
+        assert_eq!(token(1, 1, (0, 1)), token(7, 3, (40, 41)));
+
+        let mut other = token(1, 1, (0, 1));
+        other.literal = Some(LiteralValue::FValue(1.0));
+        assert_ne!(token(1, 1, (0, 1)), other);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let token = Token::new(
+            TokenType::StringLiteral,
+            "\"a\\\"b\"".to_string(),
+            Some(LiteralValue::StringValue("a\"b\\c\nd".to_string())),
+            2,
+            7,
+            (10, 16),
+        );
+        assert_eq!(
+            token.to_json(),
+            r#"{"type":"StringLiteral","lexeme":"\"a\\\"b\"","literal":"a\"b\\c\nd","line":2,"span":[10,16]}"#
+        );
+    }
 
-    pub fn to_string(self: &Self) -> String {
-        format!("{} {} {:?}", self.token_type, self.lexeme, self.literal)
+    #[test]
+    fn test_tokens_to_json() {
+        let tokens = vec![
+            Token::new(
+                TokenType::Number,
+                "12".to_string(),
+                Some(LiteralValue::IntValue(12)),
+                1,
+                1,
+                (0, 2),
+            ),
+            Token::new(TokenType::Eof, "".to_string(), None, 1, 3, (2, 2)),
+        ];
+        assert_eq!(
+            tokens_to_json(&tokens),
+            r#"[{"type":"Number","lexeme":"12","literal":12,"line":1,"span":[0,2]},{"type":"Eof","lexeme":"","literal":null,"line":1,"span":[2,2]}]"#
+        );
+        assert_eq!(tokens_to_json(&[]), "[]");
     }
 }
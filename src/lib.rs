@@ -0,0 +1,196 @@
+//! Oli's Legendary Interpreter, as a library: lex, parse and run Oli source
+//! from another program. The `oli` binary is a command line over this.
+
+pub mod ast;
+pub mod interpreter;
+pub mod lexer;
+
+pub use ast::expression::Expression;
+pub use ast::parser::Parser;
+pub use interpreter::interpreter::Interpreter;
+pub use lexer::lexer::Lexer;
+pub use lexer::token::{Token, TokenType};
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use ast::error::ParserError;
+use ast::statement::Statement;
+use interpreter::error::RuntimeError;
+use interpreter::resolver;
+use lexer::error::LexError;
+
+/// Why source couldn't be run to the end.
+#[derive(Debug)]
+pub enum OliError {
+    /// Lexing, parsing or resolving failed, so nothing ran, or the source
+    /// can't be formatted. Holds every error found, one per line.
+    Compile(String),
+    Runtime(RuntimeError),
+}
+
+impl From<Vec<LexError>> for OliError {
+    fn from(errors: Vec<LexError>) -> Self {
+        OliError::Compile(join_lines(&errors))
+    }
+}
+
+impl From<Vec<ParserError>> for OliError {
+    fn from(errors: Vec<ParserError>) -> Self {
+        OliError::Compile(join_lines(&errors))
+    }
+}
+
+impl From<RuntimeError> for OliError {
+    fn from(error: RuntimeError) -> Self {
+        OliError::Runtime(error)
+    }
+}
+
+impl std::fmt::Display for OliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OliError::Compile(message) => write!(f, "{}", message),
+            OliError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for OliError {}
+
+fn join_lines<T: ToString>(errors: &[T]) -> String {
+    errors
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Reads source to its end, as from a script or stdin. Source has to be
+/// UTF-8, and anything else is an `InvalidData` error saying where it broke.
+pub fn read_source(mut input: impl Read) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid UTF-8 at byte {}", error.utf8_error().valid_up_to()),
+        )
+    })
+}
+
+/// The source's tokens, ending with EOF.
+pub fn lex(source: &str) -> Result<Vec<Token>, OliError> {
+    Ok(Lexer::new(source).scan_tokens()?)
+}
+
+/// The source's statements, as written: nothing is resolved or folded.
+pub fn parse(source: &str) -> Result<Vec<Statement>, OliError> {
+    Ok(Parser::new(lex(source)?).parse_program()?)
+}
+
+/// The source formatted as `oli --fmt` prints it. Comments would be lost,
+/// so source with any is refused, the error pointing at the first.
+pub fn format(source: &str) -> Result<String, OliError> {
+    let tokens = Lexer::new_with_trivia(source).scan_tokens()?;
+    if let Some(comment) = tokens
+        .iter()
+        .find(|token| token.token_type == TokenType::Comment)
+    {
+        return Err(OliError::Compile(format!(
+            "[line {}] Can't format source with comments: they would be lost.",
+            comment.line_number
+        )));
+    }
+    Ok(ast::format::format_program(&parse(source)?))
+}
+
+/// Runs a program on `interpreter`, whose globals it can use and add to.
+/// Statements before a runtime error have already run when it's returned.
+pub fn run(interpreter: &mut Interpreter, source: &str) -> Result<(), OliError> {
+    let mut statements = parse(source)?;
+    resolver::resolve(&mut statements)?;
+    for statement in &statements {
+        interpreter.execute(statement)?;
+    }
+    Ok(())
+}
+
+/// Runs a program on a fresh interpreter, giving back the lines it printed.
+/// Recursion that goes too deep for a thread with the default stack stops
+/// with a stack overflow error; see `DEFAULT_STACK_LIMIT`.
+pub fn run_source(source: &str) -> Result<Vec<String>, OliError> {
+    let output = SharedBuffer::default();
+    run(&mut Interpreter::with_output(output.clone()), source)?;
+    Ok(output.text().lines().map(String::from).collect())
+}
+
+/// An output for an interpreter that can still be read once the interpreter
+/// has it, through a clone.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Everything written so far.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_source() {
+        assert_eq!(
+            run_source("var total = 0;\nfor (var i = 1; i <= 3; i = i + 1) {\n  total = total + i;\n  say total;\n}").unwrap(),
+            vec!["1", "3", "6"]
+        );
+        assert_eq!(run_source("var x = 1;").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_read_source() {
+        assert_eq!(read_source("say 1;".as_bytes()).unwrap(), "say 1;");
+
+        let empty = read_source(io::empty()).unwrap();
+        assert_eq!(empty, "");
+        assert_eq!(run_source(&empty).unwrap(), Vec::<String>::new());
+
+        let error = read_source(&b"say \"caf\xe9\";"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid UTF-8 at byte 8");
+    }
+
+    #[test]
+    fn test_run_source_errors() {
+        assert_eq!(
+            run_source("say (;\nsay );").unwrap_err().to_string(),
+            "[line 1] Error at ';': Expected expression.\n[line 2] Error at ')': Expected expression."
+        );
+        assert!(matches!(
+            run_source("say \"a\";\nsay -\"a\";"),
+            Err(OliError::Runtime(error)) if error.token.line_number == 2
+        ));
+
+        // An output of the caller's own keeps the lines printed before the
+        // error, and only those.
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        assert!(run(&mut interpreter, "say 1;\nsay 2 + Nil;\nsay 3;").is_err());
+        assert_eq!(output.text(), "1\n");
+    }
+}
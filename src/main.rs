@@ -1,33 +1,223 @@
-mod ast;
-mod lexer;
+mod repl;
 
 use std::env;
 use std::fs;
 use std::io::{self, BufRead, Write};
 use std::process::exit;
+use std::thread;
 
-use ast::parser::Parser;
-use lexer::lexer::Lexer;
+use oli::ast::parser::ReplLine;
+use oli::ast::rpn::RpnPrinter;
+use oli::ast::{dot, optimize};
+use oli::interpreter::resolver;
+use oli::lexer::token;
+use oli::{Interpreter, OliError, Parser};
+use repl::ReplHistory;
 
-fn run_file(path: &str) -> Result<(), String> {
-    match fs::read_to_string(path) {
-        Err(msg) => Err(msg.to_string()),
-        Ok(contents) => run(&contents),
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Run,
+    Fold,
+    Dot,
+    AstJson,
+    Rpn,
+    Format,
+    Tokens,
+    TokensJson,
+}
+
+/// Why a script stopped early, told apart so the CLI can exit with the
+/// conventional code for each.
+#[derive(Debug)]
+enum RunError {
+    /// The script couldn't be read.
+    Io(String),
+    Oli(OliError),
+}
+
+impl RunError {
+    /// The exit code from BSD's `sysexits.h` that fits the error.
+    fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Io(_) => 66,
+            RunError::Oli(OliError::Compile(_)) => 65,
+            RunError::Oli(OliError::Runtime(_)) => 70,
+        }
+    }
+}
+
+impl From<OliError> for RunError {
+    fn from(error: OliError) -> Self {
+        RunError::Oli(error)
+    }
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunError::Io(message) => write!(f, "{}", message),
+            RunError::Oli(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Reads the script at `path`, or stdin to its end if the path is `-`.
+fn read_script(path: &str) -> Result<String, RunError> {
+    let (name, contents) = if path == "-" {
+        ("stdin".to_string(), oli::read_source(io::stdin().lock()))
+    } else {
+        (
+            format!("'{}'", path),
+            fs::File::open(path).and_then(oli::read_source),
+        )
+    };
+    contents.map_err(|error| RunError::Io(format!("Could not read {}: {}", name, error)))
+}
+
+fn run_file(path: &str, mode: Mode) -> Result<(), RunError> {
+    let contents = read_script(path)?;
+    match mode {
+        Mode::Run => Ok(oli::run(&mut interpreter(), &contents)?),
+        Mode::Fold => {
+            let statements = oli::parse(&contents)?;
+            for statement in optimize::fold_statements(statements) {
+                println!("{}", statement);
+            }
+            Ok(())
+        }
+        Mode::Dot => {
+            let statements = oli::parse(&contents)?;
+            print!("{}", dot::program_to_dot(&statements));
+            Ok(())
+        }
+        Mode::AstJson => {
+            let statements = oli::parse(&contents)?;
+            let objects: Vec<String> = statements.iter().map(|s| s.to_json()).collect();
+            println!("[{}]", objects.join(","));
+            Ok(())
+        }
+        Mode::Rpn => {
+            for statement in oli::parse(&contents)? {
+                println!("{}", RpnPrinter.print_statement(&statement));
+            }
+            Ok(())
+        }
+        Mode::Format => {
+            print!("{}", oli::format(&contents)?);
+            Ok(())
+        }
+        Mode::Tokens => {
+            print!("{}", format_tokens(&contents)?);
+            Ok(())
+        }
+        Mode::TokensJson => {
+            println!("{}", token::tokens_to_json(&oli::lex(&contents)?));
+            Ok(())
+        }
+    }
+}
+
+/// Renders the token stream one token per line, for `--tokens`.
+fn format_tokens(contents: &str) -> Result<String, OliError> {
+    let mut output = String::new();
+    for token in oli::lex(contents)? {
+        output.push_str(&token.to_string());
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Runs a line typed at the REPL, which may be a bare expression. An
+/// expression's value is echoed to the interpreter's output, where `say`
+/// prints, so whatever captures one captures both. As with `say`, a failed
+/// write isn't the line's error.
+///
+/// The newline that ended the input is dropped first, so an error at its end
+/// is reported on the last line typed rather than the empty one after it.
+fn run_line(interpreter: &mut Interpreter, line: &str) -> Result<(), OliError> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let mut parser = Parser::new(oli::lex(line)?);
+    match parser.parse_repl_line()? {
+        ReplLine::Statements(mut statements) => {
+            resolver::resolve(&mut statements)?;
+            for statement in &statements {
+                interpreter.execute(statement)?;
+            }
+        }
+        ReplLine::Expression(mut expression) => {
+            resolver::resolve_expression(&mut expression)?;
+            let value = interpreter.evaluate(&expression)?;
+            let _ = writeln!(interpreter.output(), "{}", value.stringify(true));
+        }
     }
+    Ok(())
 }
 
-fn run(contents: &str) -> Result<(), String> {
-    let mut lexer = Lexer::new(contents);
-    let tokens = lexer.scan_tokens()?;
-    let mut parser = Parser::new(tokens);
-    let expression = parser.parse()?;
-    println!("{}", expression.to_string());
-    return Ok(());
+/// What a REPL command other than `:quit` prints, or the error to report
+/// instead. A replayed input is run as if typed, with nothing added.
+fn run_command(
+    interpreter: &mut Interpreter,
+    history: &ReplHistory,
+    command: repl::Command,
+) -> Result<String, String> {
+    let result = match command {
+        repl::Command::Tokens(code) => format_tokens(code)
+            .map(|tokens| tokens.trim_end().to_string())
+            .map_err(|error| error.to_string()),
+        repl::Command::Ast(code) => oli::lex(code)
+            .and_then(|tokens| match Parser::new(tokens).parse_repl_line()? {
+                ReplLine::Statements(statements) => Ok(statements
+                    .iter()
+                    .map(|statement| statement.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n")),
+                ReplLine::Expression(expression) => Ok(expression.to_string()),
+            })
+            .map_err(|error| error.to_string()),
+        repl::Command::Env => Ok(interpreter
+            .globals()
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value.stringify(true)))
+            .collect::<Vec<String>>()
+            .join("\n")),
+        repl::Command::History => Ok(history.list()),
+        repl::Command::Replay(Some(number)) => match history.get(number) {
+            Some(input) => match repl::classify(input) {
+                repl::Line::Command(command) => run_command(interpreter, history, command),
+                _ => run_line(interpreter, input)
+                    .map(|()| String::new())
+                    .map_err(|error| error.to_string()),
+            },
+            None => Err(format!(
+                "No input {} in the history. Type :history for a list.",
+                number
+            )),
+        },
+        repl::Command::Replay(None) => Err("Usage: :replay <n>".to_string()),
+        repl::Command::Help | repl::Command::Quit => Ok(repl::HELP.to_string()),
+        repl::Command::Unknown(name) => Err(format!(
+            "Unknown command ':{}'. Type :help for a list.",
+            name
+        )),
+    };
+    result.map_err(|error| error.trim_end().to_string())
 }
 
+/// Reads and runs lines until the input ends or `:quit`. One interpreter
+/// lasts the whole session, and an error only ends the input it came from.
+///
+/// Input with an unclosed bracket, string or comment carries on over more
+/// lines, prompted with `..`, until it's complete. A blank line abandons it.
+///
+/// Each complete input and command goes into the history, except those that
+/// only look at the history. A replayed input goes in again, as if retyped.
 fn run_prompt() -> Result<(), String> {
+    let mut interpreter = interpreter();
+    let mut history = ReplHistory::default();
+    let mut pending = String::new();
     loop {
-        print!("> ");
+        print!("{}", if pending.is_empty() { "> " } else { ".. " });
         match io::stdout().flush() {
             Ok(_) => (),
             Err(_) => return Err("Could not flush stdout".to_string()),
@@ -37,42 +227,313 @@ fn run_prompt() -> Result<(), String> {
         let stdin = io::stdin();
         let mut handle = stdin.lock();
         match handle.read_line(&mut buffer) {
-            Ok(n) => {
-                if n <= 1 {
-                    return Ok(());
-                }
+            // End of input, as from Ctrl-D, so finish the prompt's line.
+            Ok(0) => {
+                println!();
+                return Ok(());
             }
+            Ok(_) => (),
             Err(_) => return Err("Couldnt read line".to_string()),
         }
-        println!("ECHO {}", buffer);
-        match run(&buffer) {
-            Ok(_) => (),
-            Err(msg) => println!("{}", msg),
+
+        if !pending.is_empty() {
+            if buffer.trim().is_empty() {
+                pending.clear();
+                continue;
+            }
+        } else {
+            match repl::classify(&buffer) {
+                repl::Line::Empty => continue,
+                repl::Line::Command(repl::Command::Quit) => return Ok(()),
+                repl::Line::Command(command) => {
+                    let replayed = match command {
+                        repl::Command::History => None,
+                        repl::Command::Replay(number) => {
+                            let input = number.and_then(|number| history.get(number));
+                            // Echo the input so it's clear what ran.
+                            if let Some(input) = input {
+                                println!("{}", input);
+                            }
+                            input.map(str::to_string)
+                        }
+                        _ => Some(buffer.clone()),
+                    };
+                    match run_command(&mut interpreter, &history, command) {
+                        Ok(output) if output.is_empty() => (),
+                        Ok(output) => println!("{}", output),
+                        Err(error) => eprintln!("{}", error),
+                    }
+                    if let Some(input) = replayed {
+                        history.push(&input);
+                    }
+                    continue;
+                }
+                repl::Line::Program(_) => (),
+            }
+        }
+
+        pending.push_str(&buffer);
+        if !repl::is_complete(&pending) {
+            continue;
+        }
+        let source = std::mem::take(&mut pending);
+        history.push(&source);
+        if let Err(msg) = run_line(&mut interpreter, &source) {
+            eprintln!("{}", msg);
         }
     }
 }
 
+/// The deepest recursion the interpreter allows takes more stack than the
+/// main thread has, at least unoptimised, so it runs on a thread of its own.
+/// See `MAX_CALL_DEPTH`.
+const STACK_SIZE: usize = 32 * 1024 * 1024;
+
+/// How much of that thread's stack a program's calls may take, leaving the
+/// rest for the expressions between one call and the next.
+const STACK_LIMIT: usize = STACK_SIZE / 2;
+
+/// An interpreter whose calls may take as much stack as the CLI's thread
+/// can spare.
+fn interpreter() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_stack_limit(STACK_LIMIT);
+    interpreter
+}
+
+const USAGE: &str = "\
+Usage: oli [--tokens | --tokens-json | --fold | --dot | --ast-json | --rpn | --fmt] [script | -]
+
+Runs the script, or stdin for `-`, or starts a REPL given neither. A flag
+prints the script another way instead of running it:
+  --tokens       its tokens, one per line
+  --tokens-json  its tokens as JSON
+  --fold         its statements with constant expressions folded
+  --dot          its syntax tree as a Graphviz digraph
+  --ast-json     its syntax tree as JSON
+  --rpn          its expressions in Reverse Polish Notation
+  --fmt          the script reformatted; comments would be lost, so a
+                 script with any is refused";
+
 fn main() {
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("Could not start the interpreter thread");
+
+    if cli.join().is_err() {
+        exit(70);
+    }
+}
+
+fn cli() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() > 2 {
-        println!("Usage: oli [script]");
-        exit(64);
-    } else if args.len() == 2 {
-        match run_file(&args[1]) {
+    let file = match args.as_slice() {
+        [_] => None,
+        [_, flag, path] if flag == "--tokens" => Some((path, Mode::Tokens)),
+        [_, flag, path] if flag == "--tokens-json" => Some((path, Mode::TokensJson)),
+        [_, flag, path] if flag == "--fold" => Some((path, Mode::Fold)),
+        [_, flag, path] if flag == "--dot" => Some((path, Mode::Dot)),
+        [_, flag, path] if flag == "--ast-json" => Some((path, Mode::AstJson)),
+        [_, flag, path] if flag == "--rpn" => Some((path, Mode::Rpn)),
+        [_, flag, path] if flag == "--fmt" => Some((path, Mode::Format)),
+        [_, path] if !path.starts_with("--") => Some((path, Mode::Run)),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(64);
+        }
+    };
+
+    if let Some((path, mode)) = file {
+        match run_file(path, mode) {
             Ok(_) => exit(0),
-            Err(msg) => {
-                println!("ERROR:\n{}", msg);
-                exit(1);
+            Err(error) => {
+                eprintln!("{}", error);
+                exit(error.exit_code());
             }
         }
     } else {
         match run_prompt() {
             Ok(_) => exit(0),
             Err(msg) => {
-                println!("ERROR\n{}", msg);
-                exit(1);
+                eprintln!("{}", msg);
+                exit(74);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oli::SharedBuffer;
+
+    /// Runs each line in one session, as typed at the prompt, collecting
+    /// the lines it prints and the errors it reports, in order.
+    fn session(lines: &[&str]) -> Vec<String> {
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        let mut printed = Vec::new();
+        for line in lines {
+            let before = output.text().len();
+            match run_line(&mut interpreter, line) {
+                Ok(()) => printed.extend(output.text()[before..].lines().map(String::from)),
+                Err(error) => printed.push(error.to_string()),
+            }
+        }
+        printed
+    }
+
+    #[test]
+    fn test_session() {
+        assert_eq!(
+            session(&[
+                "var x = 3;",
+                "x * 2",
+                "fun twice(n) { return n * 2; }",
+                "twice(x) + 1"
+            ]),
+            vec!["6", "7"]
+        );
+        assert_eq!(session(&["\"a\" + 1", "Nil"]), vec!["\"a1\"", "Nil"]);
+    }
+
+    fn command(
+        interpreter: &mut Interpreter,
+        history: &ReplHistory,
+        line: &str,
+    ) -> Result<String, String> {
+        match repl::classify(line) {
+            repl::Line::Command(command) => run_command(interpreter, history, command),
+            line => panic!("Expected a command, got {:?}", line),
+        }
+    }
+
+    fn ok(output: &str) -> Result<String, String> {
+        Ok(output.to_string())
+    }
+
+    fn err(error: &str) -> Result<String, String> {
+        Err(error.to_string())
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut interpreter = Interpreter::new();
+        let history = ReplHistory::default();
+        assert_eq!(command(&mut interpreter, &history, ":env"), ok(""));
+
+        run_line(&mut interpreter, "var name = \"oli\";").unwrap();
+        run_line(&mut interpreter, "const answer = 6 * 7;").unwrap();
+        let mut command = |line: &str| command(&mut interpreter, &history, line);
+        assert_eq!(command(":env"), ok("answer = 42\nname = \"oli\""));
+
+        assert_eq!(
+            command(":tokens x = 1"),
+            ok("IDENTIFIER 'x' [line 1]\nEQUAL '=' [line 1]\nNUMBER '1' [line 1]\nEOF '' [line 1]")
+        );
+        assert_eq!(command(":ast 1 + 2 * x"), ok("(+ 1 (* 2 x))"));
+        assert_eq!(command(":ast say -x;"), ok("(say (- x))"));
+        assert_eq!(
+            command(":ast say (;"),
+            err("[line 1] Error at ';': Expected expression.")
+        );
+        assert!(command(":help").unwrap().contains(":env"));
+        assert_eq!(
+            command(":what"),
+            err("Unknown command ':what'. Type :help for a list.")
+        );
+    }
+
+    #[test]
+    fn test_history_commands() {
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        let mut history = ReplHistory::default();
+        for input in ["var x = 1;", "x = x + 1;", ":env", "x * 10"] {
+            history.push(input);
+        }
+        run_line(&mut interpreter, "var x = 1;").unwrap();
+        run_line(&mut interpreter, "x = x + 1;").unwrap();
+        assert_eq!(
+            command(&mut interpreter, &history, ":history"),
+            ok("    1  var x = 1;\n    2  x = x + 1;\n    3  :env\n    4  x * 10")
+        );
+
+        assert_eq!(command(&mut interpreter, &history, "!2"), ok(""));
+        assert_eq!(command(&mut interpreter, &history, ":replay 4"), ok(""));
+        assert_eq!(output.text(), "30\n");
+        assert_eq!(command(&mut interpreter, &history, "!3"), ok("x = 3"));
+        assert_eq!(
+            command(&mut interpreter, &history, "!9"),
+            err("No input 9 in the history. Type :history for a list.")
+        );
+        assert_eq!(
+            command(&mut interpreter, &history, ":replay last"),
+            err("Usage: :replay <n>")
+        );
+
+        history.push("x + Nil");
+        assert_eq!(
+            command(&mut interpreter, &history, "!5"),
+            err("[line 1] Can't add number and nil.")
+        );
+    }
+
+    #[test]
+    fn test_session_survives_errors() {
+        assert_eq!(
+            session(&["var x = 1;", "x + Nil", "y", "var = 2;", "x = x + 1;", "x"]),
+            vec![
+                "[line 1] Can't add number and nil.",
+                "[line 1] Undefined variable 'y'.",
+                "[line 1] Error at '=': Expected variable name after 'var'.",
+                "2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_at_end_of_input() {
+        // Input comes with the newline that ended it, but that isn't a line
+        // of its own.
+        assert_eq!(
+            session(&["1 +\n", "var s = \"a\nb\" +\n"]),
+            vec![
+                "[line 1] Error at end: Unexpected end of input.",
+                "[line 2] Error at end: Unexpected end of input.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_tokens() {
+        let output = format_tokens("var count = 1;\nsay count;").unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "VARIABLE 'var' [line 1]");
+        assert_eq!(lines[1], "IDENTIFIER 'count' [line 1]");
+        assert_eq!(lines[4], "SEMI_COLON ';' [line 1]");
+        assert_eq!(lines[5], "SAY 'say' [line 2]");
+        assert_eq!(lines[8], "EOF '' [line 2]");
+    }
+
+    #[test]
+    fn test_tokens_json() {
+        let json = token::tokens_to_json(&oli::lex("say \"hi\\n\";").unwrap());
+
+        assert!(json.starts_with('[') && json.ends_with(']'));
+        assert_eq!(json.matches("\"type\":").count(), 4);
+        assert!(json.contains(
+            r#"{"type":"StringLiteral","lexeme":"\"hi\\n\"","literal":"hi\n","line":1,"span":[4,10]}"#
+        ));
+        assert!(json.contains(r#""type":"Eof""#));
+    }
+
+    #[test]
+    fn test_format_tokens_error() {
+        assert!(format_tokens("var @").is_err());
+    }
+}
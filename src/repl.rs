@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+
+/// What a line typed at the REPL asks for.
+#[derive(Debug, PartialEq)]
+pub enum Line<'a> {
+    /// Nothing but whitespace, which just prompts again.
+    Empty,
+    Command(Command<'a>),
+    /// Code to run.
+    Program(&'a str),
+}
+
+/// A line starting with `:`, which the REPL handles itself.
+#[derive(Debug, PartialEq)]
+pub enum Command<'a> {
+    /// Prints the tokens the code lexes to.
+    Tokens(&'a str),
+    /// Prints the tree the code parses to.
+    Ast(&'a str),
+    /// Lists the session's variables.
+    Env,
+    Help,
+    /// Lists earlier inputs, numbered.
+    History,
+    /// Runs an earlier input again by number, or `None` if what followed
+    /// `:replay` wasn't one.
+    Replay(Option<usize>),
+    Quit,
+    /// Anything else, by the name typed.
+    Unknown(&'a str),
+}
+
+pub const HELP: &str = "\
+:tokens <code>  print the tokens the code lexes to
+:ast <code>     print the tree the code parses to
+:env            list the variables defined so far
+:history        list earlier inputs, numbered
+:replay <n>     run input number n again, as does !n
+:help           show this list
+:quit           leave the REPL, as do `exit` and Ctrl-D";
+
+/// Commands start with `:`, and `exit` on its own is `:quit` for those who
+/// type it by habit. `!n` replays input `n`, as in a shell; `!` followed by
+/// anything but digits is code.
+pub fn classify(line: &str) -> Line<'_> {
+    let line = line.trim();
+    if let Some(number) = line.strip_prefix('!').and_then(|n| n.parse().ok()) {
+        return Line::Command(Command::Replay(Some(number)));
+    }
+    match line {
+        "" => Line::Empty,
+        "exit" => Line::Command(Command::Quit),
+        _ => match line.strip_prefix(':') {
+            Some(command) => Line::Command(parse_command(command)),
+            None => Line::Program(line),
+        },
+    }
+}
+
+/// Parses what follows the `:` of a command.
+pub fn parse_command(command: &str) -> Command<'_> {
+    let command = command.trim();
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command, ""),
+    };
+    match name {
+        "tokens" => Command::Tokens(argument),
+        "ast" => Command::Ast(argument),
+        "env" => Command::Env,
+        "help" => Command::Help,
+        "history" => Command::History,
+        "replay" => Command::Replay(argument.parse().ok()),
+        "quit" => Command::Quit,
+        name => Command::Unknown(name),
+    }
+}
+
+/// Whether `source` is ready to run, or is still waiting on a closing
+/// bracket, quote or `*/`. This skims the source the way the lexer reads it,
+/// so brackets inside strings and comments don't count. A stray closing
+/// bracket counts as ready, for the parser to report.
+pub fn is_complete(source: &str) -> bool {
+    let mut depth: usize = 0;
+    let mut chars = source.chars().peekable();
+    let mut previous = ' ';
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return true,
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n' && c != '\r').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut comments = 1;
+                while comments > 0 {
+                    match (chars.next(), chars.peek()) {
+                        (None, _) => return false,
+                        (Some('/'), Some('*')) => {
+                            chars.next();
+                            comments += 1;
+                        }
+                        (Some('*'), Some('/')) => {
+                            chars.next();
+                            comments -= 1;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            // `r"..."` has no escapes, but an `r` ending a name isn't one.
+            'r' if chars.peek() == Some(&'"') && !is_name_char(previous) => {
+                chars.next();
+                if !chars.any(|c| c == '"') {
+                    return false;
+                }
+            }
+            '"' | '\'' => loop {
+                match chars.next() {
+                    None => return false,
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some(quote) if quote == c => break,
+                    Some(_) => (),
+                }
+            },
+            _ => (),
+        }
+        previous = c;
+    }
+    depth == 0
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// How many inputs the REPL remembers.
+pub const HISTORY_SIZE: usize = 500;
+
+/// The most recent inputs of a session, numbered from 1 in the order they
+/// were entered. Numbers stay the same as old inputs are forgotten, so `!3`
+/// means the same thing until input 3 is gone.
+pub struct ReplHistory {
+    entries: VecDeque<String>,
+    capacity: usize,
+    /// How many inputs have been forgotten to stay within `capacity`.
+    forgotten: usize,
+}
+
+impl Default for ReplHistory {
+    fn default() -> Self {
+        Self::new(HISTORY_SIZE)
+    }
+}
+
+impl ReplHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            forgotten: 0,
+        }
+    }
+
+    /// Remembers an input, unless it's blank or the same as the one before.
+    pub fn push(&mut self, input: &str) {
+        let input = input.trim();
+        if input.is_empty() || self.entries.back().is_some_and(|last| last == input) {
+            return;
+        }
+        self.entries.push_back(input.to_string());
+        if self.entries.len() > self.capacity {
+            self.entries.pop_front();
+            self.forgotten += 1;
+        }
+    }
+
+    /// Input number `number`, if it's still remembered.
+    pub fn get(&self, number: usize) -> Option<&str> {
+        number
+            .checked_sub(self.forgotten + 1)
+            .and_then(|index| self.entries.get(index))
+            .map(String::as_str)
+    }
+
+    /// Every remembered input with its number, one per line. Later lines of
+    /// a multi-line input are indented to line up with its first.
+    pub fn list(&self) -> String {
+        let mut lines = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let number = self.forgotten + index + 1;
+            let entry = entry.replace('\n', "\n       ");
+            lines.push(format!("{:5}  {}", number, entry));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("tokens say 1;"), Command::Tokens("say 1;"));
+        assert_eq!(parse_command("ast   1 + 2 "), Command::Ast("1 + 2"));
+        assert_eq!(parse_command("tokens"), Command::Tokens(""));
+        assert_eq!(parse_command("env"), Command::Env);
+        assert_eq!(parse_command("help"), Command::Help);
+        assert_eq!(parse_command("quit"), Command::Quit);
+        assert_eq!(parse_command("envv x"), Command::Unknown("envv"));
+        assert_eq!(parse_command(""), Command::Unknown(""));
+        assert_eq!(parse_command("history"), Command::History);
+        assert_eq!(parse_command("replay 12"), Command::Replay(Some(12)));
+        assert_eq!(parse_command("replay x"), Command::Replay(None));
+        assert_eq!(parse_command("replay"), Command::Replay(None));
+    }
+
+    #[test]
+    fn test_classify_replay() {
+        assert_eq!(classify("!3\n"), Line::Command(Command::Replay(Some(3))));
+        assert_eq!(classify("!x"), Line::Program("!x"));
+        assert_eq!(classify("!(3)"), Line::Program("!(3)"));
+    }
+
+    #[test]
+    fn test_history_numbering() {
+        let mut history = ReplHistory::default();
+        history.push("var x = 1;\n");
+        history.push("   ");
+        history.push("x + 1");
+        history.push("x + 1\n");
+        history.push("var x = 1;");
+        assert_eq!(history.get(0), None);
+        assert_eq!(history.get(1), Some("var x = 1;"));
+        assert_eq!(history.get(2), Some("x + 1"));
+        assert_eq!(history.get(3), Some("var x = 1;"));
+        assert_eq!(history.get(4), None);
+        assert_eq!(
+            history.list(),
+            "    1  var x = 1;\n    2  x + 1\n    3  var x = 1;"
+        );
+    }
+
+    #[test]
+    fn test_history_capacity() {
+        let mut history = ReplHistory::new(2);
+        for input in ["a", "b", "c"] {
+            history.push(input);
+        }
+        assert_eq!(history.get(1), None);
+        assert_eq!(history.get(2), Some("b"));
+        assert_eq!(history.get(3), Some("c"));
+
+        history.push("while (True) {\n  say 1;\n}");
+        assert_eq!(
+            history.list(),
+            "    3  c\n    4  while (True) {\n         say 1;\n       }"
+        );
+    }
+
+    #[test]
+    fn test_is_complete_brackets() {
+        assert!(is_complete("say 1;"));
+        assert!(!is_complete("while (x < 3) {"));
+        assert!(!is_complete("while (x < 3) {\n  if (f(x[0]) {\n"));
+        assert!(!is_complete("var a = [1,\n 2"));
+        assert!(is_complete("while (x < 3) {\n  x = [f(x)][0];\n}"));
+        assert!(is_complete("}"));
+    }
+
+    #[test]
+    fn test_is_complete_strings() {
+        assert!(is_complete("say \"{\";"));
+        assert!(is_complete("say '(' + \"\\\"[\";"));
+        assert!(!is_complete("say \"unfinished {"));
+        assert!(!is_complete("say 'it\\'s"));
+        assert!(is_complete("say r\"\\\";"));
+        assert!(!is_complete("say r\"{"));
+        assert!(!is_complete("say bar\"x"));
+    }
+
+    #[test]
+    fn test_is_complete_comments() {
+        assert!(is_complete("say 1; // {"));
+        assert!(!is_complete("// }\nfun f() {"));
+        assert!(is_complete("/* { /* ( */ [ */ say 1;"));
+        assert!(!is_complete("say 1; /* still /* going */"));
+        assert!(is_complete("say 4 / 2;"));
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("\n"), Line::Empty);
+        assert_eq!(classify("   \t \r\n"), Line::Empty);
+        assert_eq!(classify(":quit\n"), Line::Command(Command::Quit));
+        assert_eq!(classify("  exit  \n"), Line::Command(Command::Quit));
+        assert_eq!(classify(": help"), Line::Command(Command::Help));
+        assert_eq!(classify(" say 1;\n"), Line::Program("say 1;"));
+        assert_eq!(classify("exit()"), Line::Program("exit()"));
+    }
+}
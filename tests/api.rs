@@ -0,0 +1,140 @@
+use oli::{Expression, Lexer, OliError, Parser, TokenType};
+
+#[test]
+fn test_lex() {
+    let tokens = Lexer::new("say 1 + x;").scan_tokens().unwrap();
+    let types: Vec<TokenType> = tokens.iter().map(|token| token.token_type).collect();
+    assert_eq!(
+        types,
+        vec![
+            TokenType::Say,
+            TokenType::Number,
+            TokenType::Plus,
+            TokenType::Identifier,
+            TokenType::SemiColon,
+            TokenType::Eof,
+        ]
+    );
+}
+
+#[test]
+fn test_parse() {
+    let tokens = Lexer::new("1 + 2 * 3").scan_tokens().unwrap();
+    let expression = Parser::new(tokens).parse_expression().unwrap();
+    match &expression {
+        Expression::Binary { operator, .. } => assert_eq!(operator.token_type, TokenType::Plus),
+        expression => panic!("Expected a binary expression, got {}", expression),
+    }
+    assert_eq!(expression.to_string(), "(+ 1 (* 2 3))");
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(
+        oli::format("var x=1;say x+1;").unwrap(),
+        "var x = 1;\nsay x + 1;\n"
+    );
+
+    // The formatter would drop comments, so it won't take source with any.
+    let error = oli::format("var x = 1;\nsay x; // two\n").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "[line 2] Can't format source with comments: they would be lost."
+    );
+    assert!(oli::format("/* */").is_err());
+    assert_eq!(
+        oli::format("say \"// not a comment\";").unwrap(),
+        "say \"// not a comment\";\n"
+    );
+}
+
+#[test]
+fn test_run_source() {
+    let source = "
+        class Counter {
+            init() { this.count = 0; }
+            tick() { this.count = this.count + 1; return this.count; }
+        }
+        var counter = Counter();
+        while (counter.tick() < 3) say \"tick \" + str(counter.count);
+        say counter.count;
+    ";
+    assert_eq!(
+        oli::run_source(source).unwrap(),
+        vec!["tick 1", "tick 2", "3"]
+    );
+}
+
+#[test]
+fn test_run_source_errors() {
+    assert!(matches!(
+        oli::run_source("say 1 +;"),
+        Err(OliError::Compile(_))
+    ));
+    let error = oli::run_source("say Nil.x;").unwrap_err();
+    assert!(matches!(error, OliError::Runtime(_)));
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Only instances have properties."
+    );
+}
+
+/// Runs on the test thread's default stack, as a library user would, at the
+/// deepest nesting the parser accepts.
+#[test]
+fn test_deep_expressions() {
+    let parens = format!("say {}1{};", "(".repeat(255), ")".repeat(255));
+    assert_eq!(oli::run_source(&parens).unwrap(), vec!["1"]);
+
+    let negations = format!("say {}1;", "-".repeat(254));
+    assert_eq!(oli::run_source(&negations).unwrap(), vec!["1"]);
+
+    let sum = format!("say {}1;", "1 + ".repeat(254));
+    assert_eq!(oli::run_source(&sum).unwrap(), vec!["255"]);
+}
+
+/// An operator chain can be any length, though it nests as deep as it's
+/// long, and still runs on the test thread's default stack.
+#[test]
+fn test_long_chains() {
+    let sum = format!("1{}", " + 1".repeat(100_000));
+    assert_eq!(
+        oli::run_source(&format!("say {};", sum)).unwrap(),
+        vec!["100001"]
+    );
+    assert_eq!(
+        oli::run_source(&format!("fun f() {{ return {}; }}\nsay f();", sum)).unwrap(),
+        vec!["100001"]
+    );
+
+    let logic = format!("say Nil{} or \"last\";", " or False and x".repeat(100_000));
+    assert_eq!(oli::run_source(&logic).unwrap(), vec!["last"]);
+
+    let json = oli::parse(&format!("say {};", sum)).unwrap()[0].to_json();
+    let tail = r#","right":{"type":"literal","line":1,"value":1}}"#.repeat(100_000);
+    assert!(json.ends_with(&format!("{}}}", tail)));
+}
+
+/// Runaway recursion is an error, not a crash, even on a thread with the
+/// default stack.
+#[test]
+fn test_runaway_recursion() {
+    let result = std::thread::spawn(|| {
+        oli::run_source("fun f() { f(); }\nf();").map_err(|error| error.to_string())
+    })
+    .join()
+    .unwrap();
+    assert_eq!(result, Err("[line 1] Stack overflow.".to_string()));
+
+    // Deeply nested expressions between the calls stop it too.
+    let nested = format!(
+        "fun f() {{ return {}f(){}; }}\nsay f();",
+        "(".repeat(250),
+        ")".repeat(250)
+    );
+    let result =
+        std::thread::spawn(move || oli::run_source(&nested).map_err(|error| error.to_string()))
+            .join()
+            .unwrap();
+    assert_eq!(result, Err("[line 1] Stack overflow.".to_string()));
+}
@@ -0,0 +1,176 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Writes `source` to a script named `name` and runs the `oli` binary on it.
+fn run_script(name: &str, source: &str) -> Output {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_oli"))
+        .arg(&path)
+        .output()
+        .unwrap()
+}
+
+fn text(stream: &[u8]) -> String {
+    String::from_utf8_lossy(stream).into_owned()
+}
+
+#[test]
+fn test_success() {
+    let output = run_script("success.oli", "say 1 + 2;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "3\n");
+    assert_eq!(text(&output.stderr), "");
+}
+
+#[test]
+fn test_compile_error() {
+    let output = run_script("compile_error.oli", "say \"before\";\nvar = 1;");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(text(&output.stdout), "");
+    assert_eq!(
+        text(&output.stderr),
+        "[line 2] Error at '=': Expected variable name after 'var'.\n"
+    );
+}
+
+#[test]
+fn test_runtime_error() {
+    let output = run_script("runtime_error.oli", "say \"before\";\nsay -\"x\";");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(text(&output.stdout), "before\n");
+    assert!(text(&output.stderr).starts_with("[line 2]"));
+}
+
+#[test]
+fn test_usage_and_missing_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oli"))
+        .args(["--nonsense", "a", "b"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+    assert_eq!(text(&output.stdout), "");
+    assert!(text(&output.stderr).starts_with("Usage: oli"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oli"))
+        .arg("no/such/script.oli")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(66));
+    assert_eq!(text(&output.stdout), "");
+    assert!(text(&output.stderr).starts_with("Could not read 'no/such/script.oli'"));
+}
+
+/// Runs the `oli` binary with `args`, piping `input` to its stdin.
+fn run_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn run_stdin(input: &[u8]) -> Output {
+    run_with_stdin(&["-"], input)
+}
+
+#[test]
+fn test_stdin() {
+    let output = run_stdin(b"for (var i = 0; i < 2; i = i + 1) say i;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "0\n1\n");
+
+    let output = run_stdin(b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "");
+
+    let output = run_stdin(b"say (;");
+    assert_eq!(output.status.code(), Some(65));
+
+    let output = run_stdin(b"say \"\xff\";");
+    assert_eq!(output.status.code(), Some(66));
+    assert_eq!(
+        text(&output.stderr),
+        "Could not read stdin: invalid UTF-8 at byte 5\n"
+    );
+}
+
+#[test]
+fn test_examples() {
+    for example in ["hello", "fizzbuzz"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_oli"))
+            .arg(format!("examples/{}.oli", example))
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    }
+}
+
+#[test]
+fn test_stack_overflow() {
+    let output = run_script(
+        "stack_overflow.oli",
+        "say \"before\";\nfun f(n) { return f(n + 1); }\nf(0);",
+    );
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(text(&output.stdout), "before\n");
+    assert_eq!(text(&output.stderr), "[line 2] Stack overflow.\n");
+}
+
+#[test]
+fn test_repl_streams() {
+    let output = run_with_stdin(&[], b"var x = 2;\nx * 3\n:what\ny\n!2\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "> > 6\n> > > x * 3\n6\n> \n");
+    assert_eq!(
+        text(&output.stderr),
+        "Unknown command ':what'. Type :help for a list.\n[line 1] Undefined variable 'y'.\n"
+    );
+}
+
+#[test]
+fn test_format_refuses_comments() {
+    let output = run_with_stdin(&["--fmt", "-"], b"say 1; // one\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(text(&output.stdout), "");
+    assert_eq!(
+        text(&output.stderr),
+        "[line 1] Can't format source with comments: they would be lost.\n"
+    );
+}
+
+/// A chain of operators nests as deep as it's long, so every mode has to
+/// handle a tree far deeper than anything could recurse through.
+#[test]
+fn test_long_chain() {
+    let source = format!("say 1{};", " + 1".repeat(100_000));
+    let output = run_with_stdin(&["--fold", "-"], source.as_bytes());
+    assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout), "(say 100001)\n");
+
+    let output = run_with_stdin(&["-"], source.as_bytes());
+    assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout), "100001\n");
+
+    let output = run_with_stdin(&["--rpn", "-"], source.as_bytes());
+    assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    assert!(text(&output.stdout).ends_with(&format!("{} say\n", " 1 +".repeat(100_000))));
+
+    let output = run_with_stdin(&["--dot", "-"], source.as_bytes());
+    assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout).matches(" -> ").count(), 200_001);
+
+    let output = run_with_stdin(&["--fmt", "-"], source.as_bytes());
+    assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout), format!("{}\n", source));
+
+    let output = run_with_stdin(&["--ast-json", "-"], source.as_bytes());
+    assert_eq!(output.status.code(), Some(0), "{}", text(&output.stderr));
+    assert!(text(&output.stdout).starts_with(r#"[{"type":"say","expression":{"type":"binary""#));
+}